@@ -0,0 +1,424 @@
+use crate::hardware::registers::Flags;
+use crate::utils::{check_bit, get_top_bit, is_overflow};
+
+// Pure arithmetic and logic for the cpu. Each op takes its inputs and the flags to
+// update and returns the result, so the cpu only has to deal with where values live
+
+pub fn set_zn(flags: &mut Flags, val: u8) {
+    flags.zero = val == 0;
+    flags.negative = get_top_bit(val);
+}
+
+// Binary mode add, carry in and out through C
+pub fn adc(flags: &mut Flags, a: u8, val: u8) -> u8 {
+    let sum = a as u16 + val as u16 + flags.carry as u16;
+    let res = sum as u8;
+    flags.carry = sum > 0xFF;
+    flags.overflow = is_overflow(res, a, val);
+    set_zn(flags, res);
+    return res;
+}
+
+// A - val - borrow is A + !val + C, which also gives the 6502's inverted borrow in C
+pub fn sbc(flags: &mut Flags, a: u8, val: u8) -> u8 {
+    return adc(flags, a, !val);
+}
+
+// Decimal mode add, ref http://www.6502.org/tutorials/decimal_mode.html.
+// On NMOS parts Z comes from the binary sum and N and V from the sum before the high
+// digit is adjusted. The 65C02 fixes N and Z to match the result, V is the same on both
+pub fn adc_decimal(flags: &mut Flags, a: u8, val: u8, cmos: bool) -> u8 {
+    let carry_in = flags.carry as i16;
+    let mut low = (a & 0x0F) as i16 + (val & 0x0F) as i16 + carry_in;
+    if low >= 0x0A {
+        low = ((low + 0x06) & 0x0F) + 0x10;
+    }
+    let mut sum = (a & 0xF0) as i16 + (val & 0xF0) as i16 + low;
+    let signed = (a & 0xF0) as i8 as i16 + (val & 0xF0) as i8 as i16 + low;
+    flags.overflow = !(-128..=127).contains(&signed);
+    flags.negative = sum & 0x80 != 0;
+    flags.zero = a.wrapping_add(val).wrapping_add(flags.carry as u8) == 0;
+    if sum >= 0xA0 {
+        sum += 0x60;
+    }
+    flags.carry = sum >= 0x100;
+    let res = sum as u8;
+    if cmos {
+        set_zn(flags, res);
+    }
+    return res;
+}
+
+// Decimal mode subtract. NMOS parts set every flag as if the subtraction were binary,
+// the 65C02 takes N and Z from the decimal result
+pub fn sbc_decimal(flags: &mut Flags, a: u8, val: u8, cmos: bool) -> u8 {
+    let borrow = !flags.carry as i16;
+    let mut low = (a & 0x0F) as i16 - (val & 0x0F) as i16 - borrow;
+    let res;
+    if cmos {
+        let mut diff = a as i16 - val as i16 - borrow;
+        if diff < 0 {
+            diff -= 0x60;
+        }
+        if low < 0 {
+            diff -= 0x06;
+        }
+        res = diff as u8;
+    } else {
+        if low < 0 {
+            low = ((low - 0x06) & 0x0F) - 0x10;
+        }
+        let mut diff = (a & 0xF0) as i16 - (val & 0xF0) as i16 + low;
+        if diff < 0 {
+            diff -= 0x60;
+        }
+        res = diff as u8;
+    }
+    sbc(flags, a, val);
+    if cmos {
+        set_zn(flags, res);
+    }
+    return res;
+}
+
+// CMP, CPX and CPY: a subtraction that only keeps the flags
+pub fn compare(flags: &mut Flags, reg: u8, val: u8) {
+    flags.carry = reg >= val;
+    set_zn(flags, reg.wrapping_sub(val));
+}
+
+// Z from A & val, N and V copied straight from bits 7 and 6 of val
+pub fn bit(flags: &mut Flags, a: u8, val: u8) {
+    flags.zero = a & val == 0;
+    flags.overflow = check_bit(val, 7);
+    flags.negative = check_bit(val, 8);
+}
+
+pub fn asl(flags: &mut Flags, val: u8) -> u8 {
+    let res = val << 1;
+    flags.carry = get_top_bit(val);
+    set_zn(flags, res);
+    return res;
+}
+
+pub fn lsr(flags: &mut Flags, val: u8) -> u8 {
+    let res = val >> 1;
+    flags.carry = check_bit(val, 1);
+    set_zn(flags, res);
+    return res;
+}
+
+pub fn rol(flags: &mut Flags, val: u8) -> u8 {
+    let res = (val << 1) | flags.carry as u8;
+    flags.carry = get_top_bit(val);
+    set_zn(flags, res);
+    return res;
+}
+
+pub fn ror(flags: &mut Flags, val: u8) -> u8 {
+    let res = (val >> 1) | ((flags.carry as u8) << 7);
+    flags.carry = check_bit(val, 1);
+    set_zn(flags, res);
+    return res;
+}
+
+pub fn inc(flags: &mut Flags, val: u8) -> u8 {
+    let res = val.wrapping_add(1);
+    set_zn(flags, res);
+    return res;
+}
+
+pub fn dec(flags: &mut Flags, val: u8) -> u8 {
+    let res = val.wrapping_sub(1);
+    set_zn(flags, res);
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_carry(carry: bool) -> Flags {
+        let mut flags = Flags::new();
+        flags.carry = carry;
+        return flags;
+    }
+
+    // (a, val, carry in) -> (result, carry, overflow, zero, negative)
+    #[test]
+    fn test_adc_table() {
+        let cases = [
+            (0x00, 0x00, false, 0x00, false, false, true, false),
+            (0x01, 0x01, false, 0x02, false, false, false, false),
+            (0x01, 0x01, true, 0x03, false, false, false, false),
+            (0xFF, 0x01, false, 0x00, true, false, true, false),
+            (0xFF, 0x00, true, 0x00, true, false, true, false),
+            (0xFF, 0xFF, true, 0xFF, true, false, false, true),
+            (0x7F, 0x01, false, 0x80, false, true, false, true),
+            (0x80, 0xFF, false, 0x7F, true, true, false, false),
+            (0x50, 0x50, false, 0xA0, false, true, false, true),
+            (0xD0, 0x90, false, 0x60, true, true, false, false),
+        ];
+        for (a, val, carry_in, res, carry, overflow, zero, negative) in cases {
+            let mut flags = with_carry(carry_in);
+            assert_eq!(adc(&mut flags, a, val), res, "{:02X} + {:02X} + {}", a, val, carry_in);
+            assert_eq!((flags.carry, flags.overflow, flags.zero, flags.negative),
+                (carry, overflow, zero, negative), "{:02X} + {:02X} + {}", a, val, carry_in);
+        }
+    }
+
+    // (a, val, carry in) -> (result, carry, overflow)
+    #[test]
+    fn test_sbc_table() {
+        let cases = [
+            (0x05, 0x03, true, 0x02, true, false),
+            (0x05, 0x03, false, 0x01, true, false),
+            (0x03, 0x05, true, 0xFE, false, false),
+            (0x00, 0x00, false, 0xFF, false, false),
+            (0x80, 0x01, true, 0x7F, true, true),
+            (0x7F, 0xFF, true, 0x80, false, true),
+            (0x50, 0xB0, true, 0xA0, false, true),
+            (0xD0, 0x70, true, 0x60, true, true),
+        ];
+        for (a, val, carry_in, res, carry, overflow) in cases {
+            let mut flags = with_carry(carry_in);
+            assert_eq!(sbc(&mut flags, a, val), res, "{:02X} - {:02X} - {}", a, val, !carry_in);
+            assert_eq!((flags.carry, flags.overflow), (carry, overflow), "{:02X} - {:02X} - {}", a, val, !carry_in);
+        }
+    }
+
+    #[test]
+    fn test_adc_exhaustive() {
+        for a in 0..=255u8 {
+            for val in 0..=255u8 {
+                for carry_in in [false, true] {
+                    let mut flags = with_carry(carry_in);
+                    let res = adc(&mut flags, a, val);
+                    let unsigned = a as u16 + val as u16 + carry_in as u16;
+                    let signed = a as i8 as i16 + val as i8 as i16 + carry_in as i16;
+                    assert_eq!(res, unsigned as u8);
+                    assert_eq!(flags.carry, unsigned > 0xFF);
+                    assert_eq!(flags.overflow, !(-128..=127).contains(&signed));
+                    assert_eq!(flags.zero, res == 0);
+                    assert_eq!(flags.negative, res >= 0x80);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sbc_exhaustive() {
+        for a in 0..=255u8 {
+            for val in 0..=255u8 {
+                for carry_in in [false, true] {
+                    let mut flags = with_carry(carry_in);
+                    let res = sbc(&mut flags, a, val);
+                    let borrow = !carry_in as i16;
+                    let unsigned = a as i16 - val as i16 - borrow;
+                    let signed = a as i8 as i16 - val as i8 as i16 - borrow;
+                    assert_eq!(res, unsigned as u8);
+                    assert_eq!(flags.carry, unsigned >= 0);
+                    assert_eq!(flags.overflow, !(-128..=127).contains(&signed));
+                    assert_eq!(flags.zero, res == 0);
+                    assert_eq!(flags.negative, res >= 0x80);
+                }
+            }
+        }
+    }
+
+    fn to_bcd(val: u8) -> u8 {
+        return ((val / 10) << 4) | (val % 10);
+    }
+
+    #[test]
+    fn test_adc_decimal_table() {
+        // (a, val, carry in) -> (result, carry)
+        let cases = [
+            (0x00, 0x00, false, 0x00, false),
+            (0x12, 0x34, false, 0x46, false),
+            (0x58, 0x46, true, 0x05, true),
+            (0x81, 0x92, false, 0x73, true),
+            (0x99, 0x01, false, 0x00, true),
+            (0x09, 0x01, false, 0x10, false),
+        ];
+        for cmos in [false, true] {
+            for (a, val, carry_in, res, carry) in cases {
+                let mut flags = with_carry(carry_in);
+                assert_eq!(adc_decimal(&mut flags, a, val, cmos), res, "{:02X} + {:02X}", a, val);
+                assert_eq!(flags.carry, carry, "{:02X} + {:02X}", a, val);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sbc_decimal_table() {
+        // (a, val, carry in) -> (result, carry)
+        let cases = [
+            (0x00, 0x00, true, 0x00, true),
+            (0x46, 0x12, true, 0x34, true),
+            (0x40, 0x13, true, 0x27, true),
+            (0x32, 0x02, false, 0x29, true),
+            (0x12, 0x21, true, 0x91, false),
+            (0x21, 0x34, true, 0x87, false),
+        ];
+        for cmos in [false, true] {
+            for (a, val, carry_in, res, carry) in cases {
+                let mut flags = with_carry(carry_in);
+                assert_eq!(sbc_decimal(&mut flags, a, val, cmos), res, "{:02X} - {:02X}", a, val);
+                assert_eq!(flags.carry, carry, "{:02X} - {:02X}", a, val);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decimal_exhaustive_valid_bcd() {
+        for a in 0..100u8 {
+            for val in 0..100u8 {
+                for carry_in in [false, true] {
+                    for cmos in [false, true] {
+                        let mut flags = with_carry(carry_in);
+                        let sum = a as u16 + val as u16 + carry_in as u16;
+                        assert_eq!(adc_decimal(&mut flags, to_bcd(a), to_bcd(val), cmos), to_bcd((sum % 100) as u8));
+                        assert_eq!(flags.carry, sum >= 100);
+
+                        let mut flags = with_carry(carry_in);
+                        let diff = a as i16 - val as i16 - !carry_in as i16;
+                        assert_eq!(sbc_decimal(&mut flags, to_bcd(a), to_bcd(val), cmos), to_bcd(diff.rem_euclid(100) as u8));
+                        assert_eq!(flags.carry, diff >= 0);
+                        if cmos {
+                            assert_eq!(flags.zero, diff.rem_euclid(100) == 0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_adc_decimal_nmos_flags() {
+        // 99 + 1 wraps to 00 but the binary sum is $9A, so NMOS leaves Z clear
+        let mut flags = Flags::new();
+        assert_eq!(adc_decimal(&mut flags, 0x99, 0x01, false), 0x00);
+        assert!(!flags.zero);
+        assert!(flags.negative);
+        let mut flags = Flags::new();
+        assert_eq!(adc_decimal(&mut flags, 0x99, 0x01, true), 0x00);
+        assert!(flags.zero);
+        assert!(!flags.negative);
+    }
+
+    #[test]
+    fn test_adc_decimal_overflow() {
+        let mut flags = Flags::new();
+        adc_decimal(&mut flags, 0x79, 0x01, false);
+        assert!(flags.overflow);
+        let mut flags = Flags::new();
+        adc_decimal(&mut flags, 0x12, 0x34, false);
+        assert!(!flags.overflow);
+    }
+
+    #[test]
+    fn test_sbc_decimal_nmos_flags() {
+        // NMOS flags come from the binary subtraction $12 - $21 = $F1
+        let mut flags = with_carry(true);
+        assert_eq!(sbc_decimal(&mut flags, 0x12, 0x21, false), 0x91);
+        assert!(flags.negative);
+        let mut flags = with_carry(true);
+        assert_eq!(sbc_decimal(&mut flags, 0x10, 0x10, false), 0x00);
+        assert!(flags.zero);
+    }
+
+    #[test]
+    fn test_compare_exhaustive() {
+        for reg in 0..=255u8 {
+            for val in 0..=255u8 {
+                let mut flags = Flags::new();
+                flags.overflow = true;
+                compare(&mut flags, reg, val);
+                assert_eq!(flags.carry, reg >= val);
+                assert_eq!(flags.zero, reg == val);
+                assert_eq!(flags.negative, reg.wrapping_sub(val) >= 0x80);
+                assert!(flags.overflow);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compare_negative_from_bit_7() {
+        let mut flags = Flags::new();
+        // 0x01 < 0xFF but the difference is 0x02, so N is clear
+        compare(&mut flags, 0x01, 0xFF);
+        assert!(!flags.carry);
+        assert!(!flags.negative);
+        // 0x90 > 0x01 but the difference is 0x8F
+        compare(&mut flags, 0x90, 0x01);
+        assert!(flags.carry);
+        assert!(flags.negative);
+    }
+
+    #[test]
+    fn test_shifts_exhaustive() {
+        for val in 0..=255u8 {
+            for carry_in in [false, true] {
+                let mut flags = with_carry(carry_in);
+                assert_eq!(asl(&mut flags, val), val << 1);
+                assert_eq!(flags.carry, val & 0x80 != 0);
+
+                let mut flags = with_carry(carry_in);
+                assert_eq!(lsr(&mut flags, val), val >> 1);
+                assert_eq!(flags.carry, val & 0x01 != 0);
+                assert!(!flags.negative);
+
+                let mut flags = with_carry(carry_in);
+                let res = rol(&mut flags, val);
+                assert_eq!(res, (val << 1) | carry_in as u8);
+                assert_eq!(flags.carry, val & 0x80 != 0);
+                assert_eq!(flags.zero, res == 0);
+
+                let mut flags = with_carry(carry_in);
+                let res = ror(&mut flags, val);
+                assert_eq!(res, (val >> 1) | ((carry_in as u8) << 7));
+                assert_eq!(flags.carry, val & 0x01 != 0);
+                assert_eq!(flags.negative, carry_in);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rotate_round_trip() {
+        for val in 0..=255u8 {
+            for carry_in in [false, true] {
+                let mut flags = with_carry(carry_in);
+                let rotated = rol(&mut flags, val);
+                assert_eq!(ror(&mut flags, rotated), val);
+                assert_eq!(flags.carry, carry_in);
+            }
+        }
+    }
+
+    #[test]
+    fn test_inc_dec_wrap() {
+        let mut flags = Flags::new();
+        assert_eq!(inc(&mut flags, 0xFF), 0x00);
+        assert!(flags.zero);
+        assert_eq!(dec(&mut flags, 0x00), 0xFF);
+        assert!(flags.negative);
+        for val in 0..=255u8 {
+            let incremented = inc(&mut flags, val);
+            assert_eq!(dec(&mut flags, incremented), val);
+        }
+    }
+
+    #[test]
+    fn test_bit() {
+        let mut flags = Flags::new();
+        bit(&mut flags, 0x01, 0xC0);
+        assert!(flags.zero);
+        assert!(flags.overflow);
+        assert!(flags.negative);
+        bit(&mut flags, 0x01, 0x21);
+        assert!(!flags.zero);
+        assert!(!flags.overflow);
+        assert!(!flags.negative);
+    }
+}
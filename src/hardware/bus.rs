@@ -0,0 +1,121 @@
+pub const MEM_SIZE: usize = 0xFFFF + 1;
+
+// Everything the cpu can address. Reads take &mut self because reading a device
+// register can change it, peek is for tools that must not disturb anything
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, val: u8);
+    fn peek(&self, addr: u16) -> u8;
+    // The store side of peek, only memory takes the value. Registers and anything
+    // else that reacts to a write ignore it
+    fn poke(&mut self, addr: u16, val: u8);
+
+    // Run one cycle of any DMA the bus has pending and return true if it halts the cpu
+    // for that cycle. `cycle` is the cpu cycle about to run, DMA alignment depends on it.
+    // `addr` is the read the cpu was halted on, it stays on the bus while DMA waits
+    fn dma_cycle(&mut self, _cycle: u64, _addr: u16) -> bool {
+        return false;
+    }
+
+    // IRQ output of devices on the bus, wired-OR with the cpu's own IRQ line
+    fn irq(&self) -> bool {
+        return false;
+    }
+}
+
+// 64 KiB of plain RAM with nothing mapped, for running bare 6502 code and tests
+pub struct FlatRam {
+    memory: Vec<u8>,
+}
+
+impl FlatRam {
+    pub fn new() -> Self {
+        return FlatRam {
+            memory: vec![0; MEM_SIZE],
+        }
+    }
+}
+
+impl Bus for FlatRam {
+    fn read(&mut self, addr: u16) -> u8 {
+        return self.memory[addr as usize];
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        self.memory[addr as usize] = val;
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        return self.memory[addr as usize];
+    }
+
+    fn poke(&mut self, addr: u16, val: u8) {
+        self.memory[addr as usize] = val;
+    }
+}
+
+// Test support shared by the cpu cores' tests
+#[cfg(test)]
+pub mod testing {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    pub enum Access {
+        Read(u16),
+        Write(u16, u8),
+    }
+
+    pub type AccessLog = Rc<RefCell<Vec<Access>>>;
+
+    // RAM that logs every cpu access in order
+    pub struct RecordingBus {
+        ram: FlatRam,
+        log: AccessLog,
+    }
+
+    impl RecordingBus {
+        // The bus and a handle on its log that stays with the caller
+        pub fn new() -> (Self, AccessLog) {
+            let log = Rc::new(RefCell::new(Vec::new()));
+            return (RecordingBus { ram: FlatRam::new(), log: log.clone() }, log);
+        }
+    }
+
+    impl Bus for RecordingBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            self.log.borrow_mut().push(Access::Read(addr));
+            return self.ram.read(addr);
+        }
+
+        fn write(&mut self, addr: u16, val: u8) {
+            self.log.borrow_mut().push(Access::Write(addr, val));
+            self.ram.write(addr, val);
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            return self.ram.peek(addr);
+        }
+
+        fn poke(&mut self, addr: u16, val: u8) {
+            self.ram.poke(addr, val);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flat_ram() {
+        let mut ram = FlatRam::new();
+        ram.write(0xFFFF, 0x42);
+        assert_eq!(ram.read(0xFFFF), 0x42);
+        assert_eq!(ram.peek(0xFFFF), 0x42);
+        assert_eq!(ram.peek(0x0000), 0x00);
+        ram.poke(0x0000, 0x24);
+        assert_eq!(ram.read(0x0000), 0x24);
+    }
+}
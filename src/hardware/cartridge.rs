@@ -0,0 +1,592 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use crate::hardware::error::{CartridgeError, CartridgeResult};
+use crate::hardware::variant::CpuVariant;
+
+pub const HEADER_SIZE: usize = 16;
+pub const TRAINER_SIZE: usize = 512;
+pub const PRG_BANK_SIZE: usize = 0x4000;
+pub const CHR_BANK_SIZE: usize = 0x2000;
+pub const PRG_RAM_BANK_SIZE: usize = 0x2000;
+// Where PRG RAM and ROM start in the cpu address space, and where a trainer is loaded
+pub const PRG_RAM_START: u16 = 0x6000;
+pub const PRG_ROM_START: u16 = 0x8000;
+pub const TRAINER_START: u16 = 0x7000;
+
+const MAGIC: [u8; 4] = [b'N', b'E', b'S', 0x1A];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    // The board has its own nametable RAM for all four screens
+    FourScreen,
+    // All four show the same nametable, set by mappers that switch mirroring
+    SingleScreenLower,
+    SingleScreenUpper,
+}
+
+pub const NAMETABLE_SIZE: usize = 0x400;
+
+impl Mirroring {
+    // Where a $2000-$3EFF address lands in nametable RAM. The console has room for
+    // two nametables, four screen boards add another two after them
+    pub fn nametable_offset(&self, addr: u16) -> usize {
+        let table = (addr as usize >> 10) & 0x03;
+        let page = match self {
+            Mirroring::Horizontal => table >> 1,
+            Mirroring::Vertical => table & 0x01,
+            Mirroring::FourScreen => table,
+            Mirroring::SingleScreenLower => 0,
+            Mirroring::SingleScreenUpper => 1,
+        };
+        return page * NAMETABLE_SIZE + (addr as usize & (NAMETABLE_SIZE - 1));
+    }
+}
+
+// NES 2.0 is flagged by bits 2-3 of byte 7 and reuses bytes 8-15, which plain
+// iNES leaves mostly unused
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    INes,
+    Nes2,
+}
+
+// The video standard the game was made for, which sets the cpu clock and frame timing
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TvSystem {
+    Ntsc,
+    Pal,
+    // Runs on either, taken as NTSC
+    Multi,
+    // Famiclone timing, PAL frame rate with a faster cpu
+    Dendy,
+}
+
+impl TvSystem {
+    // The system to run a machine as
+    pub fn machine(&self) -> TvSystem {
+        return match self {
+            TvSystem::Multi => TvSystem::Ntsc,
+            _ => *self,
+        };
+    }
+
+    pub fn cpu_clock_hz(&self) -> u32 {
+        return match self.machine() {
+            TvSystem::Pal => 1_662_607,
+            TvSystem::Dendy => 1_773_448,
+            _ => 1_789_773,
+        };
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem,
+    PlayChoice,
+    // NES 2.0 byte 13, clones and other odd hardware
+    Extended(u8),
+}
+
+// Extended console type of a Famiclone that kept the 6502's decimal mode
+const DECIMAL_FAMICLONE: u8 = 3;
+
+// The 16 byte iNES header. Sizes are in bytes, RAM sizes that plain iNES doesn't
+// have are filled in the way emulators have always guessed them
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Header {
+    pub format: Format,
+    pub prg_rom_size: usize,
+    // 0 means the board has CHR RAM instead
+    pub chr_rom_size: usize,
+    pub mapper: u16,
+    pub submapper: u8,
+    pub mirroring: Mirroring,
+    // Battery backed PRG RAM at $6000-$7FFF
+    pub battery: bool,
+    // 512 bytes between the header and PRG ROM, loaded at $7000
+    pub trainer: bool,
+    pub prg_ram_size: usize,
+    // Battery backed, kept between runs
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub tv_system: TvSystem,
+    pub console: ConsoleType,
+    // NES 2.0 default expansion device, 1 is the standard controllers and 0 unspecified
+    pub expansion_device: u8,
+}
+
+// NES 2.0 RAM sizes are a shift count, 64 << n bytes, with 0 meaning none
+fn ram_size(shift: u8) -> usize {
+    if shift == 0 {
+        return 0;
+    }
+    return 64 << shift;
+}
+
+// NES 2.0 ROM sizes: a 12 bit bank count, or 2^E * (M * 2 + 1) bytes when the
+// upper nibble is $F
+fn rom_size(lsb: u8, msb: u8, bank_size: usize) -> CartridgeResult<usize> {
+    if msb != 0x0F {
+        return Ok(((msb as usize) << 8 | lsb as usize) * bank_size);
+    }
+    let exponent = (lsb >> 2) as u32;
+    let multiplier = (lsb & 0x03) as usize * 2 + 1;
+    return 1usize.checked_shl(exponent)
+        .and_then(|size| size.checked_mul(multiplier))
+        .ok_or(CartridgeError::TooLarge);
+}
+
+impl Header {
+    pub fn parse(bytes: &[u8]) -> CartridgeResult<Header> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(CartridgeError::TooShort { len: bytes.len() });
+        }
+        if bytes[0..4] != MAGIC {
+            return Err(CartridgeError::BadMagic);
+        }
+        let flags6 = bytes[6];
+        let mirroring = if flags6 & 0x08 != 0 {
+            Mirroring::FourScreen
+        } else if flags6 & 0x01 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        let header = if bytes[7] & 0x0C == 0x08 {
+            Header::parse_nes2(bytes, mirroring)?
+        } else {
+            Header::parse_ines(bytes, mirroring)
+        };
+        if header.prg_rom_size == 0 {
+            return Err(CartridgeError::NoPrgRom);
+        }
+        header.file_size()?;
+        return Ok(header);
+    }
+
+    fn parse_ines(bytes: &[u8], mirroring: Mirroring) -> Header {
+        let flags6 = bytes[6];
+        // Old dumps have junk like "DiskDude!" in bytes 7-15, nothing past byte 6
+        // can be trusted unless the unused tail is zero
+        let clean = bytes[12..HEADER_SIZE].iter().all(|byte| *byte == 0);
+        let (flags7, flags9) = if clean { (bytes[7], bytes[9]) } else { (0, 0) };
+        let battery = flags6 & 0x02 != 0;
+        // 0 is taken as one bank for compatibility
+        let prg_ram_size = if clean { bytes[8].max(1) } else { 1 } as usize * PRG_RAM_BANK_SIZE;
+        let chr_rom_size = bytes[5] as usize * CHR_BANK_SIZE;
+        return Header {
+            format: Format::INes,
+            prg_rom_size: bytes[4] as usize * PRG_BANK_SIZE,
+            chr_rom_size,
+            mapper: ((flags7 & 0xF0) | (flags6 >> 4)).into(),
+            submapper: 0,
+            mirroring,
+            battery,
+            trainer: flags6 & 0x04 != 0,
+            prg_ram_size: if battery { 0 } else { prg_ram_size },
+            prg_nvram_size: if battery { prg_ram_size } else { 0 },
+            chr_ram_size: if chr_rom_size == 0 { CHR_BANK_SIZE } else { 0 },
+            chr_nvram_size: 0,
+            tv_system: if flags9 & 0x01 != 0 { TvSystem::Pal } else { TvSystem::Ntsc },
+            console: if flags7 & 0x01 != 0 {
+                ConsoleType::VsSystem
+            } else if flags7 & 0x02 != 0 {
+                ConsoleType::PlayChoice
+            } else {
+                ConsoleType::Nes
+            },
+            expansion_device: 0,
+        };
+    }
+
+    fn parse_nes2(bytes: &[u8], mirroring: Mirroring) -> CartridgeResult<Header> {
+        let flags6 = bytes[6];
+        let flags7 = bytes[7];
+        return Ok(Header {
+            format: Format::Nes2,
+            prg_rom_size: rom_size(bytes[4], bytes[9] & 0x0F, PRG_BANK_SIZE)?,
+            chr_rom_size: rom_size(bytes[5], bytes[9] >> 4, CHR_BANK_SIZE)?,
+            mapper: (bytes[8] as u16 & 0x0F) << 8 | (flags7 & 0xF0) as u16 | (flags6 >> 4) as u16,
+            submapper: bytes[8] >> 4,
+            mirroring,
+            battery: flags6 & 0x02 != 0,
+            trainer: flags6 & 0x04 != 0,
+            prg_ram_size: ram_size(bytes[10] & 0x0F),
+            prg_nvram_size: ram_size(bytes[10] >> 4),
+            chr_ram_size: ram_size(bytes[11] & 0x0F),
+            chr_nvram_size: ram_size(bytes[11] >> 4),
+            tv_system: match bytes[12] & 0x03 {
+                0 => TvSystem::Ntsc,
+                1 => TvSystem::Pal,
+                2 => TvSystem::Multi,
+                _ => TvSystem::Dendy,
+            },
+            console: match flags7 & 0x03 {
+                0 => ConsoleType::Nes,
+                1 => ConsoleType::VsSystem,
+                2 => ConsoleType::PlayChoice,
+                _ => ConsoleType::Extended(bytes[13] & 0x0F),
+            },
+            expansion_device: bytes[15] & 0x3F,
+        });
+    }
+
+    // Header, trainer, PRG and CHR, anything after that is ignored. NES 2.0
+    // exponent sizes can add up to more than fits in a usize
+    pub fn file_size(&self) -> CartridgeResult<usize> {
+        let trainer = if self.trainer { TRAINER_SIZE } else { 0 };
+        return (HEADER_SIZE + trainer).checked_add(self.prg_rom_size)
+            .and_then(|size| size.checked_add(self.chr_rom_size))
+            .ok_or(CartridgeError::TooLarge);
+    }
+
+    // The 2A03 and 2A07 both lack decimal mode, only some clones kept it
+    pub fn cpu_variant(&self) -> CpuVariant {
+        return match self.console {
+            ConsoleType::Extended(DECIMAL_FAMICLONE) => CpuVariant::Nmos6502,
+            _ => CpuVariant::Ricoh2A03,
+        };
+    }
+}
+
+// ex. Mapper 0, 32 KiB PRG, 8 KiB CHR ROM, vertical mirroring
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Mapper {}", self.mapper)?;
+        if self.submapper != 0 {
+            write!(f, ".{}", self.submapper)?;
+        }
+        write!(f, ", {} KiB PRG, ", self.prg_rom_size / 1024)?;
+        if self.chr_rom_size == 0 {
+            write!(f, "{} KiB CHR RAM", (self.chr_ram_size + self.chr_nvram_size) / 1024)?;
+        } else {
+            write!(f, "{} KiB CHR ROM", self.chr_rom_size / 1024)?;
+        }
+        write!(f, ", {:?} mirroring", self.mirroring)?;
+        if self.battery {
+            write!(f, ", battery")?;
+        }
+        if self.trainer {
+            write!(f, ", trainer")?;
+        }
+        if self.tv_system != TvSystem::Ntsc {
+            write!(f, ", {:?}", self.tv_system)?;
+        }
+        return Ok(());
+    }
+}
+
+// A ROM image split into the pieces the header describes, plus the RAM the
+// header says the board carries
+pub struct Cartridge {
+    pub header: Header,
+    pub prg_rom: Vec<u8>,
+    pub chr_rom: Vec<u8>,
+    // Volatile and battery backed PRG RAM share $6000-$7FFF
+    pub prg_ram: Vec<u8>,
+    pub chr_ram: Vec<u8>,
+}
+
+impl Cartridge {
+    pub fn load(path: &Path) -> CartridgeResult<Cartridge> {
+        let bytes = fs::read(path)?;
+        return Cartridge::from_bytes(&bytes);
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> CartridgeResult<Cartridge> {
+        let header = Header::parse(bytes)?;
+        let file_size = header.file_size()?;
+        if bytes.len() < file_size {
+            return Err(CartridgeError::Truncated { expected: file_size, found: bytes.len() });
+        }
+        let mut prg_ram = vec![0; header.prg_ram_size + header.prg_nvram_size];
+        let mut start = HEADER_SIZE;
+        if header.trainer {
+            // A trainer needs RAM at $7000 even if the header forgot to ask for it
+            if prg_ram.len() < PRG_RAM_BANK_SIZE {
+                prg_ram.resize(PRG_RAM_BANK_SIZE, 0);
+            }
+            let offset = (TRAINER_START - PRG_RAM_START) as usize;
+            prg_ram[offset..offset + TRAINER_SIZE].copy_from_slice(&bytes[start..start + TRAINER_SIZE]);
+            start += TRAINER_SIZE;
+        }
+        // Both fit, they're no bigger than file_size
+        let prg_end = start + header.prg_rom_size;
+        let chr_end = prg_end + header.chr_rom_size;
+        return Ok(Cartridge {
+            header,
+            prg_rom: bytes[start..prg_end].to_vec(),
+            chr_rom: bytes[prg_end..chr_end].to_vec(),
+            prg_ram,
+            chr_ram: vec![0; header.chr_ram_size + header.chr_nvram_size],
+        });
+    }
+
+    // PRG ROM as the cpu sees it at $8000-$FFFF. A single 16 KiB bank shows up twice
+    pub fn read_prg(&self, addr: u16) -> u8 {
+        return self.read_prg_offset((addr - PRG_ROM_START) as usize);
+    }
+
+    // For banked boards, `offset` wraps around the ROM size so bank numbers past
+    // the end mirror the way the unconnected address lines make them
+    pub fn read_prg_offset(&self, offset: usize) -> u8 {
+        return self.prg_rom[offset % self.prg_rom.len()];
+    }
+
+    // Pattern table memory, CHR ROM if the board has it and CHR RAM otherwise.
+    // `offset` wraps around whatever size it is
+    pub fn read_chr(&self, offset: usize) -> u8 {
+        let chr = if self.chr_rom.is_empty() { &self.chr_ram } else { &self.chr_rom };
+        if chr.is_empty() {
+            return 0;
+        }
+        return chr[offset % chr.len()];
+    }
+
+    // Writes only stick with CHR RAM
+    pub fn write_chr(&mut self, offset: usize, val: u8) {
+        if self.chr_rom.is_empty() && !self.chr_ram.is_empty() {
+            let len = self.chr_ram.len();
+            self.chr_ram[offset % len] = val;
+        }
+    }
+
+    // PRG RAM at $6000-$7FFF, mirrored when it's smaller than 8 KiB. None if the
+    // board has no RAM there
+    pub fn read_prg_ram(&self, addr: u16) -> Option<u8> {
+        if self.prg_ram.is_empty() {
+            return None;
+        }
+        return Some(self.prg_ram[(addr - PRG_RAM_START) as usize % self.prg_ram.len()]);
+    }
+
+    pub fn write_prg_ram(&mut self, addr: u16, val: u8) {
+        if !self.prg_ram.is_empty() {
+            let len = self.prg_ram.len();
+            self.prg_ram[(addr - PRG_RAM_START) as usize % len] = val;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A header with the given flag bytes followed by PRG banks filled with their
+    // bank number and CHR banks filled with $CC
+    fn rom(prg_banks: u8, chr_banks: u8, flags6: u8, flags7: u8) -> Vec<u8> {
+        let mut bytes = vec![b'N', b'E', b'S', 0x1A, prg_banks, chr_banks, flags6, flags7];
+        bytes.resize(HEADER_SIZE, 0);
+        if flags6 & 0x04 != 0 {
+            bytes.extend(vec![0x77; TRAINER_SIZE]);
+        }
+        for bank in 0..prg_banks {
+            bytes.extend(vec![bank; PRG_BANK_SIZE]);
+        }
+        bytes.extend(vec![0xCC; chr_banks as usize * CHR_BANK_SIZE]);
+        return bytes;
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let header = Header::parse(&rom(2, 1, 0x13, 0x40)).unwrap();
+        assert_eq!(header.format, Format::INes);
+        assert_eq!(header.prg_rom_size, 2 * PRG_BANK_SIZE);
+        assert_eq!(header.chr_rom_size, CHR_BANK_SIZE);
+        assert_eq!(header.mapper, 0x41);
+        assert_eq!(header.mirroring, Mirroring::Vertical);
+        assert!(header.battery);
+        assert!(!header.trainer);
+        assert_eq!(header.prg_ram_size, 0);
+        assert_eq!(header.prg_nvram_size, 0x2000);
+        assert_eq!(header.chr_ram_size, 0);
+        assert_eq!(header.tv_system, TvSystem::Ntsc);
+        assert_eq!(header.console, ConsoleType::Nes);
+    }
+
+    #[test]
+    fn test_mirroring() {
+        assert_eq!(Header::parse(&rom(1, 0, 0x00, 0)).unwrap().mirroring, Mirroring::Horizontal);
+        assert_eq!(Header::parse(&rom(1, 0, 0x09, 0)).unwrap().mirroring, Mirroring::FourScreen);
+    }
+
+    #[test]
+    fn test_dirty_header_mapper() {
+        let mut bytes = rom(1, 1, 0x10, 0x40);
+        bytes[7..HEADER_SIZE].copy_from_slice(b"DiskDude!");
+        let header = Header::parse(&bytes).unwrap();
+        assert_eq!(header.mapper, 1);
+        // 'k' in byte 9 would otherwise mean PAL
+        assert_eq!(header.tv_system, TvSystem::Ntsc);
+    }
+
+    #[test]
+    fn test_ines_extras() {
+        let mut bytes = rom(1, 0, 0, 0x01);
+        bytes[8] = 4;
+        bytes[9] = 0x01;
+        let header = Header::parse(&bytes).unwrap();
+        assert_eq!(header.prg_ram_size, 4 * PRG_RAM_BANK_SIZE);
+        assert_eq!(header.chr_ram_size, CHR_BANK_SIZE);
+        assert_eq!(header.tv_system, TvSystem::Pal);
+        assert_eq!(header.console, ConsoleType::VsSystem);
+    }
+
+    #[test]
+    fn test_parse_nes2() {
+        let mut bytes = rom(2, 0, 0x12, 0x48);
+        // Mapper $541 submapper 3, PRG ROM $102 banks, 8 KiB PRG RAM, 32 KiB PRG
+        // NVRAM, 8 KiB CHR RAM, Dendy, expansion device $2A
+        bytes[8] = 0x35;
+        bytes[9] = 0x01;
+        bytes[10] = 0x97;
+        bytes[11] = 0x07;
+        bytes[12] = 0x03;
+        bytes[15] = 0x2A;
+        let header = Header::parse(&bytes[..HEADER_SIZE]).unwrap();
+        assert_eq!(header.format, Format::Nes2);
+        assert_eq!(header.mapper, 0x541);
+        assert_eq!(header.submapper, 3);
+        assert_eq!(header.prg_rom_size, 0x102 * PRG_BANK_SIZE);
+        assert_eq!(header.chr_rom_size, 0);
+        assert_eq!(header.prg_ram_size, 0x2000);
+        assert_eq!(header.prg_nvram_size, 0x8000);
+        assert_eq!(header.chr_ram_size, 0x2000);
+        assert_eq!(header.chr_nvram_size, 0);
+        assert_eq!(header.tv_system, TvSystem::Dendy);
+        assert_eq!(header.console, ConsoleType::Nes);
+        assert_eq!(header.expansion_device, 0x2A);
+        assert_eq!(header.to_string(), "Mapper 1345.3, 4128 KiB PRG, 8 KiB CHR RAM, Horizontal mirroring, battery, Dendy");
+    }
+
+    #[test]
+    fn test_nes2_exponent_size() {
+        let mut bytes = rom(1, 0, 0, 0x08);
+        // 2^10 * 3 bytes of PRG
+        bytes[4] = 10 << 2 | 1;
+        bytes[9] = 0x0F;
+        assert_eq!(Header::parse(&bytes).unwrap().prg_rom_size, 3 * 1024);
+        bytes[4] = 0xFF;
+        assert_eq!(Header::parse(&bytes), Err(CartridgeError::TooLarge));
+
+        // 2^63 bytes each of PRG and CHR, fine alone but not added together
+        bytes[4] = 0xFC;
+        bytes[5] = 0xFC;
+        bytes[9] = 0xFF;
+        assert_eq!(Header::parse(&bytes), Err(CartridgeError::TooLarge));
+        assert_eq!(Cartridge::from_bytes(&bytes).err(), Some(CartridgeError::TooLarge));
+    }
+
+    #[test]
+    fn test_nes2_console() {
+        let mut bytes = rom(1, 0, 0, 0x0B);
+        bytes[13] = 0x03;
+        let header = Header::parse(&bytes).unwrap();
+        assert_eq!(header.console, ConsoleType::Extended(3));
+        assert_eq!(header.cpu_variant(), CpuVariant::Nmos6502);
+        assert_eq!(Header::parse(&rom(1, 0, 0, 0x09)).unwrap().console, ConsoleType::VsSystem);
+        assert_eq!(Header::parse(&rom(1, 0, 0, 0x08)).unwrap().cpu_variant(), CpuVariant::Ricoh2A03);
+    }
+
+    #[test]
+    fn test_tv_system_machine() {
+        assert_eq!(TvSystem::Multi.machine(), TvSystem::Ntsc);
+        assert_eq!(TvSystem::Multi.cpu_clock_hz(), 1_789_773);
+        assert_eq!(TvSystem::Pal.cpu_clock_hz(), 1_662_607);
+    }
+
+    #[test]
+    fn test_header_errors() {
+        assert_eq!(Header::parse(&[0x4E, 0x45]), Err(CartridgeError::TooShort { len: 2 }));
+        let mut bytes = rom(1, 0, 0, 0);
+        bytes[3] = 0;
+        assert_eq!(Header::parse(&bytes), Err(CartridgeError::BadMagic));
+        assert_eq!(Header::parse(&rom(0, 1, 0, 0)), Err(CartridgeError::NoPrgRom));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut bytes = rom(2, 1, 0, 0);
+        bytes.truncate(HEADER_SIZE + PRG_BANK_SIZE);
+        assert_eq!(
+            Cartridge::from_bytes(&bytes).err(),
+            Some(CartridgeError::Truncated { expected: 0xA010, found: 0x4010 })
+        );
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let cart = Cartridge::from_bytes(&rom(2, 1, 0x04, 0)).unwrap();
+        assert_eq!(cart.prg_ram[0x1000..0x1200], [0x77; TRAINER_SIZE]);
+        assert_eq!(cart.prg_rom.len(), 2 * PRG_BANK_SIZE);
+        assert_eq!(cart.prg_rom[PRG_BANK_SIZE], 1);
+        assert_eq!(cart.chr_rom, vec![0xCC; CHR_BANK_SIZE]);
+    }
+
+    #[test]
+    fn test_read_prg() {
+        let cart = Cartridge::from_bytes(&rom(2, 0, 0, 0)).unwrap();
+        assert_eq!(cart.read_prg(0x8000), 0);
+        assert_eq!(cart.read_prg(0xFFFF), 1);
+
+        let cart = Cartridge::from_bytes(&rom(1, 0, 0, 0)).unwrap();
+        assert_eq!(cart.read_prg(0xC000), 0);
+    }
+
+    #[test]
+    fn test_chr() {
+        let mut cart = Cartridge::from_bytes(&rom(1, 1, 0, 0)).unwrap();
+        cart.write_chr(0x10, 0x42);
+        assert_eq!(cart.read_chr(0x10), 0xCC);
+
+        let mut cart = Cartridge::from_bytes(&rom(1, 0, 0, 0)).unwrap();
+        cart.write_chr(0x10, 0x42);
+        assert_eq!(cart.read_chr(0x2010), 0x42);
+    }
+
+    #[test]
+    fn test_nametable_offset() {
+        assert_eq!(Mirroring::Horizontal.nametable_offset(0x2401), 0x001);
+        assert_eq!(Mirroring::Horizontal.nametable_offset(0x2801), 0x401);
+        assert_eq!(Mirroring::Vertical.nametable_offset(0x2401), 0x401);
+        assert_eq!(Mirroring::Vertical.nametable_offset(0x2C01), 0x401);
+        assert_eq!(Mirroring::FourScreen.nametable_offset(0x2C01), 0xC01);
+        assert_eq!(Mirroring::SingleScreenLower.nametable_offset(0x2C01), 0x001);
+        assert_eq!(Mirroring::SingleScreenUpper.nametable_offset(0x2001), 0x401);
+        // $3000-$3EFF mirrors $2000-$2EFF
+        assert_eq!(Mirroring::Vertical.nametable_offset(0x3401), 0x401);
+    }
+
+    #[test]
+    fn test_prg_ram() {
+        // NES 2.0 with 2 KiB of PRG RAM, mirrored through $6000-$7FFF
+        let mut bytes = rom(1, 0, 0, 0x08);
+        bytes[10] = 0x05;
+        let mut cart = Cartridge::from_bytes(&bytes).unwrap();
+        assert_eq!(cart.prg_ram.len(), 0x800);
+        assert!(cart.chr_ram.is_empty());
+        cart.write_prg_ram(0x6001, 0x42);
+        assert_eq!(cart.read_prg_ram(0x7801), Some(0x42));
+
+        let mut cart = Cartridge::from_bytes(&rom(1, 0, 0, 0x08)).unwrap();
+        cart.write_prg_ram(0x6001, 0x42);
+        assert_eq!(cart.read_prg_ram(0x6001), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let header = Header::parse(&rom(2, 1, 0x01, 0)).unwrap();
+        assert_eq!(header.to_string(), "Mapper 0, 32 KiB PRG, 8 KiB CHR ROM, Vertical mirroring");
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let err = Cartridge::load(Path::new("/nonexistent/rom.nes")).err();
+        assert_eq!(err, Some(CartridgeError::Io(std::io::ErrorKind::NotFound)));
+    }
+}
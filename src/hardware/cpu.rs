@@ -1,26 +1,62 @@
 use crate::hardware::registers::{Flags, Registers};
-use crate::hardware::instruction::{AddrModes, Ops, TransferOption};
-use crate::hardware::memory::{MEM_SIZE, MemoryOps};
-use crate::utils::{get_top_bit, is_overflow, check_bit, combine_bytes};
-use num_traits::FromPrimitive;
+use crate::hardware::instruction::{Access, AddrModes, Instruction, JamBehavior, Mnemonic, Ops, TransferOption};
+use crate::hardware::bus::{Bus, FlatRam};
+use crate::hardware::memory::MemoryOps;
+use crate::hardware::stack::{StackOps, STACK_BASE};
+use crate::hardware::timing::{Timing, crosses_page};
+use crate::hardware::interrupts::Interrupts;
+use crate::hardware::error::{CpuError, CpuResult};
+use crate::hardware::alu;
+use crate::hardware::variant::CpuVariant;
+use crate::hardware::cycle::{CycleOps, ExecMode, MicroState};
+use crate::utils::{get_top_bit, check_bit};
 
 
 pub struct Cpu {
-    pub (super) memory: Vec<u8>,
-    rega: u8,
+    pub (super) bus: Box<dyn Bus>,
+    pub (super) rega: u8,
     pub (super) regx: u8,
     pub (super) regy: u8,
     pub (super) flags: Flags,
     // pub (super) stack: Vec<u8>,
     pub (super) pc: u16,
-    cycles_taken: u8,
-    pub (super) sp: usize,
+    pub (super) cycles_taken: u8,
+    // Total cycles run since power on
+    pub (super) cycles: u64,
+    // Set while executing when an indexed address or branch target lands on another page
+    pub (super) page_crossed: bool,
+    pub (super) branch_taken: bool,
+    pub (super) sp: u8,
+    // NMI edge latched until serviced, IRQ is the current level of the line
+    pub (super) nmi_pending: bool,
+    pub (super) irq_line: bool,
+    pub (super) jam_behavior: JamBehavior,
+    // Set once a KIL opcode halts the cpu, only reset brings it back
+    pub (super) jammed: bool,
+    pub (super) variant: CpuVariant,
+    pub (super) exec_mode: ExecMode,
+    // The instruction in flight when running cycle by cycle
+    pub (super) micro: MicroState,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        return Cpu::new();
+    }
 }
 
 impl Cpu {
     pub fn new() -> Self {
+        return Cpu::with_variant(CpuVariant::Ricoh2A03);
+    }
+
+    pub fn with_variant(variant: CpuVariant) -> Self {
+        return Cpu::with_bus(variant, Box::new(FlatRam::new()));
+    }
+
+    pub fn with_bus(variant: CpuVariant, bus: Box<dyn Bus>) -> Self {
         return Cpu {
-            memory: vec![0; MEM_SIZE],
+            bus,
             rega: 0,
             regx: 0,
             regy: 0,
@@ -28,233 +64,376 @@ impl Cpu {
             pc: 0,
             // stack: Vec::new(),
             cycles_taken: 0,
+            cycles: 0,
+            page_crossed: false,
+            branch_taken: false,
             sp: 0xFF,
+            nmi_pending: false,
+            irq_line: false,
+            jam_behavior: JamBehavior::Halt,
+            jammed: false,
+            variant,
+            exec_mode: ExecMode::Instruction,
+            micro: MicroState::new(),
         }
     }
 
-    pub fn exec_instruction(&mut self, op: Ops) {
-        match op {
-            Ops::AdcI => self.adc(AddrModes::Immediate),
-            Ops::AdcZp => self.adc(AddrModes::ZeroPage),
-            Ops::AdcZpX => self.adc(AddrModes::ZeroPageX),
-            Ops::AdcAbs => self.adc(AddrModes::Absolute),
-            Ops::AdcAbsX => self.adc(AddrModes::AbsoluteX),
-            Ops::AdcAbsY => self.adc(AddrModes::AbsoluteY),
-            Ops::AdcIndX => self.adc(AddrModes::IndirectX),
-            Ops::AdcIndY => self.adc(AddrModes::IndirectY),
-
-            Ops::AndI => self.and(AddrModes::Immediate),
-            Ops::AndZp => self.and(AddrModes::ZeroPage),
-            Ops::AndZpX => self.and(AddrModes::ZeroPageX),
-            Ops::AndAbs => self.and(AddrModes::Absolute),
-            Ops::AndAbsX => self.and(AddrModes::AbsoluteX),
-            Ops::AndAbsY => self.and(AddrModes::AbsoluteY),
-            Ops::AndIndX => self.and(AddrModes::IndirectX),
-            Ops::AndIndY => self.and(AddrModes::IndirectY),
-
-            Ops::AslAccum => self.asl(AddrModes::Accumulator),
-            Ops::AslZp => self.asl(AddrModes::ZeroPage),
-            Ops::AslZpX => self.asl(AddrModes::ZeroPageX),
-            Ops::AslAbs => self.asl(AddrModes::Absolute),
-            Ops::AslAbsX => self.asl(AddrModes::AbsoluteX),
-
-            Ops::Bcc | Ops::Bcs => self.bcc_or_bcs(op != Ops::Bcc),
-            Ops::Beq | Ops::Bne => self.beq_or_bne(op == Ops::Beq),
-            Ops::Bmi => self.bmi(),
-            Ops::Bpl => self.bpl(),
-            Ops::Bvs | Ops::Bvc => self.bvs_or_bvc(op == Ops::Bvs),
-
-            Ops::BitAbs => self.bit(AddrModes::Absolute),
-            Ops::BitZp => self.bit(AddrModes::ZeroPage),
-
-            Ops::Brk => self.brk(),
-
-            Ops::Clc => self.flags.carry = false,
-            Ops::Cld => self.flags.decimal = false,
-            Ops::Cli => self.flags.inter_disable = false,
-            Ops::Clv => self.flags.overflow = false,
-
-            Ops::CmpI => self.cmp(AddrModes::Immediate, Registers::A),
-            Ops::CmpZp => self.cmp(AddrModes::ZeroPage, Registers::A),
-            Ops::CmpZpX => self.cmp(AddrModes::ZeroPageX, Registers::A),
-            Ops::CmpAbs => self.cmp(AddrModes::Absolute, Registers::A),
-            Ops::CmpAbsX => self.cmp(AddrModes::AbsoluteX, Registers::A),
-            Ops::CmpAbsY => self.cmp(AddrModes::AbsoluteY, Registers::A),
-            Ops::CmpIndX => self.cmp(AddrModes::IndirectX, Registers::A),
-            Ops::CmpIndY => self.cmp(AddrModes::IndirectY, Registers::A),
-            Ops::CmpXI => self.cmp(AddrModes::Immediate, Registers::X),
-            Ops::CmpXZp => self.cmp(AddrModes::ZeroPage, Registers::X),
-            Ops::CmpXAbs => self.cmp(AddrModes::Absolute, Registers::X),
-            Ops::CmpYI => self.cmp(AddrModes::Immediate, Registers::Y),
-            Ops::CmpYZp => self.cmp(AddrModes::ZeroPage, Registers::Y),
-            Ops::CmpYAbs => self.cmp(AddrModes::Absolute, Registers::Y),
-
-            Ops::DecZp => self.dec(AddrModes::ZeroPage),
-            Ops::DecZpX => self.dec(AddrModes::ZeroPageX),
-            Ops::DecAbs => self.dec(AddrModes::Absolute),
-            Ops::DecAbsX => self.dec(AddrModes::AbsoluteX),
-            Ops::DecX => self.dec_reg(Registers::X),
-            Ops::DecY => self.dec_reg(Registers::Y),
-
-            Ops::EORI => self.eor(AddrModes::Immediate),
-            Ops::EORZp => self.eor(AddrModes::ZeroPage),
-            Ops::EORZpX => self.eor(AddrModes::ZeroPageX),
-            Ops::EORAbs => self.eor(AddrModes::Absolute),
-            Ops::EORAbsX => self.eor(AddrModes::AbsoluteX),
-            Ops::EORAbsY => self.eor(AddrModes::AbsoluteY),
-            Ops::EORIndX => self.eor(AddrModes::IndirectX),
-            Ops::EORIndY => self.eor(AddrModes::IndirectY),
-
-            Ops::IncZp => self.inc(AddrModes::ZeroPage),
-            Ops::IncZpX => self.inc(AddrModes::ZeroPageX),
-            Ops::IncAbs => self.inc(AddrModes::Absolute),
-            Ops::IncAbsX => self.inc(AddrModes::AbsoluteX),
-            Ops::IncX => self.inc_reg(Registers::X),
-            Ops::IncY => self.inc_reg(Registers::Y),
-
-            Ops::JmpInd => self.jmp(AddrModes::Indirect),
-            Ops::JmpAbs => self.jmp(AddrModes::Absolute),
-            Ops::Jsr => self.jsr(),
-
-            Ops::LdaI => self.ld_reg(AddrModes::Immediate, Registers::A),
-            Ops::LdaZp => self.ld_reg(AddrModes::ZeroPage, Registers::A),
-            Ops::LdaZpX => self.ld_reg(AddrModes::ZeroPageX, Registers::A),
-            Ops::LdaAbs => self.ld_reg(AddrModes::Absolute, Registers::A),
-            Ops::LdaAbsX => self.ld_reg(AddrModes::AbsoluteX, Registers::A),
-            Ops::LdaAbsY => self.ld_reg(AddrModes::AbsoluteY, Registers::A),
-            Ops::LdaIndX => self.ld_reg(AddrModes::IndirectX, Registers::A),
-            Ops::LdaIndY => self.ld_reg(AddrModes::IndirectY, Registers::A),
-
-            Ops::LdxI => self.ld_reg(AddrModes::Immediate, Registers::X),
-            Ops::LdxZp => self.ld_reg(AddrModes::ZeroPage, Registers::X),
-            Ops::LdxZpY => self.ld_reg(AddrModes::ZeroPageY, Registers::X),
-            Ops::LdxAbs => self.ld_reg(AddrModes::Absolute, Registers::X),
-            Ops::LdxAbsY => self.ld_reg(AddrModes::AbsoluteY, Registers::X),
-
-            Ops::LdyI => self.ld_reg(AddrModes::Immediate, Registers::Y),
-            Ops::LdyZp => self.ld_reg(AddrModes::ZeroPage, Registers::Y),
-            Ops::LdyZpX => self.ld_reg(AddrModes::ZeroPageX, Registers::Y),
-            Ops::LdyAbs => self.ld_reg(AddrModes::Absolute, Registers::Y),
-            Ops::LdyAbsX => self.ld_reg(AddrModes::AbsoluteY, Registers::Y),
-
-            Ops::LsrAccum => self.lsr(AddrModes::Accumulator),
-            Ops::LsrZp => self.lsr(AddrModes::ZeroPage),
-            Ops::LsrZpX => self.lsr(AddrModes::ZeroPageX),
-            Ops::LsrAbs => self.lsr(AddrModes::Absolute),
-            Ops::LsrAbsX => self.lsr(AddrModes::AbsoluteX),
-
-            Ops::Nop => {},
-
-            Ops::ORI => self.or(AddrModes::Immediate),
-            Ops::ORZp => self.or(AddrModes::ZeroPage),
-            Ops::ORZpX => self.or(AddrModes::ZeroPageX),
-            Ops::ORAbs => self.or(AddrModes::Absolute),
-            Ops::ORAbsX => self.or(AddrModes::AbsoluteX),
-            Ops::ORAbsY => self.or(AddrModes::AbsoluteY),
-            Ops::ORIndX => self.or(AddrModes::IndirectX),
-            Ops::ORIndY => self.or(AddrModes::IndirectY),
-
-            Ops::Pha => self.push_stack(self.rega),
-            Ops::Php => self.save_status(),
-
-            Ops::Pla => self.pull_register(true),
-            Ops::Plp => self.pull_register(false),
-
-            Ops::RolAccum => self.ror_or_rol(AddrModes::Accumulator, false),
-            Ops::RolZp => self.ror_or_rol(AddrModes::ZeroPage, false),
-            Ops::RolZpX => self.ror_or_rol(AddrModes::ZeroPageX, false),
-            Ops::RolAbs => self.ror_or_rol(AddrModes::Absolute, false),
-            Ops::RolAbsX => self.ror_or_rol(AddrModes::AbsoluteX, false),
-
-            Ops::RorAccum => self.ror_or_rol(AddrModes::Accumulator, true),
-            Ops::RorZp => self.ror_or_rol(AddrModes::ZeroPage, true),
-            Ops::RorZpX => self.ror_or_rol(AddrModes::ZeroPageX, true),
-            Ops::RorAbs => self.ror_or_rol(AddrModes::Absolute, true),
-            Ops::RorAbsX => self.ror_or_rol(AddrModes::AbsoluteX, true),
-
-            Ops::Rti | Ops::Rts => self.rti(),
-
-            Ops::SbcI => self.sbc(AddrModes::Immediate),
-            Ops::SbcZp => self.sbc(AddrModes::ZeroPage),
-            Ops::SbcZpX => self.sbc(AddrModes::ZeroPageX),
-            Ops::SbcAbs => self.sbc(AddrModes::Absolute),
-            Ops::SbcAbsX => self.sbc(AddrModes::AbsoluteX),
-            Ops::SbcAbsY => self.sbc(AddrModes::AbsoluteY),
-            Ops::SbcIndX => self.sbc(AddrModes::IndirectX),
-            Ops::SbcIndY => self.sbc(AddrModes::IndirectY),
-
-            Ops::Sec => self.flags.carry = true,
-            Ops::Sed => self.flags.decimal = true,
-            Ops::Sei => self.flags.inter_disable = true,
-
-            Ops::StaZp => self.store_reg(AddrModes::ZeroPage, Registers::A),
-            Ops::StaZpX => self.store_reg(AddrModes::ZeroPageX, Registers::A),
-            Ops::StaAbs => self.store_reg(AddrModes::Absolute, Registers::A),
-            Ops::StaAbsX => self.store_reg(AddrModes::AbsoluteX, Registers::A),
-            Ops::StaAbsY => self.store_reg(AddrModes::AbsoluteY, Registers::A),
-            Ops::StaIndX => self.store_reg(AddrModes::IndirectX, Registers::A),
-            Ops::StaIndY => self.store_reg(AddrModes::IndirectY, Registers::A),
-
-            Ops::StxZp => self.store_reg(AddrModes::ZeroPage, Registers::X),
-            Ops::StxZpY => self.store_reg(AddrModes::ZeroPageY, Registers::X),
-            Ops::StxAbs => self.store_reg(AddrModes::Absolute, Registers::X),
-
-            Ops::StyZp => self.store_reg(AddrModes::ZeroPage, Registers::Y),
-            Ops::StyZpX => self.store_reg(AddrModes::ZeroPageX, Registers::Y),
-            Ops::StyAbs => self.store_reg(AddrModes::Absolute, Registers::Y),
-
-            Ops::Tax => self.transfer_reg(TransferOption::A, TransferOption::X),
-            Ops::Tay => self.transfer_reg(TransferOption::A, TransferOption::Y),
-            Ops::Tsx => self.transfer_reg(TransferOption::S, TransferOption::X),
-            Ops::Txa => self.transfer_reg(TransferOption::X, TransferOption::A),
-            Ops::Txs => self.transfer_reg(TransferOption::X, TransferOption::S),
-            Ops::Tya => self.transfer_reg(TransferOption::Y, TransferOption::A),
-
-            _ => self.cry(op as u8)
+    // Service a pending interrupt, or fetch the opcode at pc, execute it and leave pc
+    // on the next instruction. Returns the number of cycles taken, pc is left on the
+    // faulting opcode if an error is returned. Cycles the cpu spends halted for DMA
+    // first are added to the total but not to the returned count
+    pub fn step(&mut self) -> CpuResult<u8> {
+        if self.exec_mode == ExecMode::Cycle || self.micro.in_progress() {
+            return self.step_cycles();
+        }
+        if self.jammed {
+            return Ok(0);
+        }
+        self.run_dma();
+        if self.poll_interrupts() {
+            return Ok(self.cycles_taken);
+        }
+
+        let byte = self.read(self.pc);
+        let inst = match self.variant.lookup(byte) {
+            Some(inst) => inst,
+            None => return Err(CpuError::InvalidOpcode { op: byte, pc: self.pc }),
+        };
+
+        let op_pc = self.pc;
+        self.page_crossed = false;
+        self.branch_taken = false;
+        if let Err(err) = self.execute(inst) {
+            self.pc = op_pc;
+            return Err(err);
+        }
+        if !inst.redirects_pc() && !self.jammed {
+            self.pc = self.pc.wrapping_add(1);
         }
+        let cycles = self.instruction_cycles(inst);
+        self.add_cycles(cycles);
+        return Ok(cycles);
     }
 
-    fn cry(&self, op: u8) {
-        panic!("Invalid opcode given: {:#02x}", op);
+    // Advance exactly one cpu cycle, making that cycle's bus access. Can be mixed with
+    // step(), which finishes any instruction left half done by tick()
+    pub fn tick(&mut self) -> CpuResult<()> {
+        return self.run_cycle();
     }
 
-    fn get_address(&mut self, mode: AddrModes) -> usize {
-        match mode {
+    pub fn set_exec_mode(&mut self, mode: ExecMode) {
+        self.exec_mode = mode;
+    }
+
+    pub fn set_jam_behavior(&mut self, behavior: JamBehavior) {
+        self.jam_behavior = behavior;
+    }
+
+    // Run whole instructions until at least `cycles` cycles have elapsed or the cpu jams.
+    // Returns the number of cycles actually run, which may overshoot by part of an instruction
+    pub fn run_for_cycles(&mut self, cycles: u64) -> CpuResult<u64> {
+        let start = self.cycles;
+        while self.cycles - start < cycles && !self.jammed {
+            self.step()?;
+        }
+        return Ok(self.cycles - start);
+    }
+
+    // Run instructions until `done` returns true for the cpu state between two instructions, or the cpu jams.
+    // Returns the number of cycles run
+    pub fn run_until<F: FnMut(&Cpu) -> bool>(&mut self, mut done: F) -> CpuResult<u64> {
+        let start = self.cycles;
+        while !done(self) && !self.jammed {
+            self.step()?;
+        }
+        return Ok(self.cycles - start);
+    }
+
+    pub fn exec_instruction(&mut self, op: Ops) -> CpuResult<()> {
+        let inst = match self.variant.lookup(op as u8) {
+            Some(inst) => inst,
+            None => return Err(CpuError::InvalidOpcode { op: op as u8, pc: self.pc }),
+        };
+        return self.execute(inst);
+    }
+
+    // Dispatch on how the instruction uses its operand, the addressing mode comes from
+    // the opcode table. The cycle-stepped core shares the same per-operation helpers
+    fn execute(&mut self, inst: &Instruction) -> CpuResult<()> {
+        let mode = inst.mode;
+        match inst.access() {
+            Access::Read => self.read_operand(inst)?,
+            Access::Write => self.store(inst.mnemonic, mode)?,
+            Access::Modify => { self.modify(inst)?; },
+            Access::Implied if inst.is_single_cycle_nop() => (),
+            // Implied ops read the next byte and throw it away
+            Access::Implied => {
+                self.read(self.pc.wrapping_add(1));
+                self.implied_op(inst.mnemonic)?;
+            },
+            Access::Push => {
+                self.read(self.pc.wrapping_add(1));
+                self.push_op(inst.mnemonic);
+            },
+            // Pulls also read the stack before sp is incremented
+            Access::Pull => {
+                self.read(self.pc.wrapping_add(1));
+                self.read(STACK_BASE + self.sp as u16);
+                self.pull_op(inst.mnemonic);
+            },
+            Access::Branch => self.branch(self.branch_condition(inst.mnemonic)),
+            Access::Jump => self.jmp(mode)?,
+            Access::Jsr => self.jsr(),
+            Access::Rts => self.rts(),
+            Access::Rti => self.rti(),
+            Access::Brk => self.brk(),
+            // The halted cpu leaves the address bus at $FFFF
+            Access::Jam => {
+                self.read(0xFFFF);
+                self.jam(inst.op)?;
+            },
+        }
+        return Ok(());
+    }
+
+    // Stores and read-modify-writes pass always_fix so indexed modes make their dummy
+    // read even without a page cross
+    fn get_address(&mut self, mode: AddrModes, always_fix: bool) -> CpuResult<u16> {
+        let addr = match mode {
             AddrModes::ZeroPage => self.fetch_zp(),
             AddrModes::ZeroPageX => self.fetch_zpx(),
+            AddrModes::ZeroPageY => self.fetch_zpy(),
             AddrModes::Absolute => self.fetch_abs(),
-            AddrModes::AbsoluteX => self.fetch_absx(),
-            AddrModes::AbsoluteY => self.fetch_absy(),
+            AddrModes::AbsoluteX => self.fetch_absx(always_fix),
+            AddrModes::AbsoluteY => self.fetch_absy(always_fix),
             AddrModes::IndirectX => self.fetch_indirectx(),
-            AddrModes::IndirectY => self.fetch_indirecty(),
+            AddrModes::IndirectY => self.fetch_indirecty(always_fix),
             AddrModes::Indirect => self.fetch_indirect(),
-            _=> panic!("Invalid AddrMode for fetching address: {:?}", mode),
-        }
+            AddrModes::ZeroPageIndirect => self.fetch_zp_indirect(),
+            AddrModes::AbsoluteIndirectX => self.fetch_abs_indirectx(),
+            _ => return Err(CpuError::InvalidAddressingMode { mode, pc: self.pc }),
+        };
+        return Ok(addr);
     }
 
-    fn get_value(&mut self, mode: AddrModes) -> u8 {
-        match mode {
-            AddrModes::Immediate => self.fetch_next_byte(),
-            _ => {
-                let addr = self.get_address(mode);
-                self.memory[addr]
+    fn read_operand(&mut self, inst: &Instruction) -> CpuResult<()> {
+        if inst.is_stall_nop() {
+            let addr = self.fetch_abs();
+            for _ in 0..5 {
+                self.read(0xFF00 | (addr & 0x00FF));
             }
+            return Ok(());
+        }
+        let addr = match inst.mode {
+            AddrModes::Immediate => {
+                self.pc = self.pc.wrapping_add(1);
+                self.pc
+            },
+            _ => self.get_address(inst.mode, false)?,
+        };
+        let val = self.read(addr);
+        self.read_op(inst, val);
+        // The 65C02 spends its extra decimal mode cycle reading the operand again
+        if self.decimal_penalty(inst.mnemonic) {
+            self.read(addr);
+        }
+        return Ok(());
+    }
+
+    // Everything that only reads its operand, once the value is off the bus
+    pub (super) fn read_op(&mut self, inst: &Instruction, val: u8) {
+        match inst.mnemonic {
+            Mnemonic::Adc => self.add_with_carry(val),
+            Mnemonic::Sbc => self.sub_with_borrow(val),
+            Mnemonic::And => self.set_rega(self.rega & val),
+            Mnemonic::Ora => self.set_rega(self.rega | val),
+            Mnemonic::Eor => self.set_rega(self.rega ^ val),
+
+            Mnemonic::Cmp => alu::compare(&mut self.flags, self.rega, val),
+            Mnemonic::Cpx => alu::compare(&mut self.flags, self.regx, val),
+            Mnemonic::Cpy => alu::compare(&mut self.flags, self.regy, val),
+
+            // BIT #imm on the 65C02 has no memory operand to copy N and V from
+            Mnemonic::Bit => if inst.mode == AddrModes::Immediate {
+                self.flags.zero = self.rega & val == 0;
+            } else {
+                alu::bit(&mut self.flags, self.rega, val);
+            },
+
+            Mnemonic::Lda => self.set_rega(val),
+            Mnemonic::Ldx => self.load_reg(Registers::X, val),
+            Mnemonic::Ldy => self.load_reg(Registers::Y, val),
+            Mnemonic::Lax => {
+                self.regx = val;
+                self.set_rega(val);
+            },
+
+            // AND then copy N into C
+            Mnemonic::Anc => {
+                self.set_rega(self.rega & val);
+                self.flags.carry = self.flags.negative;
+            },
+            Mnemonic::Alr => {
+                self.set_rega(self.rega & val);
+                self.rega = alu::lsr(&mut self.flags, self.rega);
+            },
+            // AND then ROR, but C comes from bit 6 and V from bit 6 xor bit 5 of the result
+            Mnemonic::Arr => {
+                self.set_rega(self.rega & val);
+                self.rega = alu::ror(&mut self.flags, self.rega);
+                self.flags.carry = check_bit(self.rega, 7);
+                self.flags.overflow = check_bit(self.rega, 7) ^ check_bit(self.rega, 6);
+            },
+            // X = (A & X) - imm, carry like CMP and no borrow in
+            Mnemonic::Axs => {
+                let masked = self.rega & self.regx;
+                alu::compare(&mut self.flags, masked, val);
+                self.regx = masked.wrapping_sub(val);
+            },
+            _ => {},
         }
     }
 
+    // The value a store puts on the bus
+    pub (super) fn store_value(&self, mnemonic: Mnemonic) -> u8 {
+        return match mnemonic {
+            Mnemonic::Sta => self.rega,
+            Mnemonic::Stx => self.regx,
+            Mnemonic::Sty => self.regy,
+            Mnemonic::Sax => self.rega & self.regx,
+            // STZ
+            _ => 0,
+        };
+    }
+
+    fn store(&mut self, mnemonic: Mnemonic, mode: AddrModes) -> CpuResult<()> {
+        let addr = self.get_address(mode, true)?;
+        self.write(addr, self.store_value(mnemonic));
+        return Ok(());
+    }
+
+    // The new value for a read-modify-write. The unofficial combined ops then feed
+    // it into a second operation on A
+    pub (super) fn modify_op(&mut self, mnemonic: Mnemonic, val: u8) -> u8 {
+        let res = match mnemonic {
+            Mnemonic::Asl | Mnemonic::Slo => alu::asl(&mut self.flags, val),
+            Mnemonic::Lsr | Mnemonic::Sre => alu::lsr(&mut self.flags, val),
+            Mnemonic::Rol | Mnemonic::Rla => alu::rol(&mut self.flags, val),
+            Mnemonic::Ror | Mnemonic::Rra => alu::ror(&mut self.flags, val),
+            Mnemonic::Inc | Mnemonic::Isc => alu::inc(&mut self.flags, val),
+            Mnemonic::Dec | Mnemonic::Dcp => alu::dec(&mut self.flags, val),
+            // TSB and TRB: Z from A & M like BIT, then set or clear the bits of A in M
+            Mnemonic::Tsb | Mnemonic::Trb => {
+                self.flags.zero = self.rega & val == 0;
+                if mnemonic == Mnemonic::Tsb { val | self.rega } else { val & !self.rega }
+            },
+            _ => val,
+        };
+        match mnemonic {
+            Mnemonic::Slo => self.set_rega(self.rega | res),
+            Mnemonic::Rla => self.set_rega(self.rega & res),
+            Mnemonic::Sre => self.set_rega(self.rega ^ res),
+            Mnemonic::Rra => self.add_with_carry(res),
+            Mnemonic::Dcp => alu::compare(&mut self.flags, self.rega, res),
+            Mnemonic::Isc => self.sub_with_borrow(res),
+            _ => {},
+        }
+        return res;
+    }
+
+    // Read-modify-write on memory, returns the value written back
+    // The 65C02 shifts on abs,X are a cycle shorter when the index doesn't carry
+    fn modify(&mut self, inst: &Instruction) -> CpuResult<u8> {
+        let mnemonic = inst.mnemonic;
+        let addr = self.get_address(inst.mode, !inst.page_penalty)?;
+        let val = self.read(addr);
+        // The old value goes back out while the new one is computed, the 65C02
+        // reads the address again instead
+        if self.variant.is_cmos() {
+            self.read(addr);
+        } else {
+            self.write(addr, val);
+        }
+        let res = self.modify_op(mnemonic, val);
+        self.write(addr, res);
+        return Ok(res);
+    }
+
+    // Single byte ops that only touch registers, including the shifts on A
+    pub (super) fn implied_op(&mut self, mnemonic: Mnemonic) -> CpuResult<()> {
+        match mnemonic {
+            Mnemonic::Clc => self.flags.carry = false,
+            Mnemonic::Cld => self.flags.decimal = false,
+            Mnemonic::Cli => self.flags.inter_disable = false,
+            Mnemonic::Clv => self.flags.overflow = false,
+            Mnemonic::Sec => self.flags.carry = true,
+            Mnemonic::Sed => self.flags.decimal = true,
+            Mnemonic::Sei => self.flags.inter_disable = true,
+
+            Mnemonic::Dex => self.dec_reg(Registers::X)?,
+            Mnemonic::Dey => self.dec_reg(Registers::Y)?,
+            Mnemonic::Inx => self.inc_reg(Registers::X)?,
+            Mnemonic::Iny => self.inc_reg(Registers::Y)?,
+
+            Mnemonic::Tax => self.transfer_reg(TransferOption::A, TransferOption::X),
+            Mnemonic::Tay => self.transfer_reg(TransferOption::A, TransferOption::Y),
+            Mnemonic::Tsx => self.transfer_reg(TransferOption::S, TransferOption::X),
+            Mnemonic::Txa => self.transfer_reg(TransferOption::X, TransferOption::A),
+            Mnemonic::Txs => self.transfer_reg(TransferOption::X, TransferOption::S),
+            Mnemonic::Tya => self.transfer_reg(TransferOption::Y, TransferOption::A),
+
+            Mnemonic::Asl | Mnemonic::Lsr | Mnemonic::Rol | Mnemonic::Ror
+            | Mnemonic::Inc | Mnemonic::Dec => self.rega = self.modify_op(mnemonic, self.rega),
+            _ => {},
+        }
+        return Ok(());
+    }
+
+    pub (super) fn push_op(&mut self, mnemonic: Mnemonic) {
+        match mnemonic {
+            Mnemonic::Php => self.save_status(true),
+            Mnemonic::Phx => self.push_stack(self.regx),
+            Mnemonic::Phy => self.push_stack(self.regy),
+            _ => self.push_stack(self.rega),
+        }
+    }
+
+    pub (super) fn pull_op(&mut self, mnemonic: Mnemonic) {
+        match mnemonic {
+            Mnemonic::Plp => self.pull_status(),
+            Mnemonic::Plx => self.pull_reg(Registers::X),
+            Mnemonic::Ply => self.pull_reg(Registers::Y),
+            _ => self.pull_reg(Registers::A),
+        }
+    }
+
+    pub (super) fn branch_condition(&self, mnemonic: Mnemonic) -> bool {
+        return match mnemonic {
+            Mnemonic::Bcc => !self.flags.carry,
+            Mnemonic::Bcs => self.flags.carry,
+            Mnemonic::Beq => self.flags.zero,
+            Mnemonic::Bne => !self.flags.zero,
+            Mnemonic::Bmi => self.flags.negative,
+            Mnemonic::Bpl => !self.flags.negative,
+            Mnemonic::Bvs => self.flags.overflow,
+            Mnemonic::Bvc => !self.flags.overflow,
+            _ => true,
+        };
+    }
+
     fn transfer_reg(&mut self, from: TransferOption, to: TransferOption) {
         let val = match from {
             TransferOption::A => self.rega,
             TransferOption::X => self.regx,
             TransferOption::Y => self.regy,
-            TransferOption::S => self.sp as u8,
+            TransferOption::S => self.sp,
         };
 
         match to {
             TransferOption::A => self.rega = val,
             TransferOption::X => self.regx = val,
             TransferOption::Y => self.regy = val,
-            TransferOption::S => self.sp = val as usize,
+            TransferOption::S => self.sp = val,
         };
         if to != TransferOption::S {
             self.flags.zero = val == 0;
@@ -262,310 +441,754 @@ impl Cpu {
         }
     }
 
-    fn store_reg(&mut self, mode: AddrModes, reg: Registers) {
-        let addr = self.get_address(mode);
-        self.memory[addr] = match reg {
-            Registers::A => self.rega,
-            Registers::X => self.regx,
-            Registers::Y => self.regy
-        };
+    fn set_rega(&mut self, val: u8) {
+        self.load_reg(Registers::A, val);
     }
 
-    fn sbc(&mut self, mode: AddrModes) {
-        let val = self.get_value(mode);
-        let (res, carry) = self.rega.overflowing_sub(val + (!self.flags.carry as u8));
-        self.flags.overflow = is_overflow(res, self.rega, val);
-        self.rega = res;
-        self.flags.carry = carry;
-        self.flags.zero = self.rega == 0;
-        self.flags.negative = get_top_bit(self.rega);
-    }
-
-    fn rti(&mut self) {
-        self.pull_register(false);
-        self.pull_pc();
-    }
-
-    fn ror_or_rol(&mut self, mode: AddrModes, is_ror: bool) {
-        let old_carry = self.flags.carry;
-        let (val, carry) = match mode {
-            AddrModes::Accumulator => {
-                let mut carry = false;
-
-                if !is_ror {
-                    carry = get_top_bit(self.rega);
-                    self.rega <<= 1;
-                    if old_carry {
-                        self.rega |= 0x1;
-                    }
-                } else {
-                    carry = check_bit(self.rega, 1);
-                    self.rega >>= 1;
-                    if old_carry {
-                        self.rega |= 0x80;
-                    }
-                }
-                (self.rega, carry)
-            },
-            AddrModes::ZeroPage | AddrModes::ZeroPageX | AddrModes::Absolute | AddrModes::AbsoluteX => {
-                let addr = self.get_address(mode);
-                let mut carry = false;
-                if !is_ror {
-                    carry = get_top_bit(self.memory[addr]);
-                    self.memory[addr] <<= 1;
-                    if old_carry {
-                        self.memory[addr] &= 0x1;
-                    }
-                } else {
-                    carry = check_bit(self.memory[addr], 1);
-                    self.memory[addr] >>= 1;
-                    if old_carry {
-                        self.memory[addr] &= 0x80;
-                    }
-                }
-                (self.memory[addr], carry)
-            },
-            _ => panic!("Invalid AddrMode for ROL: {:?}", mode),
+    fn load_reg(&mut self, reg: Registers, val: u8) {
+        match reg {
+            Registers::A => self.rega = val,
+            Registers::X => self.regx = val,
+            Registers::Y => self.regy = val,
         };
+        alu::set_zn(&mut self.flags, val);
+    }
 
-        self.flags.zero = val == 0;
-        self.flags.negative = get_top_bit(val);
-        self.flags.carry = carry;
+    fn sub_with_borrow(&mut self, val: u8) {
+        self.rega = if self.decimal_mode() {
+            alu::sbc_decimal(&mut self.flags, self.rega, val, self.variant.is_cmos())
+        } else {
+            alu::sbc(&mut self.flags, self.rega, val)
+        };
     }
 
-    fn pull_register(&mut self, pull_accum: bool) {
-        let popped = self.pop_stack();
-        match pull_accum {
-            true =>{
-                self.rega = popped;
-                self.flags.zero = self.rega == 0;
-                self.flags.negative = get_top_bit(self.rega);
-            },
-            false => {
-                self.flags = Flags::from(popped);
-            }
+    fn add_with_carry(&mut self, val: u8) {
+        self.rega = if self.decimal_mode() {
+            alu::adc_decimal(&mut self.flags, self.rega, val, self.variant.is_cmos())
+        } else {
+            alu::adc(&mut self.flags, self.rega, val)
         };
+    }
 
+    // D only changes ADC and SBC on variants that kept the decimal adder
+    pub (super) fn decimal_mode(&self) -> bool {
+        return self.flags.decimal && self.variant.has_decimal_mode();
     }
 
-    fn or(&mut self, mode: AddrModes) {
-        let val = self.get_value(mode);
-        self.rega |= val;
-        self.flags.zero = self.rega == 0;
-        self.flags.negative = get_top_bit(self.rega);
+    fn pull_reg(&mut self, reg: Registers) {
+        let val = self.pop_stack();
+        self.load_reg(reg, val);
     }
 
-    fn lsr(&mut self, mode: AddrModes) {
-        let (val, carry) = match mode {
-            AddrModes::Accumulator => {
-                let carry = check_bit(self.rega, 1);
-                self.rega >>= 1;
-                (self.rega, carry)
-            },
-            AddrModes::ZeroPage | AddrModes::ZeroPageX | AddrModes::Absolute | AddrModes::AbsoluteX => {
-                let addr = self.get_address(mode);
-                let carry = check_bit(self.memory[addr], 1);
-                self.memory[addr] >>= 1;
-                (self.memory[addr], carry)
-            },
-            _ => panic!("Invalid AddrMode for LSR: {:?}", mode),
-        };
+    fn jmp(&mut self, mode: AddrModes) -> CpuResult<()> {
+        self.pc = self.get_address(mode, false)?;
+        return Ok(());
+    }
 
-        self.flags.zero = val == 0;
-        self.flags.negative = get_top_bit(val);
-        self.flags.carry = carry;
+    fn inc_reg(&mut self, reg: Registers) -> CpuResult<()> {
+        match reg {
+            Registers::X => self.regx = alu::inc(&mut self.flags, self.regx),
+            Registers::Y => self.regy = alu::inc(&mut self.flags, self.regy),
+            Registers::A => return Err(CpuError::InvalidRegister { reg, pc: self.pc }),
+        };
+        return Ok(());
     }
 
-    fn ld_reg(&mut self, mode: AddrModes, reg: Registers) {
-        let val = self.get_value(mode);
+    fn dec_reg(&mut self, reg: Registers) -> CpuResult<()> {
         match reg {
-            Registers::A => self.rega = val,
-            Registers::X => self.regx = val,
-            Registers::Y => self.regy = val,
+            Registers::X => self.regx = alu::dec(&mut self.flags, self.regx),
+            Registers::Y => self.regy = alu::dec(&mut self.flags, self.regy),
+            Registers::A => return Err(CpuError::InvalidRegister { reg, pc: self.pc }),
         };
+        return Ok(());
+    }
 
-        self.flags.zero = val == 0;
-        self.flags.negative = get_top_bit(val);
+    // The offset is a signed byte relative to the instruction after the branch.
+    // Records whether the branch was taken and crossed a page for the timing module
+    fn branch(&mut self, condition: bool) {
+        let offset = self.fetch_next_byte() as i8;
+        if !condition {
+            return;
+        }
+        // Taking the branch reads the next opcode, a carry into the high byte reads
+        // the target's low byte in the old page
+        let next = self.pc.wrapping_add(1);
+        let target = next.wrapping_add(offset as u16);
+        self.read(next);
+        self.branch_taken = true;
+        self.page_crossed = crosses_page(next, target);
+        if self.page_crossed {
+            self.read((next & 0xFF00) | (target & 0x00FF));
+        }
+        // pc is left on the offset byte, step moves it onto the target
+        self.pc = target.wrapping_sub(1);
     }
 
-    fn jsr(&mut self) {
-        self.save_pc(true);
-        self.pc = self.get_address(AddrModes::Absolute) as u16;
+    pub (super) fn jam(&mut self, op: u8) -> CpuResult<()> {
+        match self.jam_behavior {
+            JamBehavior::Halt => self.jammed = true,
+            JamBehavior::Error => return Err(CpuError::Jammed { op, pc: self.pc }),
+        }
+        return Ok(());
     }
+}
 
-    fn jmp(&mut self, mode: AddrModes) {
-        self.pc = self.get_address(mode) as u16;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::debug::DebugUtils;
+    use crate::hardware::stack::STACK_BASE;
+    use crate::hardware::instruction::CmosOps;
+    use crate::hardware::nes_bus::NesBus;
+    use crate::hardware::cycle::ExecMode;
+    use crate::hardware::bus::testing::{Access, RecordingBus};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_step_advances_pc() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0, Ops::LdaI as u8);
+        cpu.poke(1, 0x42);
+        cpu.poke(2, Ops::Tax as u8);
+        cpu.step().unwrap();
+        assert_eq!(cpu.rega, 0x42);
+        assert_eq!(cpu.pc, 2);
+        cpu.step().unwrap();
+        assert_eq!(cpu.regx, 0x42);
+        assert_eq!(cpu.pc, 3);
     }
 
-    fn inc_reg(&mut self, reg: Registers) {
-        let affected = match reg {
-            Registers::X => {
-                self.regx += 1;
-                self.regx
-            },
-            Registers::Y =>  {
-                self.regy += 1;
-                self.regy
-            },
-            Registers::A => panic!("Invalid decrement register A"),
-        };
-        self.flags.zero = affected == 0;
-        self.flags.negative = get_top_bit(affected);
+    #[test]
+    fn test_step_jump() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0, Ops::JmpAbs as u8);
+        cpu.poke(1, 0x34);
+        cpu.poke(2, 0x12);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x1234);
     }
 
-    fn inc(&mut self, mode: AddrModes) {
-        let addr = self.get_address(mode);
-        self.memory[addr] += 1;
-        self.flags.zero = self.memory[addr] == 0;
-        self.flags.negative = get_top_bit(self.memory[addr]);
+    #[test]
+    fn test_step_invalid_opcode() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x0200;
+        cpu.poke(0x0200, 0x8B);
+        assert_eq!(cpu.step(), Err(CpuError::InvalidOpcode { op: 0x8B, pc: 0x0200 }));
+        assert_eq!(cpu.pc, 0x0200);
     }
 
-    fn eor(&mut self, mode: AddrModes) {
-        let value = self.get_value(mode);
-        self.rega ^= value;
-        self.flags.zero = self.rega == 0;
-        self.flags.negative = get_top_bit(self.rega);
+    #[test]
+    fn test_run_stops_on_error() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0, Ops::Nop as u8);
+        cpu.poke(1, 0x8B);
+        assert_eq!(cpu.run_for_cycles(100), Err(CpuError::InvalidOpcode { op: 0x8B, pc: 1 }));
     }
 
-    fn dec_reg(&mut self, reg: Registers) {
-        let affected = match reg {
-            Registers::X => {
-                self.regx -= 1;
-                self.regx
-            },
-            Registers::Y =>  {
-                self.regy -= 1;
-                self.regy
-            },
-            Registers::A => panic!("Invalid decrement register A"),
-        };
-        self.flags.zero = affected == 0;
-        self.flags.negative = get_top_bit(affected);
+    #[test]
+    fn test_invalid_register() {
+        let mut cpu = Cpu::new();
+        assert_eq!(cpu.inc_reg(Registers::A), Err(CpuError::InvalidRegister { reg: Registers::A, pc: 0 }));
+        assert_eq!(cpu.dec_reg(Registers::A), Err(CpuError::InvalidRegister { reg: Registers::A, pc: 0 }));
     }
 
-    fn dec(&mut self, mode: AddrModes) {
-        let addr = self.get_address(mode);
-        self.memory[addr] -= 1;
-        self.flags.zero = self.memory[addr] == 0;
-        self.flags.negative = get_top_bit(self.memory[addr]);
+    // RAM with a status register at $2002 that counts how often the cpu reads it
+    struct CountingBus {
+        ram: FlatRam,
+        reads: Rc<Cell<u32>>,
     }
 
-    fn cmp(&mut self, mode: AddrModes, register: Registers) {
-        let val = self.get_value(mode);
-        let to_compare = match register {
-            Registers::A => self.rega,
-            Registers::X => self.regx,
-            Registers::Y => self.regy
-        };
-        self.flags.set_for_cmp(to_compare, val);
+    impl Bus for CountingBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            if addr == 0x2002 {
+                self.reads.set(self.reads.get() + 1);
+                return 0x80;
+            }
+            return self.ram.read(addr);
+        }
+
+        fn write(&mut self, addr: u16, val: u8) {
+            self.ram.write(addr, val);
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            if addr == 0x2002 {
+                return 0x80;
+            }
+            return self.ram.peek(addr);
+        }
+
+        fn poke(&mut self, addr: u16, val: u8) {
+            if addr != 0x2002 {
+                self.ram.poke(addr, val);
+            }
+        }
     }
 
-    fn bvs_or_bvc(&mut self, check_for_set: bool) {
-        let displace: u16 = self.fetch_next_byte().into();
+    #[test]
+    fn test_custom_bus() {
+        let reads = Rc::new(Cell::new(0));
+        let bus = CountingBus { ram: FlatRam::new(), reads: reads.clone() };
+        let mut cpu = Cpu::with_bus(CpuVariant::Ricoh2A03, Box::new(bus));
+        cpu.poke(0, Ops::LdaAbs as u8);
+        cpu.poke(1, 0x02);
+        cpu.poke(2, 0x20);
+        assert_eq!(cpu.peek(0x2002), 0x80);
+        assert_eq!(reads.get(), 0);
+        cpu.step().unwrap();
+        assert_eq!(cpu.rega, 0x80);
+        assert_eq!(reads.get(), 1);
+    }
 
-        if self.flags.overflow == check_for_set {
-            self.pc += displace;
+    // Runs one instruction from $0200 and returns the accesses it made
+    fn record(variant: CpuVariant, program: &[u8], setup: fn(&mut Cpu)) -> Vec<Access> {
+        let (bus, log) = RecordingBus::new();
+        let mut cpu = Cpu::with_bus(variant, Box::new(bus));
+        for (i, byte) in program.iter().enumerate() {
+            cpu.poke(0x0200 + i as u16, *byte);
         }
+        cpu.pc = 0x0200;
+        setup(&mut cpu);
+        log.borrow_mut().clear();
+        cpu.step().unwrap();
+        return log.take();
+    }
+
+    #[test]
+    fn test_rmw_writes_old_value() {
+        let accesses = record(CpuVariant::Ricoh2A03, &[Ops::IncZp as u8, 0x10], |cpu| cpu.poke(0x10, 0x41));
+        assert_eq!(accesses, vec![
+            Access::Read(0x0200),
+            Access::Read(0x0201),
+            Access::Read(0x0010),
+            Access::Write(0x0010, 0x41),
+            Access::Write(0x0010, 0x42),
+        ]);
+    }
+
+    #[test]
+    fn test_rmw_cmos_reads_twice() {
+        let accesses = record(CpuVariant::Cmos65C02, &[Ops::AslZp as u8, 0x10], |cpu| cpu.poke(0x10, 0x41));
+        assert_eq!(accesses, vec![
+            Access::Read(0x0200),
+            Access::Read(0x0201),
+            Access::Read(0x0010),
+            Access::Read(0x0010),
+            Access::Write(0x0010, 0x82),
+        ]);
+    }
+
+    #[test]
+    fn test_rmw_absx_dummy_read() {
+        let accesses = record(CpuVariant::Ricoh2A03, &[Ops::DecAbsX as u8, 0x00, 0x20], |cpu| cpu.regx = 7);
+        assert_eq!(accesses, vec![
+            Access::Read(0x0200),
+            Access::Read(0x0201),
+            Access::Read(0x0202),
+            Access::Read(0x2007),
+            Access::Read(0x2007),
+            Access::Write(0x2007, 0x00),
+            Access::Write(0x2007, 0xFF),
+        ]);
+    }
+
+    #[test]
+    fn test_cmos_shift_absx_no_dummy_read() {
+        // The 65C02 shifts only fix up the address when the index carries
+        let accesses = record(CpuVariant::Cmos65C02, &[Ops::AslAbsX as u8, 0x00, 0x20], |cpu| cpu.regx = 7);
+        assert_eq!(accesses, vec![
+            Access::Read(0x0200),
+            Access::Read(0x0201),
+            Access::Read(0x0202),
+            Access::Read(0x2007),
+            Access::Read(0x2007),
+            Access::Write(0x2007, 0x00),
+        ]);
+    }
+
+    #[test]
+    fn test_cmos_index_dummy_read() {
+        // The 65C02 reads the last operand byte again instead of the un-carried address
+        let accesses = record(CpuVariant::Cmos65C02, &[Ops::AslAbsX as u8, 0xF0, 0x20], |cpu| cpu.regx = 0x20);
+        assert_eq!(&accesses[2..4], &[Access::Read(0x0202), Access::Read(0x0202)]);
+        assert_eq!(accesses.len(), 7);
+
+        let accesses = record(CpuVariant::Cmos65C02, &[Ops::StaAbsY as u8, 0x00, 0x10], |cpu| cpu.regy = 0x20);
+        assert_eq!(&accesses[3..], &[Access::Read(0x0202), Access::Write(0x1020, 0x00)]);
+    }
+
+    #[test]
+    fn test_load_absx_page_cross_dummy_read() {
+        let accesses = record(CpuVariant::Ricoh2A03, &[Ops::LdaAbsX as u8, 0xF0, 0x10], |cpu| cpu.regx = 0x20);
+        assert_eq!(accesses, vec![
+            Access::Read(0x0200),
+            Access::Read(0x0201),
+            Access::Read(0x0202),
+            Access::Read(0x1010),
+            Access::Read(0x1110),
+        ]);
+    }
+
+    #[test]
+    fn test_load_absx_no_page_cross() {
+        let accesses = record(CpuVariant::Ricoh2A03, &[Ops::LdaAbsX as u8, 0x00, 0x10], |cpu| cpu.regx = 0x20);
+        assert_eq!(accesses.last(), Some(&Access::Read(0x1020)));
+        assert_eq!(accesses.len(), 4);
     }
 
-    fn brk(&mut self) {
-        self.save_pc(false);
-        self.save_status();
-        let (lower, upper) = (self.memory[0xFFFE], self.memory[0xFFFF]);
-        self.pc = combine_bytes(upper.into(), lower.into());
-        self.flags.set_breaks();
+    #[test]
+    fn test_store_absy_dummy_read() {
+        let accesses = record(CpuVariant::Ricoh2A03, &[Ops::StaAbsY as u8, 0x00, 0x10], |cpu| {
+            cpu.rega = 0x55;
+            cpu.regy = 0x20;
+        });
+        assert_eq!(&accesses[3..], &[Access::Read(0x1020), Access::Write(0x1020, 0x55)]);
     }
 
-    fn bpl(&mut self) {
-        let displace: u16 = self.fetch_next_byte().into();
+    #[test]
+    fn test_store_indy_dummy_read() {
+        let accesses = record(CpuVariant::Ricoh2A03, &[Ops::StaIndY as u8, 0x40], |cpu| {
+            cpu.poke(0x40, 0xFF);
+            cpu.poke(0x41, 0x10);
+            cpu.regy = 1;
+        });
+        assert_eq!(&accesses[2..], &[
+            Access::Read(0x0040),
+            Access::Read(0x0041),
+            Access::Read(0x1000),
+            Access::Write(0x1100, 0x00),
+        ]);
+    }
+
+    #[test]
+    fn test_zpx_dummy_read() {
+        let accesses = record(CpuVariant::Ricoh2A03, &[Ops::LdaZpX as u8, 0x80], |cpu| cpu.regx = 0x90);
+        assert_eq!(&accesses[2..], &[Access::Read(0x0080), Access::Read(0x0010)]);
+    }
 
-        if !self.flags.negative {
-            self.pc += displace;
+    #[test]
+    fn test_indx_dummy_read() {
+        let accesses = record(CpuVariant::Ricoh2A03, &[Ops::LdaIndX as u8, 0x20], |cpu| {
+            cpu.poke(0x24, 0x00);
+            cpu.poke(0x25, 0x30);
+            cpu.regx = 4;
+        });
+        assert_eq!(&accesses[2..], &[
+            Access::Read(0x0020),
+            Access::Read(0x0024),
+            Access::Read(0x0025),
+            Access::Read(0x3000),
+        ]);
+    }
+
+    // STA $4014 from page 3 at $0200, then a NOP
+    fn oam_dma_cpu(mode: ExecMode) -> Cpu {
+        let mut cpu = Cpu::with_bus(CpuVariant::Ricoh2A03, Box::new(NesBus::new()));
+        cpu.set_exec_mode(mode);
+        for i in 0..=0xFF {
+            cpu.poke(0x0300 + i, 0xFF - i as u8);
+        }
+        cpu.poke(0x0200, Ops::StaAbs as u8);
+        cpu.poke(0x0201, 0x14);
+        cpu.poke(0x0202, 0x40);
+        cpu.poke(0x0203, Ops::Nop as u8);
+        cpu.pc = 0x0200;
+        cpu.rega = 0x03;
+        return cpu;
+    }
+
+    #[test]
+    fn test_oam_dma_stalls_cpu() {
+        for mode in [ExecMode::Instruction, ExecMode::Cycle] {
+            let mut cpu = oam_dma_cpu(mode);
+            assert_eq!(cpu.step().unwrap(), 4);
+            assert_eq!(cpu.step().unwrap(), 2);
+            assert_eq!(cpu.cycles, 4 + 513 + 2);
+            assert_eq!(cpu.pc, 0x0204);
+            // Through the $2004 data port, $2003 is a register so it takes a real write
+            cpu.bus.write(0x2003, 0x00);
+            assert_eq!(cpu.peek(0x2004), 0xFF);
+            cpu.bus.write(0x2003, 0xFF);
+            assert_eq!(cpu.peek(0x2004), 0x00);
         }
     }
 
-    fn bmi(&mut self) {
-        let displace: u16 = self.fetch_next_byte().into();
+    #[test]
+    fn test_oam_dma_odd_cycle() {
+        let mut cpu = oam_dma_cpu(ExecMode::Instruction);
+        cpu.cycles = 1;
+        let elapsed = cpu.run_for_cycles(5).unwrap();
+        assert_eq!(elapsed, 4 + 514 + 2);
+    }
 
-        if self.flags.negative {
-            self.pc += displace;
+    #[test]
+    fn test_oam_dma_ticks() {
+        let mut cpu = oam_dma_cpu(ExecMode::Cycle);
+        for _ in 0..4 {
+            cpu.tick().unwrap();
+        }
+        for _ in 0..513 {
+            cpu.tick().unwrap();
+            assert_eq!(cpu.pc, 0x0203);
         }
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x0204);
+        assert_eq!(cpu.cycles, 4 + 513 + 1);
+    }
+
+    #[test]
+    fn test_dmc_dma_halts_mid_instruction() {
+        // Requested for the cycle LDA fetches its high byte on, the 4 cycle DMC
+        // fetch runs there instead of waiting for the next instruction
+        let mut bus = NesBus::new();
+        bus.request_dmc(0xC000, 2);
+        let mut cpu = Cpu::with_bus(CpuVariant::Ricoh2A03, Box::new(bus));
+        cpu.set_exec_mode(ExecMode::Cycle);
+        cpu.poke(0, Ops::LdaAbs as u8);
+        cpu.poke(1, 0x10);
+        cpu.poke(2, 0x00);
+        cpu.poke(0x10, 0x42);
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        for _ in 0..4 {
+            cpu.tick().unwrap();
+            assert_eq!(cpu.pc, 0x0002);
+        }
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert_eq!(cpu.cycles, 4 + 4);
+        assert_eq!(cpu.rega, 0x42);
     }
 
-    fn bit(&mut self, mode: AddrModes) {
-        let val = self.get_value(mode);
-        let res = self.rega & val;
-        self.flags.zero = res == 0;
-        self.flags.overflow = check_bit(val, 6);
-        self.flags.negative = check_bit(val, 7);
+    #[test]
+    fn test_nes_bus_stack_mirror() {
+        let mut cpu = Cpu::with_bus(CpuVariant::Ricoh2A03, Box::new(NesBus::new()));
+        cpu.rega = 0x42;
+        cpu.exec_instruction(Ops::Pha).unwrap();
+        assert_eq!(cpu.peek(0x09FF), 0x42);
     }
 
-    fn beq_or_bne(&mut self, should_be_zero: bool) {
-        let displace: u16 = self.fetch_next_byte().into();
+    #[test]
+    fn test_nes_open_bus_operand() {
+        // The last byte on the bus before the read is the operand's high byte
+        let mut cpu = Cpu::with_bus(CpuVariant::Ricoh2A03, Box::new(NesBus::new()));
+        cpu.poke(0, Ops::LdaAbs as u8);
+        cpu.poke(1, 0x1A);
+        cpu.poke(2, 0x40);
+        cpu.step().unwrap();
+        assert_eq!(cpu.rega, 0x40);
+    }
 
-        if self.flags.zero == should_be_zero {
-            self.pc += displace;
+    #[test]
+    fn test_run_until() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0, Ops::IncX as u8);
+        cpu.poke(1, Ops::IncX as u8);
+        cpu.poke(2, Ops::IncX as u8);
+        cpu.run_until(|cpu| cpu.pc == 3).unwrap();
+        assert_eq!(cpu.regx, 3);
+    }
+
+    #[test]
+    fn test_run_for_cycles() {
+        let mut cpu = Cpu::new();
+        for addr in 0..4 {
+            cpu.poke(addr, Ops::Nop as u8);
         }
+        let elapsed = cpu.run_for_cycles(6).unwrap();
+        assert!(elapsed >= 6);
+        assert_eq!(cpu.pc, 3);
     }
 
+    #[test]
+    fn test_step_cycles() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0, Ops::LdaI as u8);
+        cpu.poke(2, Ops::StaAbs as u8);
+        assert_eq!(cpu.step().unwrap(), 2);
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert_eq!(cpu.cycles, 6);
+    }
 
-    fn bcc_or_bcs(&mut self, branch_on_set: bool) {
-        // Might as well do this before since the pc needs to be incremented anyway
-        let displace: u16 = self.fetch_next_byte().into();
+    #[test]
+    fn test_step_cycles_page_cross() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0, Ops::LdaAbsX as u8);
+        cpu.poke(1, 0xFF);
+        cpu.poke(2, 0x10);
+        cpu.regx = 1;
+        assert_eq!(cpu.step().unwrap(), 5);
+        cpu.poke(3, Ops::StaAbsX as u8);
+        cpu.poke(4, 0xFF);
+        cpu.poke(5, 0x10);
+        assert_eq!(cpu.step().unwrap(), 5);
+    }
 
-        if self.flags.carry == branch_on_set {
-            self.pc += displace;
-        }
+    #[test]
+    fn test_step_cycles_branch() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0, Ops::Bcc as u8);
+        cpu.poke(1, 10);
+        assert_eq!(cpu.step().unwrap(), 3);
+        assert_eq!(cpu.pc, 12);
+        cpu.flags.carry = true;
+        cpu.poke(12, Ops::Bcc as u8);
+        assert_eq!(cpu.step().unwrap(), 2);
     }
 
-    fn asl(&mut self, mode: AddrModes) {
-        let (val, carry) = match mode {
-            AddrModes::Accumulator => {
-                let carry = get_top_bit(self.rega);
-                self.rega <<= 1;
-                (self.rega, carry)
-            },
-            AddrModes::ZeroPage | AddrModes::ZeroPageX | AddrModes::Absolute | AddrModes::AbsoluteX => {
-                let addr = self.get_address(mode);
-                let carry = get_top_bit(self.memory[addr]);
-                self.memory[addr] <<= 1;
-                (self.memory[addr], carry)
-            },
-            _ => panic!("Invalid AddrMode for ASL: {:?}", mode),
-        };
+    #[test]
+    fn test_step_cycles_branch_page_cross() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x00F0;
+        cpu.poke(0xF0, Ops::Bcc as u8);
+        cpu.poke(0xF1, 0x20);
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert_eq!(cpu.pc, 0x0112);
+    }
 
-        self.flags.zero = val == 0;
-        self.flags.negative = get_top_bit(val);
-        self.flags.carry = carry;
+    #[test]
+    fn test_branch_backward() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x0210;
+        cpu.poke(0x0210, Ops::Bne as u8);
+        cpu.poke(0x0211, 0xFC);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x020E);
+        assert!(cpu.branch_taken);
+        assert!(!cpu.page_crossed);
     }
 
-    fn and(&mut self, mode: AddrModes) {
-        let val = self.get_value(mode);
-        self.rega &= val;
-        self.flags.zero = self.rega == 0;
-        self.flags.negative = get_top_bit(self.rega);
+    #[test]
+    fn test_branch_backward_page_cross() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x0200;
+        cpu.poke(0x0200, Ops::Beq as u8);
+        cpu.poke(0x0201, 0x80);
+        cpu.flags.zero = true;
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert_eq!(cpu.pc, 0x0182);
     }
 
-    fn adc(&mut self, mode: AddrModes) {
-        let val = self.get_value(mode);
-        let (res, carry) = self.rega.overflowing_add(val + (self.flags.carry as u8));
-        self.flags.overflow = is_overflow(res, self.rega, val);
-        self.rega = res;
-        self.flags.carry = carry;
-        self.flags.zero = self.rega == 0;
-        self.flags.negative = get_top_bit(self.rega);
+    #[test]
+    fn test_branch_wraps_pc() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0, Ops::Bpl as u8);
+        cpu.poke(1, 0xF0);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0xFFF2);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_branch_loop() {
+        let mut cpu = Cpu::new();
+        // LDX #5; DEX; BNE -3
+        cpu.poke(0, Ops::LdxI as u8);
+        cpu.poke(1, 5);
+        cpu.poke(2, Ops::DecX as u8);
+        cpu.poke(3, Ops::Bne as u8);
+        cpu.poke(4, 0xFD);
+        cpu.run_until(|cpu| cpu.pc == 5).unwrap();
+        assert_eq!(cpu.regx, 0);
+    }
+
+    #[test]
+    fn test_lax() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x10);
+        cpu.poke(0x10, 0x80);
+        cpu.exec_instruction(Ops::LaxZp).unwrap();
+        assert_eq!(cpu.rega, 0x80);
+        assert_eq!(cpu.regx, 0x80);
+        assert!(cpu.flags.negative);
+    }
+
+    #[test]
+    fn test_lax_zpy() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x10);
+        cpu.poke(0x12, 0x42);
+        cpu.regy = 2;
+        cpu.exec_instruction(Ops::LaxZpY).unwrap();
+        assert_eq!(cpu.rega, 0x42);
+        assert_eq!(cpu.regx, 0x42);
+    }
+
+    #[test]
+    fn test_sax() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x10);
+        cpu.rega = 0xF0;
+        cpu.regx = 0x3C;
+        cpu.flags.zero = true;
+        cpu.exec_instruction(Ops::SaxZp).unwrap();
+        assert_eq!(cpu.peek(0x10), 0x30);
+        assert!(cpu.flags.zero);
+    }
+
+    #[test]
+    fn test_dcp() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x10);
+        cpu.poke(0x10, 0x43);
+        cpu.rega = 0x42;
+        cpu.exec_instruction(Ops::DcpZp).unwrap();
+        assert_eq!(cpu.peek(0x10), 0x42);
+        assert!(cpu.flags.zero);
+        assert!(cpu.flags.carry);
+    }
+
+    #[test]
+    fn test_isc() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x10);
+        cpu.poke(0x10, 0x09);
+        cpu.rega = 0x20;
+        cpu.flags.carry = true;
+        cpu.exec_instruction(Ops::IscZp).unwrap();
+        assert_eq!(cpu.peek(0x10), 0x0A);
+        assert_eq!(cpu.rega, 0x16);
+    }
+
+    #[test]
+    fn test_slo() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x10);
+        cpu.poke(0x10, 0x81);
+        cpu.rega = 0x01;
+        cpu.exec_instruction(Ops::SloZp).unwrap();
+        assert_eq!(cpu.peek(0x10), 0x02);
+        assert_eq!(cpu.rega, 0x03);
+        assert!(cpu.flags.carry);
+    }
+
+    #[test]
+    fn test_rla() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x10);
+        cpu.poke(0x10, 0x81);
+        cpu.rega = 0x0F;
+        cpu.exec_instruction(Ops::RlaZp).unwrap();
+        assert_eq!(cpu.peek(0x10), 0x02);
+        assert_eq!(cpu.rega, 0x02);
+        assert!(cpu.flags.carry);
+    }
+
+    #[test]
+    fn test_sre() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x10);
+        cpu.poke(0x10, 0x03);
+        cpu.rega = 0xFF;
+        cpu.exec_instruction(Ops::SreZp).unwrap();
+        assert_eq!(cpu.peek(0x10), 0x01);
+        assert_eq!(cpu.rega, 0xFE);
+        assert!(cpu.flags.carry);
+        assert!(cpu.flags.negative);
+    }
+
+    #[test]
+    fn test_rra() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x10);
+        cpu.poke(0x10, 0x04);
+        cpu.rega = 0x10;
+        cpu.exec_instruction(Ops::RraZp).unwrap();
+        assert_eq!(cpu.peek(0x10), 0x02);
+        assert_eq!(cpu.rega, 0x12);
+    }
+
+    #[test]
+    fn test_anc() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x80);
+        cpu.rega = 0xFF;
+        cpu.exec_instruction(Ops::AncI).unwrap();
+        assert_eq!(cpu.rega, 0x80);
+        assert!(cpu.flags.negative);
+        assert!(cpu.flags.carry);
+    }
+
+    #[test]
+    fn test_alr() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x03);
+        cpu.rega = 0xFF;
+        cpu.exec_instruction(Ops::AlrI).unwrap();
+        assert_eq!(cpu.rega, 0x01);
+        assert!(cpu.flags.carry);
+    }
+
+    #[test]
+    fn test_arr() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0xFF);
+        cpu.rega = 0x80;
+        cpu.flags.carry = true;
+        cpu.exec_instruction(Ops::ArrI).unwrap();
+        assert_eq!(cpu.rega, 0xC0);
+        assert!(cpu.flags.carry);
+        assert!(cpu.flags.overflow);
+        assert!(cpu.flags.negative);
+    }
+
+    #[test]
+    fn test_arr_no_carry() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0xFF);
+        cpu.rega = 0x60;
+        cpu.exec_instruction(Ops::ArrI).unwrap();
+        assert_eq!(cpu.rega, 0x30);
+        assert!(!cpu.flags.carry);
+        assert!(cpu.flags.overflow);
+    }
+
+    #[test]
+    fn test_axs() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x02);
+        cpu.rega = 0x0F;
+        cpu.regx = 0x07;
+        cpu.exec_instruction(Ops::AxsI).unwrap();
+        assert_eq!(cpu.regx, 0x05);
+        assert!(cpu.flags.carry);
+    }
+
+    #[test]
+    fn test_nop_reads_operand() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0, Ops::NopAbsX1C as u8);
+        cpu.poke(1, 0xFF);
+        cpu.poke(2, 0x10);
+        cpu.regx = 1;
+        assert_eq!(cpu.step().unwrap(), 5);
+        assert_eq!(cpu.pc, 3);
+    }
+
+    #[test]
+    fn test_kil_halts() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0, Ops::Kil02 as u8);
+        cpu.step().unwrap();
+        assert!(cpu.jammed);
+        assert_eq!(cpu.pc, 0);
+        assert_eq!(cpu.step().unwrap(), 0);
+        assert_eq!(cpu.run_for_cycles(100).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_kil_error() {
+        let mut cpu = Cpu::new();
+        cpu.set_jam_behavior(JamBehavior::Error);
+        cpu.poke(0, Ops::Kil02 as u8);
+        assert_eq!(cpu.step(), Err(CpuError::Jammed { op: 0x02, pc: 0 }));
+        assert!(!cpu.jammed);
+    }
 
     #[test]
     fn test_tya() {
         let mut cpu = Cpu::new();
         cpu.regy = 14;
-        cpu.exec_instruction(Ops::Tya);
+        cpu.exec_instruction(Ops::Tya).unwrap();
         assert_eq!(cpu.rega, 14);
     }
 
@@ -573,7 +1196,7 @@ mod tests {
     fn test_txs() {
         let mut cpu = Cpu::new();
         cpu.regx = 14;
-        cpu.exec_instruction(Ops::Txs);
+        cpu.exec_instruction(Ops::Txs).unwrap();
         assert_eq!(cpu.sp, 14);
     }
 
@@ -581,7 +1204,7 @@ mod tests {
     fn test_txa() {
         let mut cpu = Cpu::new();
         cpu.regx = 14;
-        cpu.exec_instruction(Ops::Txa);
+        cpu.exec_instruction(Ops::Txa).unwrap();
         assert_eq!(cpu.rega, 14);
     }
 
@@ -589,7 +1212,7 @@ mod tests {
     fn test_tsx() {
         let mut cpu = Cpu::new();
         cpu.sp = 14;
-        cpu.exec_instruction(Ops::Tsx);
+        cpu.exec_instruction(Ops::Tsx).unwrap();
         assert_eq!(cpu.regx, 14);
     }
 
@@ -597,7 +1220,7 @@ mod tests {
     fn test_tay() {
         let mut cpu = Cpu::new();
         cpu.rega = 14;
-        cpu.exec_instruction(Ops::Tay);
+        cpu.exec_instruction(Ops::Tay).unwrap();
         assert_eq!(cpu.regy, 14);
     }
 
@@ -605,60 +1228,60 @@ mod tests {
     fn test_tax() {
         let mut cpu = Cpu::new();
         cpu.rega = 14;
-        cpu.exec_instruction(Ops::Tax);
+        cpu.exec_instruction(Ops::Tax).unwrap();
         assert_eq!(cpu.regx, 14);
     }
 
     #[test]
     fn test_stx() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 0x56;
-        cpu.memory[2] = 0x63;
+        cpu.poke(1, 0x56);
+        cpu.poke(2, 0x63);
         cpu.regx = 45;
-        cpu.exec_instruction(Ops::StxAbs);
-        assert_eq!(cpu.memory[0x5663], 45);
+        cpu.exec_instruction(Ops::StxAbs).unwrap();
+        assert_eq!(cpu.peek(0x6356), 45);
     }
 
     #[test]
     fn test_sty() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 0x56;
-        cpu.memory[2] = 0x63;
+        cpu.poke(1, 0x56);
+        cpu.poke(2, 0x63);
         cpu.regy = 45;
-        cpu.exec_instruction(Ops::StyAbs);
-        assert_eq!(cpu.memory[0x5663], 45);
+        cpu.exec_instruction(Ops::StyAbs).unwrap();
+        assert_eq!(cpu.peek(0x6356), 45);
     }
 
     #[test]
     fn test_sta() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 0x56;
-        cpu.memory[2] = 0x63;
+        cpu.poke(1, 0x56);
+        cpu.poke(2, 0x63);
         cpu.rega = 45;
-        cpu.exec_instruction(Ops::StaAbs);
-        assert_eq!(cpu.memory[0x5663], 45);
+        cpu.exec_instruction(Ops::StaAbs).unwrap();
+        assert_eq!(cpu.peek(0x6356), 45);
     }
 
     #[test]
     fn test_sbczp_carry() {
         let mut cpu = Cpu::new();
-        let loc: usize = 0x64;
-        cpu.memory[1] = loc as u8;
-        cpu.memory[loc] = 10;
+        let loc: u16 = 0x64;
+        cpu.poke(1, loc as u8);
+        cpu.poke(loc, 10);
         cpu.rega = 30;
         cpu.flags.carry = true;
-        cpu.exec_instruction(Ops::SbcZp);
+        cpu.exec_instruction(Ops::SbcZp).unwrap();
         assert_eq!(cpu.rega, 20);
     }
 
     #[test]
     fn test_sbczp_no_carry() {
         let mut cpu = Cpu::new();
-        let loc: usize = 0x64;
-        cpu.memory[1] = loc as u8;
-        cpu.memory[loc] = 10;
+        let loc: u16 = 0x64;
+        cpu.poke(1, loc as u8);
+        cpu.poke(loc, 10);
         cpu.rega = 30;
-        cpu.exec_instruction(Ops::SbcZp);
+        cpu.exec_instruction(Ops::SbcZp).unwrap();
         assert_eq!(cpu.rega, 19);
     }
 
@@ -667,11 +1290,11 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.carry = true;
         cpu.pc = 0x4567;
-        cpu.save_pc(false);
-        cpu.save_status();
+        cpu.save_pc();
+        cpu.save_status(false);
         cpu.flags.carry = false;
         cpu.pc = 0x4598;
-        cpu.exec_instruction(Ops::Rti);
+        cpu.exec_instruction(Ops::Rti).unwrap();
         assert_eq!(cpu.pc, 0x4567);
         assert!(cpu.flags.carry);
     }
@@ -681,7 +1304,7 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.rega = 0x1;
         cpu.flags.carry = true;
-        cpu.exec_instruction(Ops::RorAccum);
+        cpu.exec_instruction(Ops::RorAccum).unwrap();
         assert_eq!(cpu.rega, 0x80);
         assert!(!cpu.flags.zero);
         assert!(cpu.flags.negative);
@@ -693,18 +1316,268 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.rega = 0x80;
         cpu.flags.carry = true;
-        cpu.exec_instruction(Ops::RolAccum);
+        cpu.exec_instruction(Ops::RolAccum).unwrap();
         assert_eq!(cpu.rega, 1);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
         assert!(cpu.flags.carry);
     }
 
+    #[test]
+    fn test_rol_memory_carry_in() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x10);
+        cpu.poke(0x10, 0x40);
+        cpu.flags.carry = true;
+        cpu.exec_instruction(Ops::RolZp).unwrap();
+        assert_eq!(cpu.peek(0x10), 0x81);
+        assert!(!cpu.flags.carry);
+        assert!(cpu.flags.negative);
+    }
+
+    #[test]
+    fn test_ror_memory_carry_in() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x10);
+        cpu.poke(0x10, 0x03);
+        cpu.flags.carry = true;
+        cpu.exec_instruction(Ops::RorZp).unwrap();
+        assert_eq!(cpu.peek(0x10), 0x81);
+        assert!(cpu.flags.carry);
+    }
+
+    #[test]
+    fn test_inc_dec_wrap() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x10);
+        cpu.poke(0x10, 0xFF);
+        cpu.exec_instruction(Ops::IncZp).unwrap();
+        assert_eq!(cpu.peek(0x10), 0x00);
+        assert!(cpu.flags.zero);
+        cpu.exec_instruction(Ops::DecX).unwrap();
+        assert_eq!(cpu.regx, 0xFF);
+        assert!(cpu.flags.negative);
+        cpu.exec_instruction(Ops::IncX).unwrap();
+        assert_eq!(cpu.regx, 0x00);
+    }
+
+    #[test]
+    fn test_adc_carry_out() {
+        let mut cpu = Cpu::new();
+        cpu.rega = 0xFF;
+        cpu.poke(1, 0x00);
+        cpu.flags.carry = true;
+        cpu.exec_instruction(Ops::AdcI).unwrap();
+        assert_eq!(cpu.rega, 0x00);
+        assert!(cpu.flags.carry);
+        assert!(cpu.flags.zero);
+    }
+
+    #[test]
+    fn test_bit_flags() {
+        let mut cpu = Cpu::new();
+        cpu.rega = 0x0F;
+        cpu.poke(1, 0x10);
+        cpu.poke(0x10, 0x40);
+        cpu.exec_instruction(Ops::BitZp).unwrap();
+        assert!(cpu.flags.zero);
+        assert!(cpu.flags.overflow);
+        assert!(!cpu.flags.negative);
+    }
+
+    #[test]
+    fn test_2a03_ignores_decimal() {
+        let mut cpu = Cpu::new();
+        cpu.flags.decimal = true;
+        cpu.rega = 0x09;
+        cpu.poke(1, 0x01);
+        cpu.exec_instruction(Ops::AdcI).unwrap();
+        assert_eq!(cpu.rega, 0x0A);
+    }
+
+    #[test]
+    fn test_nmos_decimal_adc_sbc() {
+        let mut cpu = Cpu::with_variant(CpuVariant::Nmos6502);
+        cpu.poke(0, Ops::Sed as u8);
+        cpu.poke(1, Ops::AdcI as u8);
+        cpu.poke(2, 0x01);
+        cpu.poke(3, Ops::SbcI as u8);
+        cpu.poke(4, 0x05);
+        cpu.rega = 0x09;
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.rega, 0x10);
+        cpu.flags.carry = true;
+        cpu.step().unwrap();
+        assert_eq!(cpu.rega, 0x05);
+    }
+
+    #[test]
+    fn test_cmos_decimal_extra_cycle() {
+        let mut cpu = Cpu::with_variant(CpuVariant::Cmos65C02);
+        cpu.flags.decimal = true;
+        cpu.poke(0, Ops::AdcI as u8);
+        assert_eq!(cpu.step().unwrap(), 3);
+        let mut cpu = Cpu::with_variant(CpuVariant::Nmos6502);
+        cpu.flags.decimal = true;
+        cpu.poke(0, Ops::AdcI as u8);
+        assert_eq!(cpu.step().unwrap(), 2);
+    }
+
+    fn cmos_cpu(program: &[u8]) -> Cpu {
+        let mut cpu = Cpu::with_variant(CpuVariant::Cmos65C02);
+        for (addr, byte) in program.iter().enumerate() {
+            cpu.poke(addr as u16, *byte);
+        }
+        return cpu;
+    }
+
+    #[test]
+    fn test_cmos_bra() {
+        let mut cpu = cmos_cpu(&[CmosOps::Bra as u8, 0x10]);
+        assert_eq!(cpu.step().unwrap(), 3);
+        assert_eq!(cpu.pc, 0x12);
+    }
+
+    #[test]
+    fn test_cmos_phx_ply() {
+        let mut cpu = cmos_cpu(&[CmosOps::Phx as u8, CmosOps::Ply as u8]);
+        cpu.regx = 0x80;
+        cpu.step().unwrap();
+        assert_eq!(cpu.peek_stack(), 0x80);
+        cpu.step().unwrap();
+        assert_eq!(cpu.regy, 0x80);
+        assert!(cpu.flags.negative);
+        assert_eq!(cpu.sp, 0xFF);
+    }
+
+    #[test]
+    fn test_cmos_phy_plx() {
+        let mut cpu = cmos_cpu(&[CmosOps::Phy as u8, CmosOps::Plx as u8]);
+        cpu.regy = 0x00;
+        cpu.regx = 0x55;
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.regx, 0x00);
+        assert!(cpu.flags.zero);
+    }
+
+    #[test]
+    fn test_cmos_stz() {
+        let mut cpu = cmos_cpu(&[CmosOps::StzAbsX as u8, 0x00, 0x03]);
+        cpu.regx = 2;
+        cpu.poke(0x0302, 0xAA);
+        assert_eq!(cpu.step().unwrap(), 5);
+        assert_eq!(cpu.peek(0x0302), 0x00);
+    }
+
+    #[test]
+    fn test_cmos_tsb_trb() {
+        let mut cpu = cmos_cpu(&[CmosOps::TsbZp as u8, 0x10, CmosOps::TrbZp as u8, 0x10]);
+        cpu.poke(0x10, 0xF0);
+        cpu.rega = 0x0F;
+        cpu.step().unwrap();
+        assert_eq!(cpu.peek(0x10), 0xFF);
+        assert!(cpu.flags.zero);
+        cpu.step().unwrap();
+        assert_eq!(cpu.peek(0x10), 0xF0);
+        assert!(!cpu.flags.zero);
+    }
+
+    #[test]
+    fn test_cmos_bit_immediate() {
+        let mut cpu = cmos_cpu(&[CmosOps::BitI as u8, 0xC0]);
+        cpu.rega = 0x01;
+        cpu.step().unwrap();
+        assert!(cpu.flags.zero);
+        assert!(!cpu.flags.negative);
+        assert!(!cpu.flags.overflow);
+    }
+
+    #[test]
+    fn test_cmos_inc_dec_accum() {
+        let mut cpu = cmos_cpu(&[CmosOps::IncAccum as u8, CmosOps::DecAccum as u8, CmosOps::DecAccum as u8]);
+        cpu.rega = 0xFF;
+        cpu.step().unwrap();
+        assert_eq!(cpu.rega, 0x00);
+        assert!(cpu.flags.zero);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.rega, 0xFE);
+    }
+
+    #[test]
+    fn test_cmos_zp_indirect() {
+        let mut cpu = cmos_cpu(&[CmosOps::LdaZpInd as u8, 0x10, CmosOps::StaZpInd as u8, 0x20]);
+        cpu.poke(0x10, 0x34);
+        cpu.poke(0x11, 0x12);
+        cpu.poke(0x1234, 0x42);
+        cpu.poke(0x20, 0x00);
+        cpu.poke(0x21, 0x04);
+        cpu.regy = 5;
+        assert_eq!(cpu.step().unwrap(), 5);
+        assert_eq!(cpu.rega, 0x42);
+        cpu.step().unwrap();
+        assert_eq!(cpu.peek(0x0400), 0x42);
+    }
+
+    #[test]
+    fn test_cmos_zp_indirect_wrap() {
+        let mut cpu = cmos_cpu(&[CmosOps::LdaZpInd as u8, 0xFF]);
+        // The pointer's high byte comes from $00, which holds the opcode
+        cpu.poke(0xFF, 0x34);
+        cpu.poke(0xB234, 0x42);
+        cpu.step().unwrap();
+        assert_eq!(cpu.rega, 0x42);
+    }
+
+    #[test]
+    fn test_cmos_jmp_abs_indirect_x() {
+        let mut cpu = cmos_cpu(&[CmosOps::JmpAbsIndX as u8, 0xFF, 0x10]);
+        cpu.regx = 1;
+        cpu.poke(0x1100, 0x34);
+        cpu.poke(0x1101, 0x12);
+        assert_eq!(cpu.step().unwrap(), 6);
+        assert_eq!(cpu.pc, 0x1234);
+    }
+
+    #[test]
+    fn test_cmos_jmp_indirect_fixed() {
+        let mut cpu = cmos_cpu(&[Ops::JmpInd as u8, 0xFF, 0x10]);
+        cpu.poke(0x10FF, 0x34);
+        cpu.poke(0x1100, 0x12);
+        cpu.poke(0x1000, 0x56);
+        assert_eq!(cpu.step().unwrap(), 6);
+        assert_eq!(cpu.pc, 0x1234);
+    }
+
+    #[test]
+    fn test_cmos_undefined_nop() {
+        let mut cpu = cmos_cpu(&[0x03, 0x02, 0xFF]);
+        assert_eq!(cpu.step().unwrap(), 1);
+        assert_eq!(cpu.pc, 1);
+        assert_eq!(cpu.step().unwrap(), 2);
+        assert_eq!(cpu.pc, 3);
+        assert!(!cpu.jammed);
+    }
+
+    #[test]
+    fn test_cmos_brk_clears_decimal() {
+        let mut cpu = cmos_cpu(&[Ops::Brk as u8]);
+        cpu.flags.decimal = true;
+        cpu.step().unwrap();
+        assert!(!cpu.flags.decimal);
+        let mut cpu = Cpu::with_variant(CpuVariant::Nmos6502);
+        cpu.flags.decimal = true;
+        cpu.step().unwrap();
+        assert!(cpu.flags.decimal);
+    }
+
     #[test]
     fn test_pla() {
         let mut cpu = Cpu::new();
         cpu.push_stack(0x42);
-        cpu.exec_instruction(Ops::Pla);
+        cpu.exec_instruction(Ops::Pla).unwrap();
         assert_eq!(cpu.rega, 0x42);
     }
 
@@ -712,7 +1585,7 @@ mod tests {
     fn test_plp() {
         let mut cpu = Cpu::new();
         cpu.push_stack(0x03);
-        cpu.exec_instruction(Ops::Plp);
+        cpu.exec_instruction(Ops::Plp).unwrap();
         assert!(cpu.flags.carry);
         assert!(cpu.flags.zero);
     }
@@ -721,15 +1594,15 @@ mod tests {
     fn test_php() {
         let mut cpu = Cpu::new();
         cpu.flags.carry = true;
-        cpu.exec_instruction(Ops::Php);
-        assert_eq!(cpu.peek_stack(), 1);
+        cpu.exec_instruction(Ops::Php).unwrap();
+        assert_eq!(cpu.peek_stack(), 0x31);
     }
 
     #[test]
     fn test_pha() {
         let mut cpu = Cpu::new();
         cpu.rega = 10;
-        cpu.exec_instruction(Ops::Pha);
+        cpu.exec_instruction(Ops::Pha).unwrap();
         assert_eq!(cpu.peek_stack(), 10);
     }
 
@@ -737,8 +1610,8 @@ mod tests {
     fn test_or_zero() {
         let mut cpu = Cpu::new();
         cpu.rega = 0x0;
-        cpu.memory[1] = 0x0;
-        cpu.exec_instruction(Ops::ORI);
+        cpu.poke(1, 0x0);
+        cpu.exec_instruction(Ops::ORI).unwrap();
         assert_eq!(cpu.rega, 0);
         assert!(!cpu.flags.negative);
         assert!(cpu.flags.zero);
@@ -748,8 +1621,8 @@ mod tests {
     fn test_or() {
         let mut cpu = Cpu::new();
         cpu.rega = 0xFF;
-        cpu.memory[1] = 0xFF;
-        cpu.exec_instruction(Ops::ORI);
+        cpu.poke(1, 0xFF);
+        cpu.exec_instruction(Ops::ORI).unwrap();
         assert_eq!(cpu.rega, 0xFF);
         assert!(cpu.flags.negative);
         assert!(!cpu.flags.zero);
@@ -759,7 +1632,7 @@ mod tests {
     fn test_nop() {
         let mut cpu = Cpu::new();
         let old_pc = cpu.pc;
-        cpu.exec_instruction(Ops::Nop);
+        cpu.exec_instruction(Ops::Nop).unwrap();
         assert_eq!(old_pc, cpu.pc);
     }
 
@@ -767,7 +1640,7 @@ mod tests {
     fn test_lsr_accum_negative() {
         let mut cpu = Cpu::new();
         cpu.rega = 0x71;
-        cpu.exec_instruction(Ops::LsrAccum);
+        cpu.exec_instruction(Ops::LsrAccum).unwrap();
         assert_eq!(cpu.rega, 0x71 >> 1);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -778,7 +1651,7 @@ mod tests {
     fn test_lsr_accum_zero_carry() {
         let mut cpu = Cpu::new();
         cpu.rega = 0x1;
-        cpu.exec_instruction(Ops::LsrAccum);
+        cpu.exec_instruction(Ops::LsrAccum).unwrap();
         assert_eq!(cpu.rega, 0);
         assert!(cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -789,7 +1662,7 @@ mod tests {
     fn test_lsr_accum() {
         let mut cpu = Cpu::new();
         cpu.rega = 2;
-        cpu.exec_instruction(Ops::LsrAccum);
+        cpu.exec_instruction(Ops::LsrAccum).unwrap();
         assert_eq!(cpu.rega, 1);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -799,8 +1672,8 @@ mod tests {
     #[test]
     fn test_ldy() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 45;
-        cpu.exec_instruction(Ops::LdyI);
+        cpu.poke(1, 45);
+        cpu.exec_instruction(Ops::LdyI).unwrap();
         assert_eq!(cpu.regy, 45);
         assert!(!cpu.flags.zero)
     }
@@ -808,8 +1681,8 @@ mod tests {
     #[test]
     fn test_ldx() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 45;
-        cpu.exec_instruction(Ops::LdxI);
+        cpu.poke(1, 45);
+        cpu.exec_instruction(Ops::LdxI).unwrap();
         assert_eq!(cpu.regx, 45);
         assert!(!cpu.flags.zero)
     }
@@ -817,8 +1690,8 @@ mod tests {
     #[test]
     fn test_lda() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 45;
-        cpu.exec_instruction(Ops::LdaI);
+        cpu.poke(1, 45);
+        cpu.exec_instruction(Ops::LdaI).unwrap();
         assert_eq!(cpu.rega, 45);
         assert!(!cpu.flags.zero)
     }
@@ -826,31 +1699,54 @@ mod tests {
     #[test]
     fn test_jsr() {
         let mut cpu = Cpu::new();
-        cpu.memory[0x2344] = 0xFF;
-        cpu.memory[0x2345] = 0xFF;
+        cpu.poke(0x2344, 0xFF);
+        cpu.poke(0x2345, 0xFF);
         cpu.pc = 0x2343;
-        cpu.exec_instruction(Ops::Jsr);
-        assert_eq!(cpu.peek_stack(), 0x23);
-        assert_eq!(cpu.memory[(cpu.sp + 2) as usize], 0x42);
+        cpu.exec_instruction(Ops::Jsr).unwrap();
+        assert_eq!(cpu.peek_stack(), 0x45);
+        assert_eq!(cpu.peek(STACK_BASE + cpu.sp as u16 + 2), 0x23);
         assert_eq!(cpu.pc, 0xFFFF);
     }
 
     #[test]
     fn test_jump_ind() {
         let mut cpu = Cpu::new();
-        cpu.memory[0x0120] = 0xFC;
-        cpu.memory[0x0121] = 0xBA;
-        cpu.memory[1] = 0x01;
-        cpu.memory[2] = 0x20;
-        cpu.exec_instruction(Ops::JmpInd);
+        cpu.poke(0x0120, 0xFC);
+        cpu.poke(0x0121, 0xBA);
+        cpu.poke(1, 0x20);
+        cpu.poke(2, 0x01);
+        cpu.exec_instruction(Ops::JmpInd).unwrap();
         assert_eq!(cpu.pc, 0xBAFC);
     }
 
+    #[test]
+    fn test_jump_ind_page_bug() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0x02FF, 0x00);
+        cpu.poke(0x0200, 0x80);
+        cpu.poke(0x0300, 0x40);
+        cpu.poke(1, 0xFF);
+        cpu.poke(2, 0x02);
+        cpu.exec_instruction(Ops::JmpInd).unwrap();
+        assert_eq!(cpu.pc, 0x8000);
+    }
+
+    #[test]
+    fn test_step_cycles_indirecty_page_cross() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0, Ops::LdaIndY as u8);
+        cpu.poke(1, 0x10);
+        cpu.poke(0x10, 0xFF);
+        cpu.poke(0x11, 0x20);
+        cpu.regy = 1;
+        assert_eq!(cpu.step().unwrap(), 6);
+    }
+
     #[test]
     fn test_incy() {
         let mut cpu = Cpu::new();
         cpu.regy = 12;
-        cpu.exec_instruction(Ops::IncY);
+        cpu.exec_instruction(Ops::IncY).unwrap();
         assert_eq!(cpu.regy, 13);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -860,7 +1756,7 @@ mod tests {
     fn test_incx() {
         let mut cpu = Cpu::new();
         cpu.regx = 12;
-        cpu.exec_instruction(Ops::IncX);
+        cpu.exec_instruction(Ops::IncX).unwrap();
         assert_eq!(cpu.regx, 13);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -869,10 +1765,10 @@ mod tests {
     #[test]
     fn test_inc() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 10;
-        cpu.memory[10] = 12;
-        cpu.exec_instruction(Ops::IncZp);
-        assert_eq!(cpu.memory[10], 13);
+        cpu.poke(1, 10);
+        cpu.poke(10, 12);
+        cpu.exec_instruction(Ops::IncZp).unwrap();
+        assert_eq!(cpu.peek(10), 13);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
     }
@@ -881,8 +1777,8 @@ mod tests {
     fn test_eor_zero() {
         let mut cpu = Cpu::new();
         cpu.rega = 0xFF;
-        cpu.memory[1] = 0xFF;
-        cpu.exec_instruction(Ops::EORI);
+        cpu.poke(1, 0xFF);
+        cpu.exec_instruction(Ops::EORI).unwrap();
         assert_eq!(cpu.rega, 0);
         assert!(!cpu.flags.negative);
         assert!(cpu.flags.zero);
@@ -892,8 +1788,8 @@ mod tests {
     fn test_eor() {
         let mut cpu = Cpu::new();
         cpu.rega = 0x0F;
-        cpu.memory[1] = 0xF0;
-        cpu.exec_instruction(Ops::EORI);
+        cpu.poke(1, 0xF0);
+        cpu.exec_instruction(Ops::EORI).unwrap();
         assert_eq!(cpu.rega, 0xFF);
         assert!(cpu.flags.negative);
         assert!(!cpu.flags.zero);
@@ -903,7 +1799,7 @@ mod tests {
     fn test_decy() {
         let mut cpu = Cpu::new();
         cpu.regy = 12;
-        cpu.exec_instruction(Ops::DecY);
+        cpu.exec_instruction(Ops::DecY).unwrap();
         assert_eq!(cpu.regy, 11);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -913,7 +1809,7 @@ mod tests {
     fn test_decx_zero() {
         let mut cpu = Cpu::new();
         cpu.regx = 1;
-        cpu.exec_instruction(Ops::DecX);
+        cpu.exec_instruction(Ops::DecX).unwrap();
         assert_eq!(cpu.regx, 0);
         assert!(cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -923,7 +1819,7 @@ mod tests {
     fn test_decx() {
         let mut cpu = Cpu::new();
         cpu.regx = 12;
-        cpu.exec_instruction(Ops::DecX);
+        cpu.exec_instruction(Ops::DecX).unwrap();
         assert_eq!(cpu.regx, 11);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -932,10 +1828,10 @@ mod tests {
     #[test]
     fn test_dec_zero() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 10;
-        cpu.memory[10] = 1;
-        cpu.exec_instruction(Ops::DecZp);
-        assert_eq!(cpu.memory[10], 0);
+        cpu.poke(1, 10);
+        cpu.poke(10, 1);
+        cpu.exec_instruction(Ops::DecZp).unwrap();
+        assert_eq!(cpu.peek(10), 0);
         assert!(cpu.flags.zero);
         assert!(!cpu.flags.negative);
     }
@@ -943,10 +1839,10 @@ mod tests {
     #[test]
     fn test_dec() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 10;
-        cpu.memory[10] = 12;
-        cpu.exec_instruction(Ops::DecZp);
-        assert_eq!(cpu.memory[10], 11);
+        cpu.poke(1, 10);
+        cpu.poke(10, 12);
+        cpu.exec_instruction(Ops::DecZp).unwrap();
+        assert_eq!(cpu.peek(10), 11);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
     }
@@ -955,8 +1851,8 @@ mod tests {
     fn test_cmpy_carry() {
         let mut cpu = Cpu::new();
         cpu.regy = 12;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::CmpYI);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::CmpYI).unwrap();
         assert!(cpu.flags.carry);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -966,8 +1862,8 @@ mod tests {
     fn test_cmpy_neg() {
         let mut cpu = Cpu::new();
         cpu.regy = 10;
-        cpu.memory[1] = 12;
-        cpu.exec_instruction(Ops::CmpYI);
+        cpu.poke(1, 12);
+        cpu.exec_instruction(Ops::CmpYI).unwrap();
         assert!(!cpu.flags.carry);
         assert!(!cpu.flags.zero);
         assert!(cpu.flags.negative);
@@ -977,8 +1873,8 @@ mod tests {
     fn test_cmpy_eq() {
         let mut cpu = Cpu::new();
         cpu.regy = 10;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::CmpYI);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::CmpYI).unwrap();
         assert!(cpu.flags.carry);
         assert!(cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -988,8 +1884,8 @@ mod tests {
     fn test_cmpx_carry() {
         let mut cpu = Cpu::new();
         cpu.regx = 12;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::CmpXI);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::CmpXI).unwrap();
         assert!(cpu.flags.carry);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -999,8 +1895,8 @@ mod tests {
     fn test_cmpx_neg() {
         let mut cpu = Cpu::new();
         cpu.regx = 10;
-        cpu.memory[1] = 12;
-        cpu.exec_instruction(Ops::CmpXI);
+        cpu.poke(1, 12);
+        cpu.exec_instruction(Ops::CmpXI).unwrap();
         assert!(!cpu.flags.carry);
         assert!(!cpu.flags.zero);
         assert!(cpu.flags.negative);
@@ -1010,8 +1906,8 @@ mod tests {
     fn test_cmpx_eq() {
         let mut cpu = Cpu::new();
         cpu.regx = 10;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::CmpXI);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::CmpXI).unwrap();
         assert!(cpu.flags.carry);
         assert!(cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -1021,8 +1917,8 @@ mod tests {
     fn test_cmp_carry() {
         let mut cpu = Cpu::new();
         cpu.rega = 12;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::CmpI);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::CmpI).unwrap();
         assert!(cpu.flags.carry);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -1032,8 +1928,8 @@ mod tests {
     fn test_cmp_neg() {
         let mut cpu = Cpu::new();
         cpu.rega = 10;
-        cpu.memory[1] = 12;
-        cpu.exec_instruction(Ops::CmpI);
+        cpu.poke(1, 12);
+        cpu.exec_instruction(Ops::CmpI).unwrap();
         assert!(!cpu.flags.carry);
         assert!(!cpu.flags.zero);
         assert!(cpu.flags.negative);
@@ -1043,8 +1939,8 @@ mod tests {
     fn test_cmp_eq() {
         let mut cpu = Cpu::new();
         cpu.rega = 10;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::CmpI);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::CmpI).unwrap();
         assert!(cpu.flags.carry);
         assert!(cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -1054,7 +1950,7 @@ mod tests {
     fn test_clear_overflow() {
         let mut cpu = Cpu::new();
         cpu.flags.overflow = true;
-        cpu.exec_instruction(Ops::Clv);
+        cpu.exec_instruction(Ops::Clv).unwrap();
         assert!(!cpu.flags.overflow);
     }
 
@@ -1062,7 +1958,7 @@ mod tests {
     fn test_clear_interrupt() {
         let mut cpu = Cpu::new();
         cpu.flags.inter_disable = true;
-        cpu.exec_instruction(Ops::Cli);
+        cpu.exec_instruction(Ops::Cli).unwrap();
         assert!(!cpu.flags.inter_disable);
     }
 
@@ -1070,7 +1966,7 @@ mod tests {
     fn test_clear_decimal() {
         let mut cpu = Cpu::new();
         cpu.flags.decimal = true;
-        cpu.exec_instruction(Ops::Cld);
+        cpu.exec_instruction(Ops::Cld).unwrap();
         assert!(!cpu.flags.decimal);
     }
 
@@ -1078,7 +1974,7 @@ mod tests {
     fn test_clear_carry() {
         let mut cpu = Cpu::new();
         cpu.flags.carry = true;
-        cpu.exec_instruction(Ops::Clc);
+        cpu.exec_instruction(Ops::Clc).unwrap();
         assert!(!cpu.flags.carry);
     }
 
@@ -1087,8 +1983,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.overflow = false;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bvs);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bvs).unwrap();
         assert_eq!(cpu.pc, old_pc + 1);
     }
 
@@ -1097,8 +1993,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.overflow = true;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bvs);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bvs).unwrap();
         assert_eq!(cpu.pc, old_pc + 11);
     }
 
@@ -1107,8 +2003,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.overflow = true;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bvc);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bvc).unwrap();
         assert_eq!(cpu.pc, old_pc + 1);
     }
 
@@ -1117,24 +2013,24 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.overflow = false;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bvc);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bvc).unwrap();
         assert_eq!(cpu.pc, old_pc + 11);
     }
 
     #[test]
     fn test_brk() {
         let mut cpu = Cpu::new();
-        cpu.memory[0xFFFE] = 0x98;
-        cpu.memory[0xFFFF] = 0x45;
+        cpu.poke(0xFFFE, 0x98);
+        cpu.poke(0xFFFF, 0x45);
         cpu.pc = 0x3456;
         cpu.flags.carry = true;
-        cpu.exec_instruction(Ops::Brk);
-        assert_eq!(cpu.peek_stack(), 1);
-        assert_eq!(cpu.memory[(cpu.sp + 2) as usize], 0x34);
-        assert_eq!(cpu.memory[(cpu.sp + 3) as usize], 0x56);
+        cpu.exec_instruction(Ops::Brk).unwrap();
+        assert_eq!(cpu.peek_stack(), 0x31);
+        assert_eq!(cpu.peek(STACK_BASE + cpu.sp as u16 + 2), 0x58);
+        assert_eq!(cpu.peek(STACK_BASE + cpu.sp as u16 + 3), 0x34);
         assert_eq!(cpu.pc, 0x4598);
-        assert!(cpu.flags.break1 && cpu.flags.break2);
+        assert!(cpu.flags.inter_disable);
     }
 
     #[test]
@@ -1142,8 +2038,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.negative = true;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bpl);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bpl).unwrap();
         assert_eq!(cpu.pc, old_pc + 1);
     }
 
@@ -1152,8 +2048,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.negative = false;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bpl);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bpl).unwrap();
         assert_eq!(cpu.pc, old_pc + 11);
     }
 
@@ -1162,8 +2058,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.zero = true;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bne);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bne).unwrap();
         assert_eq!(cpu.pc, old_pc + 1);
     }
 
@@ -1172,8 +2068,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.zero = false;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bne);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bne).unwrap();
         assert_eq!(cpu.pc, old_pc + 11);
     }
 
@@ -1182,8 +2078,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.negative = false;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bmi);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bmi).unwrap();
         assert_eq!(cpu.pc, old_pc + 1);
     }
 
@@ -1192,18 +2088,18 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.negative = true;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bmi);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bmi).unwrap();
         assert_eq!(cpu.pc, old_pc + 11);
     }
 
     #[test]
     fn test_bit() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 10;
-        cpu.memory[10] = 0x01;
+        cpu.poke(1, 10);
+        cpu.poke(10, 0x01);
         cpu.rega = 0x0F;
-        cpu.exec_instruction(Ops::BitZp);
+        cpu.exec_instruction(Ops::BitZp).unwrap();
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.overflow);
         assert!(!cpu.flags.negative);
@@ -1212,10 +2108,10 @@ mod tests {
     #[test]
     fn test_bit_zero() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 10;
-        cpu.memory[10] = 0xF0;
+        cpu.poke(1, 10);
+        cpu.poke(10, 0xF0);
         cpu.rega = 0x0F;
-        cpu.exec_instruction(Ops::BitZp);
+        cpu.exec_instruction(Ops::BitZp).unwrap();
         assert!(cpu.flags.zero);
         assert!(cpu.flags.overflow);
         assert!(cpu.flags.negative);
@@ -1226,8 +2122,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.zero = false;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Beq);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Beq).unwrap();
         assert_eq!(cpu.pc, old_pc + 1);
     }
 
@@ -1236,8 +2132,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.zero = true;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Beq);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Beq).unwrap();
         assert_eq!(cpu.pc, old_pc + 11);
     }
 
@@ -1246,8 +2142,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.carry = false;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bcs);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bcs).unwrap();
         assert_eq!(cpu.pc, old_pc + 1);
     }
 
@@ -1256,8 +2152,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.carry = true;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bcs);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bcs).unwrap();
         assert_eq!(cpu.pc, old_pc + 11);
     }
 
@@ -1266,8 +2162,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.carry = true;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bcc);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bcc).unwrap();
         assert_eq!(cpu.pc, old_pc + 1);
     }
 
@@ -1276,8 +2172,8 @@ mod tests {
         let mut cpu = Cpu::new();
         cpu.flags.carry = false;
         let old_pc = cpu.pc;
-        cpu.memory[1] = 10;
-        cpu.exec_instruction(Ops::Bcc);
+        cpu.poke(1, 10);
+        cpu.exec_instruction(Ops::Bcc).unwrap();
         assert_eq!(cpu.pc, old_pc + 11);
     }
 
@@ -1285,7 +2181,7 @@ mod tests {
     fn test_asl_accum_negative() {
         let mut cpu = Cpu::new();
         cpu.rega = 0x71;
-        cpu.exec_instruction(Ops::AslAccum);
+        cpu.exec_instruction(Ops::AslAccum).unwrap();
         assert_eq!(cpu.rega, 0x71 << 1);
         assert!(!cpu.flags.zero);
         assert!(cpu.flags.negative);
@@ -1296,7 +2192,7 @@ mod tests {
     fn test_asl_accum_zero_carry() {
         let mut cpu = Cpu::new();
         cpu.rega = 0x80;
-        cpu.exec_instruction(Ops::AslAccum);
+        cpu.exec_instruction(Ops::AslAccum).unwrap();
         assert_eq!(cpu.rega, 0);
         assert!(cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -1307,7 +2203,7 @@ mod tests {
     fn test_asl_accum() {
         let mut cpu = Cpu::new();
         cpu.rega = 2;
-        cpu.exec_instruction(Ops::AslAccum);
+        cpu.exec_instruction(Ops::AslAccum).unwrap();
         assert_eq!(cpu.rega, 4);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -1317,18 +2213,18 @@ mod tests {
     #[test]
     fn test_and_negative() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 0xFF;
+        cpu.poke(1, 0xFF);
         cpu.rega = 0x89;
-        cpu.exec_instruction(Ops::AndI);
-        assert_eq!(cpu.rega, 0xFF & 0x89);
+        cpu.exec_instruction(Ops::AndI).unwrap();
+        assert_eq!(cpu.rega, 0x89);
         assert!(cpu.flags.negative);
     }
 
     #[test]
     fn test_and_zero() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 20;
-        cpu.exec_instruction(Ops::AndI);
+        cpu.poke(1, 20);
+        cpu.exec_instruction(Ops::AndI).unwrap();
         assert_eq!(cpu.rega, 0);
         assert!(cpu.flags.zero);
     }
@@ -1336,9 +2232,9 @@ mod tests {
     #[test]
     fn test_and() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 0x0F;
+        cpu.poke(1, 0x0F);
         cpu.rega = 0x09;
-        cpu.exec_instruction(Ops::AndI);
+        cpu.exec_instruction(Ops::AndI).unwrap();
         assert_eq!(cpu.rega, 0x0F & 0x09);
         assert!(!cpu.flags.zero);
         assert!(!cpu.flags.negative);
@@ -1347,9 +2243,9 @@ mod tests {
     #[test]
     fn test_adci_overflow_true() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 80;
+        cpu.poke(1, 80);
         cpu.rega = 80;
-        cpu.exec_instruction(Ops::AdcI);
+        cpu.exec_instruction(Ops::AdcI).unwrap();
         assert_eq!(cpu.rega, 160);
         assert!(cpu.flags.overflow);
     }
@@ -1357,8 +2253,8 @@ mod tests {
     #[test]
     fn test_adci_negative_true() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 0xFF;
-        cpu.exec_instruction(Ops::AdcI);
+        cpu.poke(1, 0xFF);
+        cpu.exec_instruction(Ops::AdcI).unwrap();
         assert_eq!(cpu.rega, 0xFF);
         assert!(cpu.flags.negative);
     }
@@ -1366,13 +2262,12 @@ mod tests {
     #[test]
     fn test_adcindy() {
         let mut cpu = Cpu::new();
-        cpu.memory[0x14] = 0x24;
-        cpu.memory[0x24] = 0x45;
-        cpu.memory[0x25] = 0x34;
-        cpu.memory[1] = 0x14 - 10;
+        cpu.poke(0x14, 0x45);
+        cpu.poke(0x15, 0x34);
+        cpu.poke(1, 0x14);
         cpu.regy = 10;
-        cpu.memory[0x3445] = 100;
-        cpu.exec_instruction(Ops::AdcIndY);
+        cpu.poke(0x3445 + 10, 100);
+        cpu.exec_instruction(Ops::AdcIndY).unwrap();
         assert_eq!(cpu.rega, 100);
         assert!(!cpu.flags.overflow);
     }
@@ -1380,64 +2275,63 @@ mod tests {
     #[test]
     fn test_adcindx() {
         let mut cpu = Cpu::new();
-        cpu.memory[0x14] = 0x24;
-        cpu.memory[0x24] = 0x45;
-        cpu.memory[0x25] = 0x34;
-        cpu.memory[1] = 0x14 - 10;
+        cpu.poke(0x14, 0x45);
+        cpu.poke(0x15, 0x34);
+        cpu.poke(1, 0x14 - 10);
         cpu.regx = 10;
-        cpu.memory[0x3445] = 100;
-        cpu.exec_instruction(Ops::AdcIndX);
+        cpu.poke(0x3445, 100);
+        cpu.exec_instruction(Ops::AdcIndX).unwrap();
         assert_eq!(cpu.rega, 100);
     }
 
     #[test]
     fn test_adcabsy() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 0x11;
-        cpu.memory[2] = 0x11;
-        cpu.memory[0x1111 + 10] = 23;
+        cpu.poke(1, 0x11);
+        cpu.poke(2, 0x11);
+        cpu.poke(0x1111 + 10, 23);
         cpu.regy = 10;
-        cpu.exec_instruction(Ops::AdcAbsY);
+        cpu.exec_instruction(Ops::AdcAbsY).unwrap();
         assert_eq!(cpu.rega, 23);
     }
 
     #[test]
     fn test_adcabsx() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 0x11;
-        cpu.memory[2] = 0x11;
-        cpu.memory[0x1111 + 10] = 23;
+        cpu.poke(1, 0x11);
+        cpu.poke(2, 0x11);
+        cpu.poke(0x1111 + 10, 23);
         cpu.regx = 10;
-        cpu.exec_instruction(Ops::AdcAbsX);
+        cpu.exec_instruction(Ops::AdcAbsX).unwrap();
         assert_eq!(cpu.rega, 23);
     }
 
     #[test]
     fn test_adcabs() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 0x11;
-        cpu.memory[2] = 0x11;
-        cpu.memory[0x1111] = 23;
-        cpu.exec_instruction(Ops::AdcAbs);
+        cpu.poke(1, 0x11);
+        cpu.poke(2, 0x11);
+        cpu.poke(0x1111, 23);
+        cpu.exec_instruction(Ops::AdcAbs).unwrap();
         assert_eq!(cpu.rega, 23);
     }
 
     #[test]
     fn test_adczpx() {
         let mut cpu = Cpu::new();
-        let loc: usize = 0x64;
-        cpu.memory[1] = loc as u8;
-        cpu.memory[loc+10] = 23;
+        let loc: u16 = 0x64;
+        cpu.poke(1, loc as u8);
+        cpu.poke(loc+10, 23);
         cpu.regx = 10;
-        cpu.exec_instruction(Ops::AdcZpX);
+        cpu.exec_instruction(Ops::AdcZpX).unwrap();
         assert_eq!(cpu.rega, 23);
     }
 
     #[test]
     fn test_adci_no_over() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 23;
-        cpu.exec_instruction(Ops::AdcI);
+        cpu.poke(1, 23);
+        cpu.exec_instruction(Ops::AdcI).unwrap();
         assert_eq!(cpu.rega, 23);
         assert!(!cpu.flags.negative);
     }
@@ -1445,9 +2339,9 @@ mod tests {
     #[test]
     fn test_adci_over() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 5;
+        cpu.poke(1, 5);
         cpu.rega = 255;
-        cpu.exec_instruction(Ops::AdcI);
+        cpu.exec_instruction(Ops::AdcI).unwrap();
         assert_eq!(cpu.rega, 4);
         assert!(cpu.flags.carry);
     }
@@ -1455,7 +2349,7 @@ mod tests {
     #[test]
     fn test_adci_zero() {
         let mut cpu = Cpu::new();
-        cpu.exec_instruction(Ops::AdcI);
+        cpu.exec_instruction(Ops::AdcI).unwrap();
         assert_eq!(cpu.rega, 0);
         assert!(cpu.flags.zero);
     }
@@ -1463,11 +2357,11 @@ mod tests {
     #[test]
     fn test_adczp() {
         let mut cpu = Cpu::new();
-        let loc: usize = 0x64;
-        cpu.memory[1] = loc as u8;
-        cpu.memory[loc] = 23;
+        let loc: u16 = 0x64;
+        cpu.poke(1, loc as u8);
+        cpu.poke(loc, 23);
         cpu.flags.carry = true;
-        cpu.exec_instruction(Ops::AdcZp);
+        cpu.exec_instruction(Ops::AdcZp).unwrap();
         assert_eq!(cpu.rega, 24);
     }
 }
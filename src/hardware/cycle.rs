@@ -0,0 +1,718 @@
+use crate::hardware::cpu::Cpu;
+use crate::hardware::error::{CpuError, CpuResult};
+use crate::hardware::instruction::{Access, AddrModes, Instruction, Mnemonic};
+use crate::hardware::interrupts::{Interrupts, IRQ_VECTOR, NMI_VECTOR};
+use crate::hardware::memory::MemoryOps;
+use crate::hardware::stack::{StackOps, STACK_BASE};
+use crate::hardware::timing::{Timing, crosses_page};
+use crate::hardware::variant::CpuVariant;
+use crate::utils::{combine_bytes, split_bytes};
+
+// Longest sequence after the opcode fetch is a (zp),Y read-modify-write
+const MAX_MICRO_OPS: usize = 8;
+
+// Hardware interrupts force a BRK into the instruction register, the opcode
+// fetch still happens but its result is thrown away
+const INTERRUPT: Instruction = Instruction {
+    op: 0x00,
+    mnemonic: Mnemonic::Brk,
+    mode: AddrModes::Implied,
+    bytes: 1,
+    cycles: 7,
+    page_penalty: false,
+};
+
+// How step() runs the cpu. Both leave the same state between instructions,
+// Cycle spreads each instruction over one tick per bus access so devices on
+// the bus see every access at the cycle it really happens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExecMode {
+    Instruction,
+    Cycle,
+}
+
+// One cpu cycle after the opcode fetch. Each makes exactly one bus access
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MicroOp {
+    // Operand bytes and address calculation, leaving the effective address in the latch
+    FetchLo,
+    FetchHi,
+    FetchHiX,
+    FetchHiY,
+    IndexZpX,
+    IndexZpY,
+    PointerLo,
+    PointerHi,
+    PointerHiY,
+    // Read of the un-carried address while the high byte is fixed up. Indexed reads
+    // skip it when the index didn't carry
+    FixIndex,
+
+    ReadImmediate,
+    ReadOperand,
+    DecimalFixup,
+    Store,
+    ModifyRead,
+    ModifyDummy,
+    ModifyWrite,
+    Implied,
+
+    DummyReadPc,
+    ReadPcInc,
+    DummyReadStack,
+    Push,
+    Pull,
+    PushPch,
+    PushPcl,
+    PushStatus,
+    PullStatus,
+    PullPcl,
+    PullPch,
+    VectorLo,
+    VectorHi,
+
+    JumpHi,
+    DummyReadOperand,
+    IndexPointerX,
+    JumpPointerHi,
+
+    Branch,
+    BranchTake,
+    BranchFix,
+
+    // 65C02 $5C keeps reading $FFxx for five extra cycles
+    Stall,
+    Halt,
+}
+
+pub enum Cycle {
+    Used,
+    // Didn't happen on this instruction, the next micro op runs in its place
+    Skipped,
+    // Used, and the instruction ends early
+    Done,
+}
+
+// The instruction in flight and the cpu's internal latches between ticks
+#[derive(Clone, Copy)]
+pub struct MicroState {
+    inst: Instruction,
+    ops: [MicroOp; MAX_MICRO_OPS],
+    len: usize,
+    next: usize,
+    // Effective address, the address before indexing and the data latch
+    addr: u16,
+    base: u16,
+    data: u8,
+    vector: u16,
+    brk: bool,
+    cycles: u8,
+}
+
+impl MicroState {
+    pub fn new() -> Self {
+        return MicroState {
+            inst: INTERRUPT,
+            ops: [MicroOp::Halt; MAX_MICRO_OPS],
+            len: 0,
+            next: 0,
+            addr: 0,
+            base: 0,
+            data: 0,
+            vector: IRQ_VECTOR,
+            brk: false,
+            cycles: 0,
+        };
+    }
+
+    pub fn in_progress(&self) -> bool {
+        return self.len > 0;
+    }
+
+    fn load(&mut self, inst: Instruction, ops: (&[MicroOp], &[MicroOp])) {
+        let (addressing, operation) = ops;
+        self.inst = inst;
+        self.len = addressing.len() + operation.len();
+        self.ops[..addressing.len()].copy_from_slice(addressing);
+        self.ops[addressing.len()..self.len].copy_from_slice(operation);
+        self.next = 0;
+    }
+}
+
+// The addressing cycles for modes that end with an effective address
+fn address_ops(mode: AddrModes) -> Option<&'static [MicroOp]> {
+    let ops: &[MicroOp] = match mode {
+        AddrModes::ZeroPage => &[MicroOp::FetchLo],
+        AddrModes::ZeroPageX => &[MicroOp::FetchLo, MicroOp::IndexZpX],
+        AddrModes::ZeroPageY => &[MicroOp::FetchLo, MicroOp::IndexZpY],
+        AddrModes::Absolute => &[MicroOp::FetchLo, MicroOp::FetchHi],
+        AddrModes::AbsoluteX => &[MicroOp::FetchLo, MicroOp::FetchHiX, MicroOp::FixIndex],
+        AddrModes::AbsoluteY => &[MicroOp::FetchLo, MicroOp::FetchHiY, MicroOp::FixIndex],
+        AddrModes::IndirectX => &[MicroOp::FetchLo, MicroOp::IndexZpX, MicroOp::PointerLo, MicroOp::PointerHi],
+        AddrModes::IndirectY => &[MicroOp::FetchLo, MicroOp::PointerLo, MicroOp::PointerHiY, MicroOp::FixIndex],
+        AddrModes::ZeroPageIndirect => &[MicroOp::FetchLo, MicroOp::PointerLo, MicroOp::PointerHi],
+        _ => return None,
+    };
+    return Some(ops);
+}
+
+// Every cycle of an instruction after its opcode fetch, as the addressing cycles
+// followed by the cycles of the operation itself
+pub fn sequence(inst: &Instruction, variant: CpuVariant) -> Option<(&'static [MicroOp], &'static [MicroOp])> {
+    let ops: (&[MicroOp], &[MicroOp]) = match inst.access() {
+        Access::Implied if inst.is_single_cycle_nop() => (&[], &[]),
+        Access::Implied => (&[MicroOp::Implied], &[]),
+        Access::Read if inst.mode == AddrModes::Immediate => (&[MicroOp::ReadImmediate, MicroOp::DecimalFixup], &[]),
+        Access::Read if inst.is_stall_nop() => (&[MicroOp::FetchLo, MicroOp::FetchHi], &[MicroOp::Stall; 5]),
+        Access::Read => (address_ops(inst.mode)?, &[MicroOp::ReadOperand, MicroOp::DecimalFixup]),
+        Access::Write => (address_ops(inst.mode)?, &[MicroOp::Store]),
+        Access::Modify => (address_ops(inst.mode)?, &[MicroOp::ModifyRead, MicroOp::ModifyDummy, MicroOp::ModifyWrite]),
+        Access::Push => (&[MicroOp::DummyReadPc, MicroOp::Push], &[]),
+        Access::Pull => (&[MicroOp::DummyReadPc, MicroOp::DummyReadStack, MicroOp::Pull], &[]),
+        Access::Branch => (&[MicroOp::Branch, MicroOp::BranchTake, MicroOp::BranchFix], &[]),
+        Access::Jump => match inst.mode {
+            AddrModes::Absolute => (&[MicroOp::FetchLo, MicroOp::JumpHi], &[]),
+            AddrModes::Indirect if variant.is_cmos() => (&[MicroOp::FetchLo, MicroOp::FetchHi, MicroOp::DummyReadOperand], &[MicroOp::PointerLo, MicroOp::JumpPointerHi]),
+            AddrModes::Indirect => (&[MicroOp::FetchLo, MicroOp::FetchHi], &[MicroOp::PointerLo, MicroOp::JumpPointerHi]),
+            AddrModes::AbsoluteIndirectX => (&[MicroOp::FetchLo, MicroOp::FetchHi, MicroOp::IndexPointerX], &[MicroOp::PointerLo, MicroOp::JumpPointerHi]),
+            _ => return None,
+        },
+        Access::Jsr => (&[MicroOp::FetchLo, MicroOp::DummyReadStack, MicroOp::PushPch, MicroOp::PushPcl, MicroOp::JumpHi], &[]),
+        Access::Rts => (&[MicroOp::DummyReadPc, MicroOp::DummyReadStack, MicroOp::PullPcl, MicroOp::PullPch, MicroOp::ReadPcInc], &[]),
+        Access::Rti => (&[MicroOp::DummyReadPc, MicroOp::DummyReadStack, MicroOp::PullStatus, MicroOp::PullPcl, MicroOp::PullPch], &[]),
+        Access::Brk => (&[MicroOp::ReadPcInc, MicroOp::PushPch, MicroOp::PushPcl, MicroOp::PushStatus], &[MicroOp::VectorLo, MicroOp::VectorHi]),
+        Access::Jam => (&[MicroOp::Halt], &[]),
+    };
+    return Some(ops);
+}
+
+const INTERRUPT_OPS: [MicroOp; 6] = [
+    MicroOp::DummyReadPc, MicroOp::PushPch, MicroOp::PushPcl, MicroOp::PushStatus, MicroOp::VectorLo, MicroOp::VectorHi
+];
+
+// The cycle-stepped core. Unlike the instruction-stepped one pc always points at
+// the next byte to fetch, so it's on the next instruction once the last cycle runs
+pub trait CycleOps {
+    fn run_cycle(&mut self) -> CpuResult<()>;
+    fn step_cycles(&mut self) -> CpuResult<u8>;
+    fn start_instruction(&mut self) -> CpuResult<()>;
+    fn run_micro_ops(&mut self) -> CpuResult<bool>;
+    fn micro_op(&mut self, op: MicroOp) -> CpuResult<Cycle>;
+    fn next_read(&self) -> Option<u16>;
+    fn skips_fix_index(&self) -> bool;
+    fn fix_index_address(&self) -> u16;
+    fn pointer_hi_address(&self) -> u16;
+    fn fetch_operand(&mut self) -> u8;
+    fn index_latch(&mut self, base: u16, index: u8);
+    fn finish_read(&mut self, val: u8) -> Cycle;
+}
+
+impl CycleOps for Cpu {
+
+    // One cycle: a DMA cycle or the opcode fetch of a new instruction or interrupt,
+    // or the next micro op of the one in flight. A jammed cpu stops ticking
+    fn run_cycle(&mut self) -> CpuResult<()> {
+        let result = if self.micro.in_progress() {
+            // DMA halts the cpu on its next read, in the middle of an instruction too
+            if let Some(addr) = self.next_read() {
+                if self.dma_stall(addr) {
+                    return Ok(());
+                }
+            }
+            self.run_micro_ops()
+        } else if self.jammed || self.dma_stall(self.pc) {
+            return Ok(());
+        } else {
+            self.start_instruction().map(|_| !self.micro.in_progress())
+        };
+        let done = match result {
+            Ok(done) => done,
+            Err(err) => {
+                self.micro.len = 0;
+                return Err(err);
+            },
+        };
+        self.micro.cycles += 1;
+        self.cycles += 1;
+        if done {
+            self.micro.len = 0;
+            self.cycles_taken = self.micro.cycles;
+        }
+        return Ok(());
+    }
+
+    // Tick until the instruction in flight, or the next one, is finished
+    fn step_cycles(&mut self) -> CpuResult<u8> {
+        if !self.micro.in_progress() {
+            if self.jammed {
+                return Ok(0);
+            }
+            self.run_dma();
+        }
+        self.run_cycle()?;
+        while self.micro.in_progress() {
+            self.run_cycle()?;
+        }
+        return Ok(self.cycles_taken);
+    }
+
+    // Interrupts are polled between instructions, like step() does
+    fn start_instruction(&mut self) -> CpuResult<()> {
+        self.page_crossed = false;
+        self.branch_taken = false;
+        self.micro.cycles = 0;
+
+        if self.nmi_pending || (self.irq_asserted() && !self.flags.inter_disable) {
+            self.micro.vector = if self.nmi_pending { NMI_VECTOR } else { IRQ_VECTOR };
+            self.micro.brk = false;
+            self.nmi_pending = false;
+            self.read(self.pc);
+            self.micro.load(INTERRUPT, (&INTERRUPT_OPS, &[]));
+            return Ok(());
+        }
+
+        let op_pc = self.pc;
+        let byte = self.read(self.pc);
+        let inst = match self.variant.lookup(byte) {
+            Some(inst) => inst,
+            None => return Err(CpuError::InvalidOpcode { op: byte, pc: op_pc }),
+        };
+        let ops = match sequence(inst, self.variant) {
+            Some(ops) => ops,
+            None => return Err(CpuError::InvalidAddressingMode { mode: inst.mode, pc: op_pc }),
+        };
+        self.pc = self.pc.wrapping_add(1);
+        self.micro.vector = IRQ_VECTOR;
+        self.micro.brk = true;
+        self.micro.load(*inst, ops);
+        return Ok(());
+    }
+
+    // Returns true once the instruction's last cycle has run
+    fn run_micro_ops(&mut self) -> CpuResult<bool> {
+        loop {
+            let op = self.micro.ops[self.micro.next];
+            self.micro.next += 1;
+            match self.micro_op(op)? {
+                Cycle::Skipped => continue,
+                Cycle::Done => return Ok(true),
+                Cycle::Used => return Ok(self.micro.next == self.micro.len),
+            }
+        }
+    }
+
+    fn micro_op(&mut self, op: MicroOp) -> CpuResult<Cycle> {
+        let inst = self.micro.inst;
+        match op {
+            MicroOp::FetchLo => self.micro.addr = self.fetch_operand().into(),
+            MicroOp::FetchHi => {
+                let upper = self.fetch_operand();
+                self.micro.addr = combine_bytes(upper.into(), self.micro.addr);
+            },
+            MicroOp::FetchHiX | MicroOp::FetchHiY => {
+                let upper = self.fetch_operand();
+                let index = if op == MicroOp::FetchHiX { self.regx } else { self.regy };
+                self.index_latch(combine_bytes(upper.into(), self.micro.addr), index);
+            },
+            MicroOp::IndexZpX | MicroOp::IndexZpY => {
+                self.read(self.micro.addr);
+                let index = if op == MicroOp::IndexZpX { self.regx } else { self.regy };
+                self.micro.addr = (self.micro.addr as u8).wrapping_add(index).into();
+            },
+            MicroOp::PointerLo => self.micro.data = self.read(self.micro.addr),
+            // Pointers stored in zero page wrap around to $00
+            MicroOp::PointerHi | MicroOp::PointerHiY => {
+                let upper = self.read((self.micro.addr as u8).wrapping_add(1).into());
+                let pointer = combine_bytes(upper.into(), self.micro.data.into());
+                if op == MicroOp::PointerHiY {
+                    self.index_latch(pointer, self.regy);
+                } else {
+                    self.micro.addr = pointer;
+                }
+            },
+            MicroOp::FixIndex => {
+                if self.skips_fix_index() {
+                    return Ok(Cycle::Skipped);
+                }
+                self.read(self.fix_index_address());
+            },
+
+            MicroOp::ReadImmediate => {
+                self.micro.addr = self.pc;
+                let val = self.fetch_operand();
+                return Ok(self.finish_read(val));
+            },
+            MicroOp::ReadOperand => {
+                let val = self.read(self.micro.addr);
+                return Ok(self.finish_read(val));
+            },
+            MicroOp::DecimalFixup => { self.read(self.micro.addr); },
+            MicroOp::Store => self.write(self.micro.addr, self.store_value(inst.mnemonic)),
+            MicroOp::ModifyRead => self.micro.data = self.read(self.micro.addr),
+            // The old value goes back out while the new one is computed, the 65C02
+            // reads the address again instead
+            MicroOp::ModifyDummy => if self.variant.is_cmos() {
+                self.read(self.micro.addr);
+            } else {
+                self.write(self.micro.addr, self.micro.data);
+            },
+            MicroOp::ModifyWrite => {
+                let res = self.modify_op(inst.mnemonic, self.micro.data);
+                self.write(self.micro.addr, res);
+            },
+            MicroOp::Implied => {
+                self.read(self.pc);
+                self.implied_op(inst.mnemonic)?;
+            },
+
+            MicroOp::DummyReadPc => { self.read(self.pc); },
+            MicroOp::ReadPcInc => { self.fetch_operand(); },
+            MicroOp::DummyReadStack => { self.read(STACK_BASE + self.sp as u16); },
+            MicroOp::Push => self.push_op(inst.mnemonic),
+            MicroOp::Pull => self.pull_op(inst.mnemonic),
+            MicroOp::PushPch => self.push_stack(split_bytes(self.pc).0),
+            MicroOp::PushPcl => self.push_stack(split_bytes(self.pc).1),
+            MicroOp::PushStatus => self.save_status(self.micro.brk),
+            MicroOp::PullStatus => self.pull_status(),
+            MicroOp::PullPcl => self.micro.data = self.pop_stack(),
+            MicroOp::PullPch => {
+                let upper = self.pop_stack();
+                self.pc = combine_bytes(upper.into(), self.micro.data.into());
+            },
+            MicroOp::VectorLo => {
+                self.micro.data = self.read(self.micro.vector);
+                self.mask_interrupts();
+            },
+            MicroOp::VectorHi => {
+                let upper = self.read(self.micro.vector.wrapping_add(1));
+                self.pc = combine_bytes(upper.into(), self.micro.data.into());
+            },
+
+            // pc is still on the high byte, there's no need to step past it
+            MicroOp::JumpHi => {
+                let upper = self.read(self.pc);
+                self.pc = combine_bytes(upper.into(), self.micro.addr);
+            },
+            MicroOp::DummyReadOperand => { self.read(self.pc.wrapping_sub(1)); },
+            MicroOp::IndexPointerX => {
+                self.read(self.pc.wrapping_sub(1));
+                self.micro.addr = self.micro.addr.wrapping_add(self.regx.into());
+            },
+            MicroOp::JumpPointerHi => {
+                let upper = self.read(self.pointer_hi_address());
+                self.pc = combine_bytes(upper.into(), self.micro.data.into());
+            },
+
+            MicroOp::Branch => {
+                self.micro.data = self.fetch_operand();
+                if !self.branch_condition(inst.mnemonic) {
+                    return Ok(Cycle::Done);
+                }
+            },
+            // The offset is added to the low byte of pc first, a carry costs one more cycle
+            MicroOp::BranchTake => {
+                self.read(self.pc);
+                let target = self.pc.wrapping_add(self.micro.data as i8 as u16);
+                self.branch_taken = true;
+                self.page_crossed = crosses_page(self.pc, target);
+                if !self.page_crossed {
+                    self.pc = target;
+                    return Ok(Cycle::Done);
+                }
+                self.micro.addr = target;
+                self.pc = (self.pc & 0xFF00) | (target & 0x00FF);
+            },
+            MicroOp::BranchFix => {
+                self.read(self.pc);
+                self.pc = self.micro.addr;
+            },
+
+            MicroOp::Stall => { self.read(0xFF00 | (self.micro.addr & 0x00FF)); },
+            // pc goes back to the KIL opcode, same as the instruction-stepped core leaves it
+            MicroOp::Halt => {
+                self.read(0xFFFF);
+                self.pc = self.pc.wrapping_sub(1);
+                self.jam(inst.op)?;
+            },
+        }
+        return Ok(Cycle::Used);
+    }
+
+    // The address the next micro op reads, or None if it writes. A skipped FixIndex
+    // hands its cycle to the read after it
+    fn next_read(&self) -> Option<u16> {
+        let mut op = self.micro.ops[self.micro.next];
+        if op == MicroOp::FixIndex && self.skips_fix_index() {
+            op = self.micro.ops[self.micro.next + 1];
+        }
+        let addr = match op {
+            MicroOp::FetchLo | MicroOp::FetchHi | MicroOp::FetchHiX | MicroOp::FetchHiY
+            | MicroOp::ReadImmediate | MicroOp::Implied | MicroOp::DummyReadPc | MicroOp::ReadPcInc
+            | MicroOp::JumpHi | MicroOp::Branch | MicroOp::BranchTake | MicroOp::BranchFix => self.pc,
+            MicroOp::IndexZpX | MicroOp::IndexZpY | MicroOp::PointerLo | MicroOp::ReadOperand
+            | MicroOp::DecimalFixup | MicroOp::ModifyRead => self.micro.addr,
+            MicroOp::ModifyDummy if self.variant.is_cmos() => self.micro.addr,
+            MicroOp::PointerHi | MicroOp::PointerHiY => (self.micro.addr as u8).wrapping_add(1).into(),
+            MicroOp::FixIndex => self.fix_index_address(),
+            MicroOp::DummyReadStack => STACK_BASE + self.sp as u16,
+            MicroOp::Pull | MicroOp::PullStatus | MicroOp::PullPcl | MicroOp::PullPch => {
+                STACK_BASE + self.sp.wrapping_add(1) as u16
+            },
+            MicroOp::VectorLo => self.micro.vector,
+            MicroOp::VectorHi => self.micro.vector.wrapping_add(1),
+            MicroOp::DummyReadOperand | MicroOp::IndexPointerX => self.pc.wrapping_sub(1),
+            MicroOp::JumpPointerHi => self.pointer_hi_address(),
+            MicroOp::Stall => 0xFF00 | (self.micro.addr & 0x00FF),
+            MicroOp::Halt => 0xFFFF,
+            MicroOp::ModifyDummy | MicroOp::Store | MicroOp::ModifyWrite | MicroOp::Push
+            | MicroOp::PushPch | MicroOp::PushPcl | MicroOp::PushStatus => return None,
+        };
+        return Some(addr);
+    }
+
+    fn skips_fix_index(&self) -> bool {
+        return !self.page_crossed && self.micro.inst.page_penalty;
+    }
+
+    // The 65C02 reads its last operand byte again, pc is already past it
+    fn fix_index_address(&self) -> u16 {
+        if self.variant.is_cmos() {
+            return self.pc.wrapping_sub(1);
+        }
+        return (self.micro.base & 0xFF00) | (self.micro.addr & 0x00FF);
+    }
+
+    // NMOS JMP ($xxFF) reads the high byte from $xx00, like fetch_indirect
+    fn pointer_hi_address(&self) -> u16 {
+        let addr = self.micro.addr;
+        if self.micro.inst.mode == AddrModes::Indirect && !self.variant.is_cmos() {
+            return (addr & 0xFF00) | (addr.wrapping_add(1) & 0x00FF);
+        }
+        return addr.wrapping_add(1);
+    }
+
+    fn fetch_operand(&mut self) -> u8 {
+        let val = self.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        return val;
+    }
+
+    fn index_latch(&mut self, base: u16, index: u8) {
+        self.micro.base = base;
+        self.micro.addr = base.wrapping_add(index.into());
+        self.page_crossed = crosses_page(base, self.micro.addr);
+    }
+
+    fn finish_read(&mut self, val: u8) -> Cycle {
+        let inst = self.micro.inst;
+        self.read_op(&inst, val);
+        if self.decimal_penalty(inst.mnemonic) {
+            return Cycle::Used;
+        }
+        return Cycle::Done;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::bus::testing::{AccessLog, RecordingBus};
+    use crate::hardware::debug::DebugUtils;
+    use crate::hardware::instruction::Ops;
+
+    struct Harness {
+        cpu: Cpu,
+        log: AccessLog,
+    }
+
+    // Zero page, the stack, the vectors and the registers filled from a seed so
+    // operands, pointers and flags differ from run to run. The opcode goes at $0200
+    fn harness(variant: CpuVariant, op: u8, seed: u32) -> Harness {
+        let (bus, log) = RecordingBus::new();
+        let mut cpu = Cpu::with_bus(variant, Box::new(bus));
+        let mut state = seed;
+        let mut next = || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            return (state >> 16) as u8;
+        };
+        for addr in (0x0000..0x0300).chain(0xFFFA..=0xFFFF) {
+            cpu.poke(addr, next());
+        }
+        cpu.poke(0x0200, op);
+        cpu.rega = next();
+        cpu.regx = next();
+        cpu.regy = next();
+        cpu.sp = next();
+        cpu.flags.set_from_stack(next());
+        cpu.pc = 0x0200;
+        log.borrow_mut().clear();
+        return Harness { cpu, log };
+    }
+
+    // Devices on the bus must see the same accesses from both cores, so the
+    // writes landing in the same places follows from the logs matching
+    fn assert_same_state(fast: &Harness, cycle: &Harness, op: u8) {
+        let (a, b) = (&fast.cpu, &cycle.cpu);
+        assert_eq!(a.dump_registers(), b.dump_registers(), "opcode {:02X}", op);
+        assert_eq!(a.cycles, b.cycles, "opcode {:02X}", op);
+        assert_eq!(a.jammed, b.jammed, "opcode {:02X}", op);
+        assert_eq!(*fast.log.borrow(), *cycle.log.borrow(), "opcode {:02X}", op);
+    }
+
+    #[test]
+    fn test_matches_instruction_stepped() {
+        for variant in [CpuVariant::Ricoh2A03, CpuVariant::Nmos6502, CpuVariant::Cmos65C02] {
+            for op in 0..=255u8 {
+                if variant.lookup(op).is_none() {
+                    continue;
+                }
+                for seed in 0..6 {
+                    let mut fast = harness(variant, op, seed);
+                    let mut cycle = harness(variant, op, seed);
+                    let cycles = fast.cpu.step().unwrap();
+                    cycle.cpu.set_exec_mode(ExecMode::Cycle);
+                    assert_eq!(cycle.cpu.step().unwrap(), cycles, "opcode {:02X}", op);
+                    assert_same_state(&fast, &cycle, op);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_interrupt_matches_instruction_stepped() {
+        for seed in 0..6 {
+            let mut fast = harness(CpuVariant::Nmos6502, Ops::Nop as u8, seed);
+            let mut cycle = harness(CpuVariant::Nmos6502, Ops::Nop as u8, seed);
+            fast.cpu.nmi();
+            cycle.cpu.nmi();
+            let cycles = fast.cpu.step().unwrap();
+            cycle.cpu.set_exec_mode(ExecMode::Cycle);
+            assert_eq!(cycle.cpu.step().unwrap(), cycles);
+            assert_same_state(&fast, &cycle, Ops::Nop as u8);
+        }
+    }
+
+    #[test]
+    fn test_one_access_per_tick() {
+        for variant in [CpuVariant::Nmos6502, CpuVariant::Cmos65C02] {
+            for op in 0..=255u8 {
+                if variant.lookup(op).is_none() {
+                    continue;
+                }
+                let mut h = harness(variant, op, 7);
+                loop {
+                    h.cpu.tick().unwrap();
+                    assert_eq!(h.log.borrow().len() as u64, h.cpu.cycles, "opcode {:02X}", op);
+                    if !h.cpu.micro.in_progress() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_store_lands_on_last_cycle() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0, Ops::StaAbs as u8);
+        cpu.poke(1, 0x00);
+        cpu.poke(2, 0x20);
+        cpu.rega = 0x42;
+        for _ in 0..3 {
+            cpu.tick().unwrap();
+            assert_eq!(cpu.peek(0x2000), 0);
+        }
+        cpu.tick().unwrap();
+        assert_eq!(cpu.peek(0x2000), 0x42);
+        assert!(!cpu.micro.in_progress());
+        assert_eq!(cpu.pc, 3);
+    }
+
+    #[test]
+    fn test_branch_cycles() {
+        let mut cpu = Cpu::new();
+        cpu.set_exec_mode(ExecMode::Cycle);
+        cpu.pc = 0x02F0;
+        cpu.poke(0x02F0, Ops::Bne as u8);
+        cpu.poke(0x02F1, 0x02);
+        cpu.flags.zero = true;
+        assert_eq!(cpu.step().unwrap(), 2);
+        assert_eq!(cpu.pc, 0x02F2);
+
+        cpu.pc = 0x02F0;
+        cpu.flags.zero = false;
+        assert_eq!(cpu.step().unwrap(), 3);
+        assert_eq!(cpu.pc, 0x02F4);
+
+        cpu.pc = 0x02F0;
+        cpu.poke(0x02F1, 0x10);
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert_eq!(cpu.pc, 0x0302);
+    }
+
+    #[test]
+    fn test_page_cross_read_cycles() {
+        let mut cpu = Cpu::new();
+        cpu.set_exec_mode(ExecMode::Cycle);
+        cpu.poke(0, Ops::LdaAbsX as u8);
+        cpu.poke(1, 0xF0);
+        cpu.poke(2, 0x10);
+        cpu.poke(0x1110, 0x99);
+        cpu.regx = 0x20;
+        assert_eq!(cpu.step().unwrap(), 5);
+        assert_eq!(cpu.rega, 0x99);
+    }
+
+    #[test]
+    fn test_interrupt_ticks() {
+        let mut cpu = Cpu::new();
+        cpu.set_exec_mode(ExecMode::Cycle);
+        cpu.poke(0xFFFA, 0x34);
+        cpu.poke(0xFFFB, 0x12);
+        cpu.pc = 0x8000;
+        cpu.nmi();
+        assert_eq!(cpu.step().unwrap(), 7);
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.peek_stack(), 0x20);
+        assert_eq!(cpu.peek(0x01FF), 0x80);
+        assert!(cpu.flags.inter_disable);
+    }
+
+    #[test]
+    fn test_step_finishes_ticked_instruction() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0, Ops::LdaAbs as u8);
+        cpu.poke(1, 0x00);
+        cpu.poke(2, 0x03);
+        cpu.poke(0x0300, 0x55);
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.step().unwrap(), 4);
+        assert_eq!(cpu.rega, 0x55);
+        assert_eq!(cpu.pc, 3);
+        assert_eq!(cpu.cycles, 4);
+    }
+
+    #[test]
+    fn test_invalid_opcode() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x0200;
+        cpu.poke(0x0200, 0x8B);
+        assert_eq!(cpu.tick(), Err(CpuError::InvalidOpcode { op: 0x8B, pc: 0x0200 }));
+        assert_eq!(cpu.pc, 0x0200);
+        assert!(!cpu.micro.in_progress());
+    }
+
+    #[test]
+    fn test_jammed_stops_ticking() {
+        let mut cpu = Cpu::new();
+        cpu.set_exec_mode(ExecMode::Cycle);
+        cpu.poke(0, Ops::Kil02 as u8);
+        assert_eq!(cpu.step().unwrap(), 2);
+        assert!(cpu.jammed);
+        assert_eq!(cpu.pc, 0);
+        cpu.tick().unwrap();
+        assert_eq!(cpu.cycles, 2);
+        assert_eq!(cpu.step().unwrap(), 0);
+    }
+}
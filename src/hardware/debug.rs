@@ -0,0 +1,165 @@
+use crate::hardware::cpu::Cpu;
+use crate::hardware::instruction::AddrModes;
+use crate::hardware::stack::STACK_BASE;
+use crate::utils::combine_bytes;
+
+const DUMP_ROW_LEN: usize = 16;
+
+// For debuggers and other tools
+pub trait DebugUtils {
+    fn dump_registers(&self) -> String;
+    fn dump_memory(&self, start: u16, end: u16) -> String;
+    fn dump_stack(&self) -> String;
+    fn disassemble(&self, addr: u16) -> (String, u8);
+    fn peek(&self, addr: u16) -> u8;
+    fn poke(&mut self, addr: u16, val: u8);
+}
+
+impl DebugUtils for Cpu {
+
+    // Same layout as the nestest log, ex. A:00 X:00 Y:00 P:24 SP:FD PC:C000
+    fn dump_registers(&self) -> String {
+        return format!(
+            "A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PC:{:04X}",
+            self.rega, self.regx, self.regy, self.flags.to_u8(), self.sp, self.pc
+        );
+    }
+
+    // Hex dump of start..=end, sixteen bytes per row prefixed with the row address
+    fn dump_memory(&self, start: u16, end: u16) -> String {
+        let mut res = String::new();
+        let addrs: Vec<u16> = (start..=end).collect();
+        for row in addrs.chunks(DUMP_ROW_LEN) {
+            let bytes: Vec<String> = row.iter().map(|addr| format!("{:02X}", self.peek(*addr))).collect();
+            res.push_str(&format!("{:04X}: {}\n", row[0], bytes.join(" ")));
+        }
+        return res;
+    }
+
+    fn dump_stack(&self) -> String {
+        return self.dump_memory(STACK_BASE, STACK_BASE + 0xFF);
+    }
+
+    // Decode the instruction at addr without running it, ex. LDA $1234,X.
+    // Returns the text and the instruction length so callers can walk forward
+    fn disassemble(&self, addr: u16) -> (String, u8) {
+        let byte = self.peek(addr);
+        let inst = match self.variant.lookup(byte) {
+            Some(inst) => inst,
+            None => return (format!(".DB ${:02X}", byte), 1),
+        };
+        let lower = self.peek(addr.wrapping_add(1));
+        let upper = self.peek(addr.wrapping_add(2));
+        let abs = combine_bytes(upper.into(), lower.into());
+        let operand = match inst.mode {
+            AddrModes::Implied => String::new(),
+            AddrModes::Accumulator => String::from(" A"),
+            AddrModes::Immediate => format!(" #${:02X}", lower),
+            AddrModes::ZeroPage => format!(" ${:02X}", lower),
+            AddrModes::ZeroPageX => format!(" ${:02X},X", lower),
+            AddrModes::ZeroPageY => format!(" ${:02X},Y", lower),
+            AddrModes::Absolute => format!(" ${:04X}", abs),
+            AddrModes::AbsoluteX => format!(" ${:04X},X", abs),
+            AddrModes::AbsoluteY => format!(" ${:04X},Y", abs),
+            AddrModes::Indirect => format!(" (${:04X})", abs),
+            AddrModes::IndirectX => format!(" (${:02X},X)", lower),
+            AddrModes::IndirectY => format!(" (${:02X}),Y", lower),
+            AddrModes::ZeroPageIndirect => format!(" (${:02X})", lower),
+            AddrModes::AbsoluteIndirectX => format!(" (${:04X},X)", abs),
+            AddrModes::Relative => {
+                let target = addr.wrapping_add(2).wrapping_add(lower as i8 as u16);
+                format!(" ${:04X}", target)
+            },
+        };
+        let name = format!("{:?}", inst.mnemonic).to_uppercase();
+        return (format!("{}{}", name, operand), inst.bytes);
+    }
+
+    // Reads and writes for tools, these never touch pc, cycles or flags and never
+    // disturb a device. A poke only lands in memory, registers ignore it
+    fn peek(&self, addr: u16) -> u8 {
+        return self.bus.peek(addr);
+    }
+
+    fn poke(&mut self, addr: u16, val: u8) {
+        self.bus.poke(addr, val);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::variant::CpuVariant;
+
+    #[test]
+    fn test_dump_registers() {
+        let mut cpu = Cpu::new();
+        cpu.regx = 0x1F;
+        cpu.pc = 0xC000;
+        cpu.flags.carry = true;
+        assert_eq!(cpu.dump_registers(), "A:00 X:1F Y:00 P:01 SP:FF PC:C000");
+    }
+
+    #[test]
+    fn test_dump_memory() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0x0200, 0xAB);
+        cpu.poke(0x0211, 0xCD);
+        let dump = cpu.dump_memory(0x0200, 0x0211);
+        let rows: Vec<&str> = dump.lines().collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].starts_with("0200: AB 00"));
+        assert_eq!(rows[1], "0210: 00 CD");
+    }
+
+    #[test]
+    fn test_dump_stack() {
+        let cpu = Cpu::new();
+        assert_eq!(cpu.dump_stack().lines().count(), 16);
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0x0200, 0xBD);
+        cpu.poke(0x0201, 0x34);
+        cpu.poke(0x0202, 0x12);
+        assert_eq!(cpu.disassemble(0x0200), (String::from("LDA $1234,X"), 3));
+        cpu.poke(0x0203, 0x0A);
+        assert_eq!(cpu.disassemble(0x0203), (String::from("ASL A"), 1));
+        cpu.poke(0x0204, 0xB1);
+        cpu.poke(0x0205, 0x80);
+        assert_eq!(cpu.disassemble(0x0204), (String::from("LDA ($80),Y"), 2));
+        cpu.poke(0x0206, 0x8B);
+        assert_eq!(cpu.disassemble(0x0206), (String::from(".DB $8B"), 1));
+    }
+
+    #[test]
+    fn test_disassemble_branch() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0x0210, 0xD0);
+        cpu.poke(0x0211, 0xFC);
+        assert_eq!(cpu.disassemble(0x0210), (String::from("BNE $020E"), 2));
+    }
+
+    #[test]
+    fn test_disassemble_cmos() {
+        let mut cpu = Cpu::with_variant(CpuVariant::Cmos65C02);
+        cpu.poke(0x0200, 0x7C);
+        cpu.poke(0x0201, 0xFF);
+        cpu.poke(0x0202, 0x10);
+        assert_eq!(cpu.disassemble(0x0200), (String::from("JMP ($10FF,X)"), 3));
+        cpu.poke(0x0203, 0xB2);
+        cpu.poke(0x0204, 0x80);
+        assert_eq!(cpu.disassemble(0x0203), (String::from("LDA ($80)"), 2));
+    }
+
+    #[test]
+    fn test_peek_poke() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0x1234, 0x56);
+        assert_eq!(cpu.peek(0x1234), 0x56);
+        assert_eq!(cpu.pc, 0);
+        assert_eq!(cpu.cycles, 0);
+    }
+}
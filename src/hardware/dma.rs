@@ -0,0 +1,230 @@
+use crate::utils::combine_bytes;
+
+// Writing a page number here copies that page to OAM
+pub const OAM_DMA: u16 = 0x4014;
+pub const OAM_DATA: u16 = 0x2004;
+const OAM_SIZE: u16 = 0x100;
+
+// The DMA unit alternates get (read) and put (write) cycles with the APU clock.
+// Odd cpu cycles are gets, so an OAM DMA that halts the cpu on an odd cycle
+// needs an extra cycle to line up and takes 514 cycles instead of 513
+pub fn is_get_cycle(cycle: u64) -> bool {
+    return cycle % 2 == 1;
+}
+
+// What the DMA unit does with the bus on one cycle the cpu is halted for
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DmaCycle {
+    Halt,
+    // The DMC's second cycle, spent before it may fetch
+    Dummy,
+    // Waiting for a get or put cycle
+    Align,
+    OamRead(u16),
+    OamWrite(u8),
+    DmcRead(u16),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum DmcState {
+    Idle,
+    // Requested by the APU, halts the cpu from the given cycle on
+    Pending { addr: u16, at: u64 },
+    Halt(u16),
+    Dummy(u16),
+    Ready(u16),
+}
+
+// OAM DMA ($4014) and the DMC sample fetches, one cycle at a time. Both halt the
+// cpu. When they overlap the DMC's halt and dummy cycles run alongside the OAM
+// copy and its fetch takes the next get cycle, pushing the OAM copy back
+pub struct Dma {
+    oam_page: Option<u8>,
+    oam_index: u16,
+    oam_latch: Option<u8>,
+    oam_halted: bool,
+    dmc: DmcState,
+    dmc_sample: Option<u8>,
+}
+
+impl Dma {
+    pub fn new() -> Self {
+        return Dma {
+            oam_page: None,
+            oam_index: 0,
+            oam_latch: None,
+            oam_halted: false,
+            dmc: DmcState::Idle,
+            dmc_sample: None,
+        };
+    }
+
+    pub fn start_oam(&mut self, page: u8) {
+        self.oam_page = Some(page);
+        self.oam_index = 0;
+        self.oam_latch = None;
+        self.oam_halted = false;
+    }
+
+    // The APU asks for the next sample byte when its buffer empties. `at` is the
+    // cpu cycle the request halts the cpu on
+    pub fn request_dmc(&mut self, addr: u16, at: u64) {
+        self.dmc = DmcState::Pending { addr, at };
+    }
+
+    pub fn take_dmc_sample(&mut self) -> Option<u8> {
+        return self.dmc_sample.take();
+    }
+
+    pub fn oam_loaded(&mut self, val: u8) {
+        self.oam_latch = Some(val);
+    }
+
+    pub fn dmc_loaded(&mut self, val: u8) {
+        self.dmc_sample = Some(val);
+    }
+
+    pub fn active(&self) -> bool {
+        return self.oam_page.is_some() || !matches!(self.dmc, DmcState::Idle | DmcState::Pending { .. });
+    }
+
+    // The DMA cycle for cpu cycle `cycle`, or None if the cpu isn't halted on it.
+    // OamRead and DmcRead values are passed back with oam_loaded and dmc_loaded
+    pub fn next(&mut self, cycle: u64) -> Option<DmaCycle> {
+        if let DmcState::Pending { addr, at } = self.dmc {
+            if at <= cycle {
+                self.dmc = DmcState::Halt(addr);
+            }
+        }
+        if !self.active() {
+            return None;
+        }
+
+        let get = is_get_cycle(cycle);
+        let dmc_cycle = match self.dmc {
+            DmcState::Halt(addr) => {
+                self.dmc = DmcState::Dummy(addr);
+                Some(DmaCycle::Halt)
+            },
+            DmcState::Dummy(addr) => {
+                self.dmc = DmcState::Ready(addr);
+                Some(DmaCycle::Dummy)
+            },
+            DmcState::Ready(addr) if get => {
+                self.dmc = DmcState::Idle;
+                return Some(DmaCycle::DmcRead(addr));
+            },
+            DmcState::Ready(_) => Some(DmaCycle::Align),
+            _ => None,
+        };
+
+        let page = match self.oam_page {
+            Some(page) => page,
+            None => return dmc_cycle,
+        };
+        if !self.oam_halted {
+            self.oam_halted = true;
+            return Some(DmaCycle::Halt);
+        }
+        if get {
+            if self.oam_latch.is_some() {
+                return Some(DmaCycle::Align);
+            }
+            return Some(DmaCycle::OamRead(combine_bytes(page.into(), self.oam_index)));
+        }
+        let val = match self.oam_latch.take() {
+            Some(val) => val,
+            None => return Some(DmaCycle::Align),
+        };
+        self.oam_index += 1;
+        if self.oam_index == OAM_SIZE {
+            self.oam_page = None;
+        }
+        return Some(DmaCycle::OamWrite(val));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs the DMA from `start` with memory where every byte is its low address
+    // byte, returns how many cycles the cpu was halted and the bytes written to OAM
+    fn run(dma: &mut Dma, start: u64) -> (u64, Vec<u8>) {
+        let mut cycle = start;
+        let mut written = Vec::new();
+        while let Some(access) = dma.next(cycle) {
+            match access {
+                DmaCycle::OamRead(addr) => dma.oam_loaded(addr as u8),
+                DmaCycle::OamWrite(val) => written.push(val),
+                DmaCycle::DmcRead(addr) => dma.dmc_loaded(addr as u8),
+                _ => {},
+            }
+            cycle += 1;
+        }
+        return (cycle - start, written);
+    }
+
+    #[test]
+    fn test_oam_dma_cycles() {
+        let mut dma = Dma::new();
+        dma.start_oam(0x02);
+        let (cycles, written) = run(&mut dma, 100);
+        assert_eq!(cycles, 513);
+        assert_eq!(written, (0..=255).collect::<Vec<u8>>());
+
+        dma.start_oam(0x02);
+        assert_eq!(run(&mut dma, 101).0, 514);
+    }
+
+    #[test]
+    fn test_idle() {
+        let mut dma = Dma::new();
+        assert_eq!(dma.next(0), None);
+        dma.request_dmc(0xC000, 10);
+        assert_eq!(dma.next(9), None);
+        assert!(dma.next(10).is_some());
+    }
+
+    #[test]
+    fn test_dmc_dma_cycles() {
+        let mut dma = Dma::new();
+        dma.request_dmc(0xC012, 101);
+        assert_eq!(run(&mut dma, 101).0, 3);
+        assert_eq!(dma.take_dmc_sample(), Some(0x12));
+        assert_eq!(dma.take_dmc_sample(), None);
+
+        dma.request_dmc(0xC012, 100);
+        assert_eq!(run(&mut dma, 100).0, 4);
+    }
+
+    // Halted on cycle 0 the OAM copy takes 513 cycles and its puts land on the
+    // even cycles, the last one on 512
+    fn oam_with_dmc(dmc_at: u64) -> (u64, Vec<u8>) {
+        let mut dma = Dma::new();
+        dma.start_oam(0x02);
+        dma.request_dmc(0xC000, dmc_at);
+        let result = run(&mut dma, 0);
+        assert_eq!(dma.take_dmc_sample(), Some(0x00));
+        return result;
+    }
+
+    #[test]
+    fn test_dmc_during_oam_dma() {
+        let (cycles, written) = oam_with_dmc(100);
+        assert_eq!(cycles, 513 + 2);
+        assert_eq!(written, (0..=255).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_dmc_at_end_of_oam_dma() {
+        assert_eq!(oam_with_dmc(510).0, 513 + 1);
+        assert_eq!(oam_with_dmc(512).0, 513 + 3);
+    }
+
+    #[test]
+    fn test_dmc_and_oam_together() {
+        let (cycles, _) = oam_with_dmc(0);
+        assert_eq!(cycles, 513 + 2);
+    }
+}
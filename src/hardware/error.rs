@@ -0,0 +1,87 @@
+use std::fmt;
+use crate::hardware::instruction::AddrModes;
+use crate::hardware::registers::Registers;
+
+// Faults in the program being run, returned instead of panicking so a host can report them
+#[derive(Debug, PartialEq)]
+pub enum CpuError {
+    // Opcode byte with no instruction behind it
+    InvalidOpcode { op: u8, pc: u16 },
+    // KIL opcode hit while the cpu is set to report jams as errors
+    Jammed { op: u8, pc: u16 },
+    InvalidAddressingMode { mode: AddrModes, pc: u16 },
+    InvalidRegister { reg: Registers, pc: u16 },
+}
+
+pub type CpuResult<T> = Result<T, CpuError>;
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            CpuError::InvalidOpcode { op, pc } => write!(f, "Invalid opcode {:#04x} at {:#06x}", op, pc),
+            CpuError::Jammed { op, pc } => write!(f, "CPU jammed by opcode {:#04x} at {:#06x}", op, pc),
+            CpuError::InvalidAddressingMode { mode, pc } => write!(f, "Invalid addressing mode {:?} at {:#06x}", mode, pc),
+            CpuError::InvalidRegister { reg, pc } => write!(f, "Invalid register {:?} at {:#06x}", reg, pc),
+        };
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+// Problems with a ROM image, found while loading it rather than while running it
+#[derive(Debug, PartialEq)]
+pub enum CartridgeError {
+    Io(std::io::ErrorKind),
+    // Fewer than 16 bytes, not even a whole header
+    TooShort { len: usize },
+    // The first four bytes aren't "NES" followed by $1A
+    BadMagic,
+    // The header promises more trainer, PRG or CHR data than the file has
+    Truncated { expected: usize, found: usize },
+    NoPrgRom,
+    // A NES 2.0 exponent size that doesn't fit in memory
+    TooLarge,
+    // No board logic for this mapper number yet
+    UnsupportedMapper(u16),
+}
+
+pub type CartridgeResult<T> = Result<T, CartridgeError>;
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            CartridgeError::Io(kind) => write!(f, "Couldn't read ROM: {:?}", kind),
+            CartridgeError::TooShort { len } => write!(f, "ROM is only {} bytes, too short for an iNES header", len),
+            CartridgeError::BadMagic => write!(f, "Not an iNES ROM, the header doesn't start with NES<EOF>"),
+            CartridgeError::Truncated { expected, found } => write!(f, "ROM should be {} bytes but is {}", expected, found),
+            CartridgeError::NoPrgRom => write!(f, "Header says there's no PRG ROM"),
+            CartridgeError::TooLarge => write!(f, "Header gives a ROM size too large to load"),
+            CartridgeError::UnsupportedMapper(mapper) => write!(f, "Mapper {} isn't supported", mapper),
+        };
+    }
+}
+
+impl std::error::Error for CartridgeError {}
+
+impl From<std::io::Error> for CartridgeError {
+    fn from(err: std::io::Error) -> Self {
+        return CartridgeError::Io(err.kind());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let err = CpuError::InvalidOpcode { op: 0x8B, pc: 0xC000 };
+        assert_eq!(err.to_string(), "Invalid opcode 0x8b at 0xc000");
+    }
+
+    #[test]
+    fn test_cartridge_display() {
+        let err = CartridgeError::Truncated { expected: 40976, found: 16400 };
+        assert_eq!(err.to_string(), "ROM should be 40976 bytes but is 16400");
+    }
+}
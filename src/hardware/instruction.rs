@@ -1,12 +1,11 @@
-use num_derive::FromPrimitive;
-use num_traits::FromPrimitive;
+// The FromPrimitive derive on Ops expands to an impl inside a const block
+#![allow(non_local_definitions)]
 
-pub struct Instruction {
-    op: u8,
-}
+use num_derive::FromPrimitive;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum AddrModes {
+    Implied,
     Immediate,
     ZeroPage,
     ZeroPageX,
@@ -17,7 +16,23 @@ pub enum AddrModes {
     IndirectX,
     IndirectY,
     Accumulator,
-    Indirect
+    Indirect,
+    Relative,
+    // 65C02 only: (zp) and JMP (abs,X)
+    ZeroPageIndirect,
+    AbsoluteIndirectX
+}
+
+impl AddrModes {
+    // Opcode byte plus operand bytes
+    pub const fn len(&self) -> u8 {
+        return match self {
+            AddrModes::Implied | AddrModes::Accumulator => 1,
+            AddrModes::Absolute | AddrModes::AbsoluteX | AddrModes::AbsoluteY
+            | AddrModes::Indirect | AddrModes::AbsoluteIndirectX => 3,
+            _ => 2,
+        };
+    }
 }
 
 #[derive(PartialEq)]
@@ -28,7 +43,7 @@ pub enum TransferOption {
     S
 }
 
-#[derive(FromPrimitive, PartialEq)]
+#[derive(FromPrimitive, PartialEq, Clone, Copy, Debug)]
 pub enum Ops {
     // ADD ops
     AdcI = 0x69,
@@ -117,6 +132,7 @@ pub enum Ops {
     IncY = 0xC8,
 
     // XOR
+    #[allow(clippy::upper_case_acronyms)]
     EORI = 0x49,
     EORZp = 0x45,
     EORZpX = 0x55,
@@ -164,6 +180,7 @@ pub enum Ops {
     Nop = 0xEA,
 
     // OR
+    #[allow(clippy::upper_case_acronyms)]
     ORI = 0x09,
     ORZp = 0x05,
     ORZpX = 0x15,
@@ -239,15 +256,896 @@ pub enum Ops {
     Txa = 0x8A,
     Txs = 0x9A,
     Tya = 0x98,
+
+    // Unofficial ops, ref https://www.nesdev.org/wiki/Programming_with_unofficial_opcodes
+
+    // ASL then ORA
+    SloZp = 0x07,
+    SloZpX = 0x17,
+    SloAbs = 0x0F,
+    SloAbsX = 0x1F,
+    SloAbsY = 0x1B,
+    SloIndX = 0x03,
+    SloIndY = 0x13,
+
+    // ROL then AND
+    RlaZp = 0x27,
+    RlaZpX = 0x37,
+    RlaAbs = 0x2F,
+    RlaAbsX = 0x3F,
+    RlaAbsY = 0x3B,
+    RlaIndX = 0x23,
+    RlaIndY = 0x33,
+
+    // LSR then EOR
+    SreZp = 0x47,
+    SreZpX = 0x57,
+    SreAbs = 0x4F,
+    SreAbsX = 0x5F,
+    SreAbsY = 0x5B,
+    SreIndX = 0x43,
+    SreIndY = 0x53,
+
+    // ROR then ADC
+    RraZp = 0x67,
+    RraZpX = 0x77,
+    RraAbs = 0x6F,
+    RraAbsX = 0x7F,
+    RraAbsY = 0x7B,
+    RraIndX = 0x63,
+    RraIndY = 0x73,
+
+    // Store A & X
+    SaxZp = 0x87,
+    SaxZpY = 0x97,
+    SaxAbs = 0x8F,
+    SaxIndX = 0x83,
+
+    // Load A and X
+    LaxZp = 0xA7,
+    LaxZpY = 0xB7,
+    LaxAbs = 0xAF,
+    LaxAbsY = 0xBF,
+    LaxIndX = 0xA3,
+    LaxIndY = 0xB3,
+
+    // DEC then CMP
+    DcpZp = 0xC7,
+    DcpZpX = 0xD7,
+    DcpAbs = 0xCF,
+    DcpAbsX = 0xDF,
+    DcpAbsY = 0xDB,
+    DcpIndX = 0xC3,
+    DcpIndY = 0xD3,
+
+    // INC then SBC
+    IscZp = 0xE7,
+    IscZpX = 0xF7,
+    IscAbs = 0xEF,
+    IscAbsX = 0xFF,
+    IscAbsY = 0xFB,
+    IscIndX = 0xE3,
+    IscIndY = 0xF3,
+
+    // Immediate combos
+    AncI = 0x0B,
+    AncI2 = 0x2B,
+    AlrI = 0x4B,
+    ArrI = 0x6B,
+    AxsI = 0xCB,
+    SbcI2 = 0xEB,
+
+    // NOPs that still read their operand
+    Nop1A = 0x1A,
+    Nop3A = 0x3A,
+    Nop5A = 0x5A,
+    Nop7A = 0x7A,
+    NopDA = 0xDA,
+    NopFA = 0xFA,
+    NopI80 = 0x80,
+    NopI82 = 0x82,
+    NopI89 = 0x89,
+    NopIC2 = 0xC2,
+    NopIE2 = 0xE2,
+    NopZp04 = 0x04,
+    NopZp44 = 0x44,
+    NopZp64 = 0x64,
+    NopZpX14 = 0x14,
+    NopZpX34 = 0x34,
+    NopZpX54 = 0x54,
+    NopZpX74 = 0x74,
+    NopZpXD4 = 0xD4,
+    NopZpXF4 = 0xF4,
+    NopAbs0C = 0x0C,
+    NopAbsX1C = 0x1C,
+    NopAbsX3C = 0x3C,
+    NopAbsX5C = 0x5C,
+    NopAbsX7C = 0x7C,
+    NopAbsXDC = 0xDC,
+    NopAbsXFC = 0xFC,
+
+    // Lock up the cpu until reset
+    Kil02 = 0x02,
+    Kil12 = 0x12,
+    Kil22 = 0x22,
+    Kil32 = 0x32,
+    Kil42 = 0x42,
+    Kil52 = 0x52,
+    Kil62 = 0x62,
+    Kil72 = 0x72,
+    Kil92 = 0x92,
+    KilB2 = 0xB2,
+    KilD2 = 0xD2,
+    KilF2 = 0xF2,
+}
+
+// What a KIL opcode does to the cpu
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JamBehavior {
+    // Stop executing until reset, like the real chip
+    Halt,
+    // Treat it as a fault in the program being run
+    Error,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mnemonic {
+    Adc, And, Asl, Bcc, Bcs, Beq, Bit, Bmi, Bne, Bpl, Brk, Bvc, Bvs, Clc,
+    Cld, Cli, Clv, Cmp, Cpx, Cpy, Dec, Dex, Dey, Eor, Inc, Inx, Iny, Jmp,
+    Jsr, Lda, Ldx, Ldy, Lsr, Nop, Ora, Pha, Php, Pla, Plp, Rol, Ror, Rti,
+    Rts, Sbc, Sec, Sed, Sei, Sta, Stx, Sty, Tax, Tay, Tsx, Txa, Txs, Tya,
+
+    // Unofficial
+    Slo, Rla, Sre, Rra, Sax, Lax, Dcp, Isc, Anc, Alr, Arr, Axs, Kil,
+
+    // 65C02
+    Bra, Phx, Phy, Plx, Ply, Stz, Trb, Tsb,
+}
+
+// What an instruction does with its operand, which decides the bus cycles it makes
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Access {
+    Implied,
+    Read,
+    Write,
+    Modify,
+    Push,
+    Pull,
+    Branch,
+    Jump,
+    Jsr,
+    Rts,
+    Rti,
+    Brk,
+    Jam,
+}
+
+// Everything the cpu, timing and debug tools need to know about an opcode
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Instruction {
+    pub op: u8,
+    pub mnemonic: Mnemonic,
+    pub mode: AddrModes,
+    pub bytes: u8,
+    // Base cycle count, before page cross and branch penalties
+    pub cycles: u8,
+    // Indexed reads take an extra cycle when the index carries into the next page.
+    // Stores and read-modify-write ops always pay for it in their base count
+    pub page_penalty: bool,
+}
+
+impl Instruction {
+    // Ops that load pc themselves rather than falling through to the next instruction
+    pub fn redirects_pc(&self) -> bool {
+        return matches!(self.mnemonic,
+            Mnemonic::Jmp | Mnemonic::Jsr | Mnemonic::Rts | Mnemonic::Rti | Mnemonic::Brk
+        );
+    }
+
+    pub fn is_jam(&self) -> bool {
+        return self.mnemonic == Mnemonic::Kil;
+    }
+
+    // The 65C02 fills the $x3, $x7, $xB and $xF columns with NOPs that finish with the opcode fetch
+    pub fn is_single_cycle_nop(&self) -> bool {
+        return self.mnemonic == Mnemonic::Nop && self.mode == AddrModes::Implied && self.op & 0x03 == 0x03;
+    }
+
+    // The 65C02's $5C fetches an absolute address, then spends five cycles reading $FFxx
+    pub fn is_stall_nop(&self) -> bool {
+        return self.op == CmosOps::NopAbs5C as u8 && self.mode == AddrModes::Absolute;
+    }
+
+    pub fn access(&self) -> Access {
+        return match self.mnemonic {
+            Mnemonic::Adc | Mnemonic::And | Mnemonic::Bit | Mnemonic::Cmp | Mnemonic::Cpx
+            | Mnemonic::Cpy | Mnemonic::Eor | Mnemonic::Lda | Mnemonic::Ldx | Mnemonic::Ldy
+            | Mnemonic::Ora | Mnemonic::Sbc | Mnemonic::Lax | Mnemonic::Anc | Mnemonic::Alr
+            | Mnemonic::Arr | Mnemonic::Axs => Access::Read,
+            // The unofficial NOPs still read their operand
+            Mnemonic::Nop if self.mode != AddrModes::Implied => Access::Read,
+
+            Mnemonic::Sta | Mnemonic::Stx | Mnemonic::Sty | Mnemonic::Sax | Mnemonic::Stz => Access::Write,
+
+            Mnemonic::Asl | Mnemonic::Lsr | Mnemonic::Rol | Mnemonic::Ror | Mnemonic::Inc
+            | Mnemonic::Dec if self.mode != AddrModes::Accumulator => Access::Modify,
+            Mnemonic::Slo | Mnemonic::Rla | Mnemonic::Sre | Mnemonic::Rra | Mnemonic::Dcp
+            | Mnemonic::Isc | Mnemonic::Trb | Mnemonic::Tsb => Access::Modify,
+
+            Mnemonic::Pha | Mnemonic::Php | Mnemonic::Phx | Mnemonic::Phy => Access::Push,
+            Mnemonic::Pla | Mnemonic::Plp | Mnemonic::Plx | Mnemonic::Ply => Access::Pull,
+
+            Mnemonic::Bcc | Mnemonic::Bcs | Mnemonic::Beq | Mnemonic::Bne | Mnemonic::Bmi
+            | Mnemonic::Bpl | Mnemonic::Bvs | Mnemonic::Bvc | Mnemonic::Bra => Access::Branch,
+
+            Mnemonic::Jmp => Access::Jump,
+            Mnemonic::Jsr => Access::Jsr,
+            Mnemonic::Rts => Access::Rts,
+            Mnemonic::Rti => Access::Rti,
+            Mnemonic::Brk => Access::Brk,
+            Mnemonic::Kil => Access::Jam,
+            _ => Access::Implied,
+        };
+    }
+}
+
+const fn op(op: u8, mnemonic: Mnemonic, mode: AddrModes, cycles: u8, page_penalty: bool) -> Option<Instruction> {
+    return Some(Instruction { op, mnemonic, mode, bytes: mode.len(), cycles, page_penalty });
+}
+
+// The NMOS table, the cpu decodes through CpuVariant::lookup
+#[cfg(test)]
+pub fn lookup(op: u8) -> Option<&'static Instruction> {
+    return OPCODES[op as usize].as_ref();
+}
+
+// Indexed by opcode byte. Cycle counts from http://obelisk.me.uk/6502/reference.html and
+// https://www.nesdev.org/wiki/CPU_unofficial_opcodes, the None entries are the unstable
+// unofficial ops that are left unimplemented. KIL is charged the two cycles spent fetching it
+pub static OPCODES: [Option<Instruction>; 256] = [
+    op(0x00, Mnemonic::Brk, AddrModes::Implied, 7, false),
+    op(0x01, Mnemonic::Ora, AddrModes::IndirectX, 6, false),
+    op(0x02, Mnemonic::Kil, AddrModes::Implied, 2, false),
+    op(0x03, Mnemonic::Slo, AddrModes::IndirectX, 8, false),
+    op(0x04, Mnemonic::Nop, AddrModes::ZeroPage, 3, false),
+    op(0x05, Mnemonic::Ora, AddrModes::ZeroPage, 3, false),
+    op(0x06, Mnemonic::Asl, AddrModes::ZeroPage, 5, false),
+    op(0x07, Mnemonic::Slo, AddrModes::ZeroPage, 5, false),
+    op(0x08, Mnemonic::Php, AddrModes::Implied, 3, false),
+    op(0x09, Mnemonic::Ora, AddrModes::Immediate, 2, false),
+    op(0x0A, Mnemonic::Asl, AddrModes::Accumulator, 2, false),
+    op(0x0B, Mnemonic::Anc, AddrModes::Immediate, 2, false),
+    op(0x0C, Mnemonic::Nop, AddrModes::Absolute, 4, false),
+    op(0x0D, Mnemonic::Ora, AddrModes::Absolute, 4, false),
+    op(0x0E, Mnemonic::Asl, AddrModes::Absolute, 6, false),
+    op(0x0F, Mnemonic::Slo, AddrModes::Absolute, 6, false),
+    op(0x10, Mnemonic::Bpl, AddrModes::Relative, 2, false),
+    op(0x11, Mnemonic::Ora, AddrModes::IndirectY, 5, true),
+    op(0x12, Mnemonic::Kil, AddrModes::Implied, 2, false),
+    op(0x13, Mnemonic::Slo, AddrModes::IndirectY, 8, false),
+    op(0x14, Mnemonic::Nop, AddrModes::ZeroPageX, 4, false),
+    op(0x15, Mnemonic::Ora, AddrModes::ZeroPageX, 4, false),
+    op(0x16, Mnemonic::Asl, AddrModes::ZeroPageX, 6, false),
+    op(0x17, Mnemonic::Slo, AddrModes::ZeroPageX, 6, false),
+    op(0x18, Mnemonic::Clc, AddrModes::Implied, 2, false),
+    op(0x19, Mnemonic::Ora, AddrModes::AbsoluteY, 4, true),
+    op(0x1A, Mnemonic::Nop, AddrModes::Implied, 2, false),
+    op(0x1B, Mnemonic::Slo, AddrModes::AbsoluteY, 7, false),
+    op(0x1C, Mnemonic::Nop, AddrModes::AbsoluteX, 4, true),
+    op(0x1D, Mnemonic::Ora, AddrModes::AbsoluteX, 4, true),
+    op(0x1E, Mnemonic::Asl, AddrModes::AbsoluteX, 7, false),
+    op(0x1F, Mnemonic::Slo, AddrModes::AbsoluteX, 7, false),
+    op(0x20, Mnemonic::Jsr, AddrModes::Absolute, 6, false),
+    op(0x21, Mnemonic::And, AddrModes::IndirectX, 6, false),
+    op(0x22, Mnemonic::Kil, AddrModes::Implied, 2, false),
+    op(0x23, Mnemonic::Rla, AddrModes::IndirectX, 8, false),
+    op(0x24, Mnemonic::Bit, AddrModes::ZeroPage, 3, false),
+    op(0x25, Mnemonic::And, AddrModes::ZeroPage, 3, false),
+    op(0x26, Mnemonic::Rol, AddrModes::ZeroPage, 5, false),
+    op(0x27, Mnemonic::Rla, AddrModes::ZeroPage, 5, false),
+    op(0x28, Mnemonic::Plp, AddrModes::Implied, 4, false),
+    op(0x29, Mnemonic::And, AddrModes::Immediate, 2, false),
+    op(0x2A, Mnemonic::Rol, AddrModes::Accumulator, 2, false),
+    op(0x2B, Mnemonic::Anc, AddrModes::Immediate, 2, false),
+    op(0x2C, Mnemonic::Bit, AddrModes::Absolute, 4, false),
+    op(0x2D, Mnemonic::And, AddrModes::Absolute, 4, false),
+    op(0x2E, Mnemonic::Rol, AddrModes::Absolute, 6, false),
+    op(0x2F, Mnemonic::Rla, AddrModes::Absolute, 6, false),
+    op(0x30, Mnemonic::Bmi, AddrModes::Relative, 2, false),
+    op(0x31, Mnemonic::And, AddrModes::IndirectY, 5, true),
+    op(0x32, Mnemonic::Kil, AddrModes::Implied, 2, false),
+    op(0x33, Mnemonic::Rla, AddrModes::IndirectY, 8, false),
+    op(0x34, Mnemonic::Nop, AddrModes::ZeroPageX, 4, false),
+    op(0x35, Mnemonic::And, AddrModes::ZeroPageX, 4, false),
+    op(0x36, Mnemonic::Rol, AddrModes::ZeroPageX, 6, false),
+    op(0x37, Mnemonic::Rla, AddrModes::ZeroPageX, 6, false),
+    op(0x38, Mnemonic::Sec, AddrModes::Implied, 2, false),
+    op(0x39, Mnemonic::And, AddrModes::AbsoluteY, 4, true),
+    op(0x3A, Mnemonic::Nop, AddrModes::Implied, 2, false),
+    op(0x3B, Mnemonic::Rla, AddrModes::AbsoluteY, 7, false),
+    op(0x3C, Mnemonic::Nop, AddrModes::AbsoluteX, 4, true),
+    op(0x3D, Mnemonic::And, AddrModes::AbsoluteX, 4, true),
+    op(0x3E, Mnemonic::Rol, AddrModes::AbsoluteX, 7, false),
+    op(0x3F, Mnemonic::Rla, AddrModes::AbsoluteX, 7, false),
+    op(0x40, Mnemonic::Rti, AddrModes::Implied, 6, false),
+    op(0x41, Mnemonic::Eor, AddrModes::IndirectX, 6, false),
+    op(0x42, Mnemonic::Kil, AddrModes::Implied, 2, false),
+    op(0x43, Mnemonic::Sre, AddrModes::IndirectX, 8, false),
+    op(0x44, Mnemonic::Nop, AddrModes::ZeroPage, 3, false),
+    op(0x45, Mnemonic::Eor, AddrModes::ZeroPage, 3, false),
+    op(0x46, Mnemonic::Lsr, AddrModes::ZeroPage, 5, false),
+    op(0x47, Mnemonic::Sre, AddrModes::ZeroPage, 5, false),
+    op(0x48, Mnemonic::Pha, AddrModes::Implied, 3, false),
+    op(0x49, Mnemonic::Eor, AddrModes::Immediate, 2, false),
+    op(0x4A, Mnemonic::Lsr, AddrModes::Accumulator, 2, false),
+    op(0x4B, Mnemonic::Alr, AddrModes::Immediate, 2, false),
+    op(0x4C, Mnemonic::Jmp, AddrModes::Absolute, 3, false),
+    op(0x4D, Mnemonic::Eor, AddrModes::Absolute, 4, false),
+    op(0x4E, Mnemonic::Lsr, AddrModes::Absolute, 6, false),
+    op(0x4F, Mnemonic::Sre, AddrModes::Absolute, 6, false),
+    op(0x50, Mnemonic::Bvc, AddrModes::Relative, 2, false),
+    op(0x51, Mnemonic::Eor, AddrModes::IndirectY, 5, true),
+    op(0x52, Mnemonic::Kil, AddrModes::Implied, 2, false),
+    op(0x53, Mnemonic::Sre, AddrModes::IndirectY, 8, false),
+    op(0x54, Mnemonic::Nop, AddrModes::ZeroPageX, 4, false),
+    op(0x55, Mnemonic::Eor, AddrModes::ZeroPageX, 4, false),
+    op(0x56, Mnemonic::Lsr, AddrModes::ZeroPageX, 6, false),
+    op(0x57, Mnemonic::Sre, AddrModes::ZeroPageX, 6, false),
+    op(0x58, Mnemonic::Cli, AddrModes::Implied, 2, false),
+    op(0x59, Mnemonic::Eor, AddrModes::AbsoluteY, 4, true),
+    op(0x5A, Mnemonic::Nop, AddrModes::Implied, 2, false),
+    op(0x5B, Mnemonic::Sre, AddrModes::AbsoluteY, 7, false),
+    op(0x5C, Mnemonic::Nop, AddrModes::AbsoluteX, 4, true),
+    op(0x5D, Mnemonic::Eor, AddrModes::AbsoluteX, 4, true),
+    op(0x5E, Mnemonic::Lsr, AddrModes::AbsoluteX, 7, false),
+    op(0x5F, Mnemonic::Sre, AddrModes::AbsoluteX, 7, false),
+    op(0x60, Mnemonic::Rts, AddrModes::Implied, 6, false),
+    op(0x61, Mnemonic::Adc, AddrModes::IndirectX, 6, false),
+    op(0x62, Mnemonic::Kil, AddrModes::Implied, 2, false),
+    op(0x63, Mnemonic::Rra, AddrModes::IndirectX, 8, false),
+    op(0x64, Mnemonic::Nop, AddrModes::ZeroPage, 3, false),
+    op(0x65, Mnemonic::Adc, AddrModes::ZeroPage, 3, false),
+    op(0x66, Mnemonic::Ror, AddrModes::ZeroPage, 5, false),
+    op(0x67, Mnemonic::Rra, AddrModes::ZeroPage, 5, false),
+    op(0x68, Mnemonic::Pla, AddrModes::Implied, 4, false),
+    op(0x69, Mnemonic::Adc, AddrModes::Immediate, 2, false),
+    op(0x6A, Mnemonic::Ror, AddrModes::Accumulator, 2, false),
+    op(0x6B, Mnemonic::Arr, AddrModes::Immediate, 2, false),
+    op(0x6C, Mnemonic::Jmp, AddrModes::Indirect, 5, false),
+    op(0x6D, Mnemonic::Adc, AddrModes::Absolute, 4, false),
+    op(0x6E, Mnemonic::Ror, AddrModes::Absolute, 6, false),
+    op(0x6F, Mnemonic::Rra, AddrModes::Absolute, 6, false),
+    op(0x70, Mnemonic::Bvs, AddrModes::Relative, 2, false),
+    op(0x71, Mnemonic::Adc, AddrModes::IndirectY, 5, true),
+    op(0x72, Mnemonic::Kil, AddrModes::Implied, 2, false),
+    op(0x73, Mnemonic::Rra, AddrModes::IndirectY, 8, false),
+    op(0x74, Mnemonic::Nop, AddrModes::ZeroPageX, 4, false),
+    op(0x75, Mnemonic::Adc, AddrModes::ZeroPageX, 4, false),
+    op(0x76, Mnemonic::Ror, AddrModes::ZeroPageX, 6, false),
+    op(0x77, Mnemonic::Rra, AddrModes::ZeroPageX, 6, false),
+    op(0x78, Mnemonic::Sei, AddrModes::Implied, 2, false),
+    op(0x79, Mnemonic::Adc, AddrModes::AbsoluteY, 4, true),
+    op(0x7A, Mnemonic::Nop, AddrModes::Implied, 2, false),
+    op(0x7B, Mnemonic::Rra, AddrModes::AbsoluteY, 7, false),
+    op(0x7C, Mnemonic::Nop, AddrModes::AbsoluteX, 4, true),
+    op(0x7D, Mnemonic::Adc, AddrModes::AbsoluteX, 4, true),
+    op(0x7E, Mnemonic::Ror, AddrModes::AbsoluteX, 7, false),
+    op(0x7F, Mnemonic::Rra, AddrModes::AbsoluteX, 7, false),
+    op(0x80, Mnemonic::Nop, AddrModes::Immediate, 2, false),
+    op(0x81, Mnemonic::Sta, AddrModes::IndirectX, 6, false),
+    op(0x82, Mnemonic::Nop, AddrModes::Immediate, 2, false),
+    op(0x83, Mnemonic::Sax, AddrModes::IndirectX, 6, false),
+    op(0x84, Mnemonic::Sty, AddrModes::ZeroPage, 3, false),
+    op(0x85, Mnemonic::Sta, AddrModes::ZeroPage, 3, false),
+    op(0x86, Mnemonic::Stx, AddrModes::ZeroPage, 3, false),
+    op(0x87, Mnemonic::Sax, AddrModes::ZeroPage, 3, false),
+    op(0x88, Mnemonic::Dey, AddrModes::Implied, 2, false),
+    op(0x89, Mnemonic::Nop, AddrModes::Immediate, 2, false),
+    op(0x8A, Mnemonic::Txa, AddrModes::Implied, 2, false),
+    None, // 0x8B XAA, unstable
+    op(0x8C, Mnemonic::Sty, AddrModes::Absolute, 4, false),
+    op(0x8D, Mnemonic::Sta, AddrModes::Absolute, 4, false),
+    op(0x8E, Mnemonic::Stx, AddrModes::Absolute, 4, false),
+    op(0x8F, Mnemonic::Sax, AddrModes::Absolute, 4, false),
+    op(0x90, Mnemonic::Bcc, AddrModes::Relative, 2, false),
+    op(0x91, Mnemonic::Sta, AddrModes::IndirectY, 6, false),
+    op(0x92, Mnemonic::Kil, AddrModes::Implied, 2, false),
+    None, // 0x93 AHX (d),Y, unstable
+    op(0x94, Mnemonic::Sty, AddrModes::ZeroPageX, 4, false),
+    op(0x95, Mnemonic::Sta, AddrModes::ZeroPageX, 4, false),
+    op(0x96, Mnemonic::Stx, AddrModes::ZeroPageY, 4, false),
+    op(0x97, Mnemonic::Sax, AddrModes::ZeroPageY, 4, false),
+    op(0x98, Mnemonic::Tya, AddrModes::Implied, 2, false),
+    op(0x99, Mnemonic::Sta, AddrModes::AbsoluteY, 5, false),
+    op(0x9A, Mnemonic::Txs, AddrModes::Implied, 2, false),
+    None, // 0x9B TAS a,Y, unstable
+    None, // 0x9C SHY a,X, unstable
+    op(0x9D, Mnemonic::Sta, AddrModes::AbsoluteX, 5, false),
+    None, // 0x9E SHX a,Y, unstable
+    None, // 0x9F AHX a,Y, unstable
+    op(0xA0, Mnemonic::Ldy, AddrModes::Immediate, 2, false),
+    op(0xA1, Mnemonic::Lda, AddrModes::IndirectX, 6, false),
+    op(0xA2, Mnemonic::Ldx, AddrModes::Immediate, 2, false),
+    op(0xA3, Mnemonic::Lax, AddrModes::IndirectX, 6, false),
+    op(0xA4, Mnemonic::Ldy, AddrModes::ZeroPage, 3, false),
+    op(0xA5, Mnemonic::Lda, AddrModes::ZeroPage, 3, false),
+    op(0xA6, Mnemonic::Ldx, AddrModes::ZeroPage, 3, false),
+    op(0xA7, Mnemonic::Lax, AddrModes::ZeroPage, 3, false),
+    op(0xA8, Mnemonic::Tay, AddrModes::Implied, 2, false),
+    op(0xA9, Mnemonic::Lda, AddrModes::Immediate, 2, false),
+    op(0xAA, Mnemonic::Tax, AddrModes::Implied, 2, false),
+    None, // 0xAB LAX #i, unstable
+    op(0xAC, Mnemonic::Ldy, AddrModes::Absolute, 4, false),
+    op(0xAD, Mnemonic::Lda, AddrModes::Absolute, 4, false),
+    op(0xAE, Mnemonic::Ldx, AddrModes::Absolute, 4, false),
+    op(0xAF, Mnemonic::Lax, AddrModes::Absolute, 4, false),
+    op(0xB0, Mnemonic::Bcs, AddrModes::Relative, 2, false),
+    op(0xB1, Mnemonic::Lda, AddrModes::IndirectY, 5, true),
+    op(0xB2, Mnemonic::Kil, AddrModes::Implied, 2, false),
+    op(0xB3, Mnemonic::Lax, AddrModes::IndirectY, 5, true),
+    op(0xB4, Mnemonic::Ldy, AddrModes::ZeroPageX, 4, false),
+    op(0xB5, Mnemonic::Lda, AddrModes::ZeroPageX, 4, false),
+    op(0xB6, Mnemonic::Ldx, AddrModes::ZeroPageY, 4, false),
+    op(0xB7, Mnemonic::Lax, AddrModes::ZeroPageY, 4, false),
+    op(0xB8, Mnemonic::Clv, AddrModes::Implied, 2, false),
+    op(0xB9, Mnemonic::Lda, AddrModes::AbsoluteY, 4, true),
+    op(0xBA, Mnemonic::Tsx, AddrModes::Implied, 2, false),
+    None, // 0xBB LAS a,Y, unstable
+    op(0xBC, Mnemonic::Ldy, AddrModes::AbsoluteX, 4, true),
+    op(0xBD, Mnemonic::Lda, AddrModes::AbsoluteX, 4, true),
+    op(0xBE, Mnemonic::Ldx, AddrModes::AbsoluteY, 4, true),
+    op(0xBF, Mnemonic::Lax, AddrModes::AbsoluteY, 4, true),
+    op(0xC0, Mnemonic::Cpy, AddrModes::Immediate, 2, false),
+    op(0xC1, Mnemonic::Cmp, AddrModes::IndirectX, 6, false),
+    op(0xC2, Mnemonic::Nop, AddrModes::Immediate, 2, false),
+    op(0xC3, Mnemonic::Dcp, AddrModes::IndirectX, 8, false),
+    op(0xC4, Mnemonic::Cpy, AddrModes::ZeroPage, 3, false),
+    op(0xC5, Mnemonic::Cmp, AddrModes::ZeroPage, 3, false),
+    op(0xC6, Mnemonic::Dec, AddrModes::ZeroPage, 5, false),
+    op(0xC7, Mnemonic::Dcp, AddrModes::ZeroPage, 5, false),
+    op(0xC8, Mnemonic::Iny, AddrModes::Implied, 2, false),
+    op(0xC9, Mnemonic::Cmp, AddrModes::Immediate, 2, false),
+    op(0xCA, Mnemonic::Dex, AddrModes::Implied, 2, false),
+    op(0xCB, Mnemonic::Axs, AddrModes::Immediate, 2, false),
+    op(0xCC, Mnemonic::Cpy, AddrModes::Absolute, 4, false),
+    op(0xCD, Mnemonic::Cmp, AddrModes::Absolute, 4, false),
+    op(0xCE, Mnemonic::Dec, AddrModes::Absolute, 6, false),
+    op(0xCF, Mnemonic::Dcp, AddrModes::Absolute, 6, false),
+    op(0xD0, Mnemonic::Bne, AddrModes::Relative, 2, false),
+    op(0xD1, Mnemonic::Cmp, AddrModes::IndirectY, 5, true),
+    op(0xD2, Mnemonic::Kil, AddrModes::Implied, 2, false),
+    op(0xD3, Mnemonic::Dcp, AddrModes::IndirectY, 8, false),
+    op(0xD4, Mnemonic::Nop, AddrModes::ZeroPageX, 4, false),
+    op(0xD5, Mnemonic::Cmp, AddrModes::ZeroPageX, 4, false),
+    op(0xD6, Mnemonic::Dec, AddrModes::ZeroPageX, 6, false),
+    op(0xD7, Mnemonic::Dcp, AddrModes::ZeroPageX, 6, false),
+    op(0xD8, Mnemonic::Cld, AddrModes::Implied, 2, false),
+    op(0xD9, Mnemonic::Cmp, AddrModes::AbsoluteY, 4, true),
+    op(0xDA, Mnemonic::Nop, AddrModes::Implied, 2, false),
+    op(0xDB, Mnemonic::Dcp, AddrModes::AbsoluteY, 7, false),
+    op(0xDC, Mnemonic::Nop, AddrModes::AbsoluteX, 4, true),
+    op(0xDD, Mnemonic::Cmp, AddrModes::AbsoluteX, 4, true),
+    op(0xDE, Mnemonic::Dec, AddrModes::AbsoluteX, 7, false),
+    op(0xDF, Mnemonic::Dcp, AddrModes::AbsoluteX, 7, false),
+    op(0xE0, Mnemonic::Cpx, AddrModes::Immediate, 2, false),
+    op(0xE1, Mnemonic::Sbc, AddrModes::IndirectX, 6, false),
+    op(0xE2, Mnemonic::Nop, AddrModes::Immediate, 2, false),
+    op(0xE3, Mnemonic::Isc, AddrModes::IndirectX, 8, false),
+    op(0xE4, Mnemonic::Cpx, AddrModes::ZeroPage, 3, false),
+    op(0xE5, Mnemonic::Sbc, AddrModes::ZeroPage, 3, false),
+    op(0xE6, Mnemonic::Inc, AddrModes::ZeroPage, 5, false),
+    op(0xE7, Mnemonic::Isc, AddrModes::ZeroPage, 5, false),
+    op(0xE8, Mnemonic::Inx, AddrModes::Implied, 2, false),
+    op(0xE9, Mnemonic::Sbc, AddrModes::Immediate, 2, false),
+    op(0xEA, Mnemonic::Nop, AddrModes::Implied, 2, false),
+    op(0xEB, Mnemonic::Sbc, AddrModes::Immediate, 2, false),
+    op(0xEC, Mnemonic::Cpx, AddrModes::Absolute, 4, false),
+    op(0xED, Mnemonic::Sbc, AddrModes::Absolute, 4, false),
+    op(0xEE, Mnemonic::Inc, AddrModes::Absolute, 6, false),
+    op(0xEF, Mnemonic::Isc, AddrModes::Absolute, 6, false),
+    op(0xF0, Mnemonic::Beq, AddrModes::Relative, 2, false),
+    op(0xF1, Mnemonic::Sbc, AddrModes::IndirectY, 5, true),
+    op(0xF2, Mnemonic::Kil, AddrModes::Implied, 2, false),
+    op(0xF3, Mnemonic::Isc, AddrModes::IndirectY, 8, false),
+    op(0xF4, Mnemonic::Nop, AddrModes::ZeroPageX, 4, false),
+    op(0xF5, Mnemonic::Sbc, AddrModes::ZeroPageX, 4, false),
+    op(0xF6, Mnemonic::Inc, AddrModes::ZeroPageX, 6, false),
+    op(0xF7, Mnemonic::Isc, AddrModes::ZeroPageX, 6, false),
+    op(0xF8, Mnemonic::Sed, AddrModes::Implied, 2, false),
+    op(0xF9, Mnemonic::Sbc, AddrModes::AbsoluteY, 4, true),
+    op(0xFA, Mnemonic::Nop, AddrModes::Implied, 2, false),
+    op(0xFB, Mnemonic::Isc, AddrModes::AbsoluteY, 7, false),
+    op(0xFC, Mnemonic::Nop, AddrModes::AbsoluteX, 4, true),
+    op(0xFD, Mnemonic::Sbc, AddrModes::AbsoluteX, 4, true),
+    op(0xFE, Mnemonic::Inc, AddrModes::AbsoluteX, 7, false),
+    op(0xFF, Mnemonic::Isc, AddrModes::AbsoluteX, 7, false),
+];
+
+// The 65C02 keeps the official NMOS encodings and reuses the unofficial slots for its
+// new ops. JMP ($xxFF) reads the right pointer and costs a cycle more, shifts and rotates
+// on abs,X only pay for a page cross, and every undefined opcode is a NOP. The $x3, $x7,
+// $xB and $xF columns are single cycle NOPs, the Rockwell bit ops there aren't emulated
+pub static CMOS_OPCODES: [Option<Instruction>; 256] = [
+    op(0x00, Mnemonic::Brk, AddrModes::Implied, 7, false),
+    op(0x01, Mnemonic::Ora, AddrModes::IndirectX, 6, false),
+    op(0x02, Mnemonic::Nop, AddrModes::Immediate, 2, false),
+    op(0x03, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x04, Mnemonic::Tsb, AddrModes::ZeroPage, 5, false),
+    op(0x05, Mnemonic::Ora, AddrModes::ZeroPage, 3, false),
+    op(0x06, Mnemonic::Asl, AddrModes::ZeroPage, 5, false),
+    op(0x07, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x08, Mnemonic::Php, AddrModes::Implied, 3, false),
+    op(0x09, Mnemonic::Ora, AddrModes::Immediate, 2, false),
+    op(0x0A, Mnemonic::Asl, AddrModes::Accumulator, 2, false),
+    op(0x0B, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x0C, Mnemonic::Tsb, AddrModes::Absolute, 6, false),
+    op(0x0D, Mnemonic::Ora, AddrModes::Absolute, 4, false),
+    op(0x0E, Mnemonic::Asl, AddrModes::Absolute, 6, false),
+    op(0x0F, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x10, Mnemonic::Bpl, AddrModes::Relative, 2, false),
+    op(0x11, Mnemonic::Ora, AddrModes::IndirectY, 5, true),
+    op(0x12, Mnemonic::Ora, AddrModes::ZeroPageIndirect, 5, false),
+    op(0x13, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x14, Mnemonic::Trb, AddrModes::ZeroPage, 5, false),
+    op(0x15, Mnemonic::Ora, AddrModes::ZeroPageX, 4, false),
+    op(0x16, Mnemonic::Asl, AddrModes::ZeroPageX, 6, false),
+    op(0x17, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x18, Mnemonic::Clc, AddrModes::Implied, 2, false),
+    op(0x19, Mnemonic::Ora, AddrModes::AbsoluteY, 4, true),
+    op(0x1A, Mnemonic::Inc, AddrModes::Accumulator, 2, false),
+    op(0x1B, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x1C, Mnemonic::Trb, AddrModes::Absolute, 6, false),
+    op(0x1D, Mnemonic::Ora, AddrModes::AbsoluteX, 4, true),
+    op(0x1E, Mnemonic::Asl, AddrModes::AbsoluteX, 6, true),
+    op(0x1F, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x20, Mnemonic::Jsr, AddrModes::Absolute, 6, false),
+    op(0x21, Mnemonic::And, AddrModes::IndirectX, 6, false),
+    op(0x22, Mnemonic::Nop, AddrModes::Immediate, 2, false),
+    op(0x23, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x24, Mnemonic::Bit, AddrModes::ZeroPage, 3, false),
+    op(0x25, Mnemonic::And, AddrModes::ZeroPage, 3, false),
+    op(0x26, Mnemonic::Rol, AddrModes::ZeroPage, 5, false),
+    op(0x27, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x28, Mnemonic::Plp, AddrModes::Implied, 4, false),
+    op(0x29, Mnemonic::And, AddrModes::Immediate, 2, false),
+    op(0x2A, Mnemonic::Rol, AddrModes::Accumulator, 2, false),
+    op(0x2B, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x2C, Mnemonic::Bit, AddrModes::Absolute, 4, false),
+    op(0x2D, Mnemonic::And, AddrModes::Absolute, 4, false),
+    op(0x2E, Mnemonic::Rol, AddrModes::Absolute, 6, false),
+    op(0x2F, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x30, Mnemonic::Bmi, AddrModes::Relative, 2, false),
+    op(0x31, Mnemonic::And, AddrModes::IndirectY, 5, true),
+    op(0x32, Mnemonic::And, AddrModes::ZeroPageIndirect, 5, false),
+    op(0x33, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x34, Mnemonic::Bit, AddrModes::ZeroPageX, 4, false),
+    op(0x35, Mnemonic::And, AddrModes::ZeroPageX, 4, false),
+    op(0x36, Mnemonic::Rol, AddrModes::ZeroPageX, 6, false),
+    op(0x37, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x38, Mnemonic::Sec, AddrModes::Implied, 2, false),
+    op(0x39, Mnemonic::And, AddrModes::AbsoluteY, 4, true),
+    op(0x3A, Mnemonic::Dec, AddrModes::Accumulator, 2, false),
+    op(0x3B, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x3C, Mnemonic::Bit, AddrModes::AbsoluteX, 4, true),
+    op(0x3D, Mnemonic::And, AddrModes::AbsoluteX, 4, true),
+    op(0x3E, Mnemonic::Rol, AddrModes::AbsoluteX, 6, true),
+    op(0x3F, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x40, Mnemonic::Rti, AddrModes::Implied, 6, false),
+    op(0x41, Mnemonic::Eor, AddrModes::IndirectX, 6, false),
+    op(0x42, Mnemonic::Nop, AddrModes::Immediate, 2, false),
+    op(0x43, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x44, Mnemonic::Nop, AddrModes::ZeroPage, 3, false),
+    op(0x45, Mnemonic::Eor, AddrModes::ZeroPage, 3, false),
+    op(0x46, Mnemonic::Lsr, AddrModes::ZeroPage, 5, false),
+    op(0x47, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x48, Mnemonic::Pha, AddrModes::Implied, 3, false),
+    op(0x49, Mnemonic::Eor, AddrModes::Immediate, 2, false),
+    op(0x4A, Mnemonic::Lsr, AddrModes::Accumulator, 2, false),
+    op(0x4B, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x4C, Mnemonic::Jmp, AddrModes::Absolute, 3, false),
+    op(0x4D, Mnemonic::Eor, AddrModes::Absolute, 4, false),
+    op(0x4E, Mnemonic::Lsr, AddrModes::Absolute, 6, false),
+    op(0x4F, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x50, Mnemonic::Bvc, AddrModes::Relative, 2, false),
+    op(0x51, Mnemonic::Eor, AddrModes::IndirectY, 5, true),
+    op(0x52, Mnemonic::Eor, AddrModes::ZeroPageIndirect, 5, false),
+    op(0x53, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x54, Mnemonic::Nop, AddrModes::ZeroPageX, 4, false),
+    op(0x55, Mnemonic::Eor, AddrModes::ZeroPageX, 4, false),
+    op(0x56, Mnemonic::Lsr, AddrModes::ZeroPageX, 6, false),
+    op(0x57, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x58, Mnemonic::Cli, AddrModes::Implied, 2, false),
+    op(0x59, Mnemonic::Eor, AddrModes::AbsoluteY, 4, true),
+    op(0x5A, Mnemonic::Phy, AddrModes::Implied, 3, false),
+    op(0x5B, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x5C, Mnemonic::Nop, AddrModes::Absolute, 8, false),
+    op(0x5D, Mnemonic::Eor, AddrModes::AbsoluteX, 4, true),
+    op(0x5E, Mnemonic::Lsr, AddrModes::AbsoluteX, 6, true),
+    op(0x5F, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x60, Mnemonic::Rts, AddrModes::Implied, 6, false),
+    op(0x61, Mnemonic::Adc, AddrModes::IndirectX, 6, false),
+    op(0x62, Mnemonic::Nop, AddrModes::Immediate, 2, false),
+    op(0x63, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x64, Mnemonic::Stz, AddrModes::ZeroPage, 3, false),
+    op(0x65, Mnemonic::Adc, AddrModes::ZeroPage, 3, false),
+    op(0x66, Mnemonic::Ror, AddrModes::ZeroPage, 5, false),
+    op(0x67, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x68, Mnemonic::Pla, AddrModes::Implied, 4, false),
+    op(0x69, Mnemonic::Adc, AddrModes::Immediate, 2, false),
+    op(0x6A, Mnemonic::Ror, AddrModes::Accumulator, 2, false),
+    op(0x6B, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x6C, Mnemonic::Jmp, AddrModes::Indirect, 6, false),
+    op(0x6D, Mnemonic::Adc, AddrModes::Absolute, 4, false),
+    op(0x6E, Mnemonic::Ror, AddrModes::Absolute, 6, false),
+    op(0x6F, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x70, Mnemonic::Bvs, AddrModes::Relative, 2, false),
+    op(0x71, Mnemonic::Adc, AddrModes::IndirectY, 5, true),
+    op(0x72, Mnemonic::Adc, AddrModes::ZeroPageIndirect, 5, false),
+    op(0x73, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x74, Mnemonic::Stz, AddrModes::ZeroPageX, 4, false),
+    op(0x75, Mnemonic::Adc, AddrModes::ZeroPageX, 4, false),
+    op(0x76, Mnemonic::Ror, AddrModes::ZeroPageX, 6, false),
+    op(0x77, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x78, Mnemonic::Sei, AddrModes::Implied, 2, false),
+    op(0x79, Mnemonic::Adc, AddrModes::AbsoluteY, 4, true),
+    op(0x7A, Mnemonic::Ply, AddrModes::Implied, 4, false),
+    op(0x7B, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x7C, Mnemonic::Jmp, AddrModes::AbsoluteIndirectX, 6, false),
+    op(0x7D, Mnemonic::Adc, AddrModes::AbsoluteX, 4, true),
+    op(0x7E, Mnemonic::Ror, AddrModes::AbsoluteX, 6, true),
+    op(0x7F, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x80, Mnemonic::Bra, AddrModes::Relative, 2, false),
+    op(0x81, Mnemonic::Sta, AddrModes::IndirectX, 6, false),
+    op(0x82, Mnemonic::Nop, AddrModes::Immediate, 2, false),
+    op(0x83, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x84, Mnemonic::Sty, AddrModes::ZeroPage, 3, false),
+    op(0x85, Mnemonic::Sta, AddrModes::ZeroPage, 3, false),
+    op(0x86, Mnemonic::Stx, AddrModes::ZeroPage, 3, false),
+    op(0x87, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x88, Mnemonic::Dey, AddrModes::Implied, 2, false),
+    op(0x89, Mnemonic::Bit, AddrModes::Immediate, 2, false),
+    op(0x8A, Mnemonic::Txa, AddrModes::Implied, 2, false),
+    op(0x8B, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x8C, Mnemonic::Sty, AddrModes::Absolute, 4, false),
+    op(0x8D, Mnemonic::Sta, AddrModes::Absolute, 4, false),
+    op(0x8E, Mnemonic::Stx, AddrModes::Absolute, 4, false),
+    op(0x8F, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x90, Mnemonic::Bcc, AddrModes::Relative, 2, false),
+    op(0x91, Mnemonic::Sta, AddrModes::IndirectY, 6, false),
+    op(0x92, Mnemonic::Sta, AddrModes::ZeroPageIndirect, 5, false),
+    op(0x93, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x94, Mnemonic::Sty, AddrModes::ZeroPageX, 4, false),
+    op(0x95, Mnemonic::Sta, AddrModes::ZeroPageX, 4, false),
+    op(0x96, Mnemonic::Stx, AddrModes::ZeroPageY, 4, false),
+    op(0x97, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x98, Mnemonic::Tya, AddrModes::Implied, 2, false),
+    op(0x99, Mnemonic::Sta, AddrModes::AbsoluteY, 5, false),
+    op(0x9A, Mnemonic::Txs, AddrModes::Implied, 2, false),
+    op(0x9B, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0x9C, Mnemonic::Stz, AddrModes::Absolute, 4, false),
+    op(0x9D, Mnemonic::Sta, AddrModes::AbsoluteX, 5, false),
+    op(0x9E, Mnemonic::Stz, AddrModes::AbsoluteX, 5, false),
+    op(0x9F, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xA0, Mnemonic::Ldy, AddrModes::Immediate, 2, false),
+    op(0xA1, Mnemonic::Lda, AddrModes::IndirectX, 6, false),
+    op(0xA2, Mnemonic::Ldx, AddrModes::Immediate, 2, false),
+    op(0xA3, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xA4, Mnemonic::Ldy, AddrModes::ZeroPage, 3, false),
+    op(0xA5, Mnemonic::Lda, AddrModes::ZeroPage, 3, false),
+    op(0xA6, Mnemonic::Ldx, AddrModes::ZeroPage, 3, false),
+    op(0xA7, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xA8, Mnemonic::Tay, AddrModes::Implied, 2, false),
+    op(0xA9, Mnemonic::Lda, AddrModes::Immediate, 2, false),
+    op(0xAA, Mnemonic::Tax, AddrModes::Implied, 2, false),
+    op(0xAB, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xAC, Mnemonic::Ldy, AddrModes::Absolute, 4, false),
+    op(0xAD, Mnemonic::Lda, AddrModes::Absolute, 4, false),
+    op(0xAE, Mnemonic::Ldx, AddrModes::Absolute, 4, false),
+    op(0xAF, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xB0, Mnemonic::Bcs, AddrModes::Relative, 2, false),
+    op(0xB1, Mnemonic::Lda, AddrModes::IndirectY, 5, true),
+    op(0xB2, Mnemonic::Lda, AddrModes::ZeroPageIndirect, 5, false),
+    op(0xB3, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xB4, Mnemonic::Ldy, AddrModes::ZeroPageX, 4, false),
+    op(0xB5, Mnemonic::Lda, AddrModes::ZeroPageX, 4, false),
+    op(0xB6, Mnemonic::Ldx, AddrModes::ZeroPageY, 4, false),
+    op(0xB7, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xB8, Mnemonic::Clv, AddrModes::Implied, 2, false),
+    op(0xB9, Mnemonic::Lda, AddrModes::AbsoluteY, 4, true),
+    op(0xBA, Mnemonic::Tsx, AddrModes::Implied, 2, false),
+    op(0xBB, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xBC, Mnemonic::Ldy, AddrModes::AbsoluteX, 4, true),
+    op(0xBD, Mnemonic::Lda, AddrModes::AbsoluteX, 4, true),
+    op(0xBE, Mnemonic::Ldx, AddrModes::AbsoluteY, 4, true),
+    op(0xBF, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xC0, Mnemonic::Cpy, AddrModes::Immediate, 2, false),
+    op(0xC1, Mnemonic::Cmp, AddrModes::IndirectX, 6, false),
+    op(0xC2, Mnemonic::Nop, AddrModes::Immediate, 2, false),
+    op(0xC3, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xC4, Mnemonic::Cpy, AddrModes::ZeroPage, 3, false),
+    op(0xC5, Mnemonic::Cmp, AddrModes::ZeroPage, 3, false),
+    op(0xC6, Mnemonic::Dec, AddrModes::ZeroPage, 5, false),
+    op(0xC7, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xC8, Mnemonic::Iny, AddrModes::Implied, 2, false),
+    op(0xC9, Mnemonic::Cmp, AddrModes::Immediate, 2, false),
+    op(0xCA, Mnemonic::Dex, AddrModes::Implied, 2, false),
+    op(0xCB, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xCC, Mnemonic::Cpy, AddrModes::Absolute, 4, false),
+    op(0xCD, Mnemonic::Cmp, AddrModes::Absolute, 4, false),
+    op(0xCE, Mnemonic::Dec, AddrModes::Absolute, 6, false),
+    op(0xCF, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xD0, Mnemonic::Bne, AddrModes::Relative, 2, false),
+    op(0xD1, Mnemonic::Cmp, AddrModes::IndirectY, 5, true),
+    op(0xD2, Mnemonic::Cmp, AddrModes::ZeroPageIndirect, 5, false),
+    op(0xD3, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xD4, Mnemonic::Nop, AddrModes::ZeroPageX, 4, false),
+    op(0xD5, Mnemonic::Cmp, AddrModes::ZeroPageX, 4, false),
+    op(0xD6, Mnemonic::Dec, AddrModes::ZeroPageX, 6, false),
+    op(0xD7, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xD8, Mnemonic::Cld, AddrModes::Implied, 2, false),
+    op(0xD9, Mnemonic::Cmp, AddrModes::AbsoluteY, 4, true),
+    op(0xDA, Mnemonic::Phx, AddrModes::Implied, 3, false),
+    op(0xDB, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xDC, Mnemonic::Nop, AddrModes::Absolute, 4, false),
+    op(0xDD, Mnemonic::Cmp, AddrModes::AbsoluteX, 4, true),
+    op(0xDE, Mnemonic::Dec, AddrModes::AbsoluteX, 7, false),
+    op(0xDF, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xE0, Mnemonic::Cpx, AddrModes::Immediate, 2, false),
+    op(0xE1, Mnemonic::Sbc, AddrModes::IndirectX, 6, false),
+    op(0xE2, Mnemonic::Nop, AddrModes::Immediate, 2, false),
+    op(0xE3, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xE4, Mnemonic::Cpx, AddrModes::ZeroPage, 3, false),
+    op(0xE5, Mnemonic::Sbc, AddrModes::ZeroPage, 3, false),
+    op(0xE6, Mnemonic::Inc, AddrModes::ZeroPage, 5, false),
+    op(0xE7, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xE8, Mnemonic::Inx, AddrModes::Implied, 2, false),
+    op(0xE9, Mnemonic::Sbc, AddrModes::Immediate, 2, false),
+    op(0xEA, Mnemonic::Nop, AddrModes::Implied, 2, false),
+    op(0xEB, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xEC, Mnemonic::Cpx, AddrModes::Absolute, 4, false),
+    op(0xED, Mnemonic::Sbc, AddrModes::Absolute, 4, false),
+    op(0xEE, Mnemonic::Inc, AddrModes::Absolute, 6, false),
+    op(0xEF, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xF0, Mnemonic::Beq, AddrModes::Relative, 2, false),
+    op(0xF1, Mnemonic::Sbc, AddrModes::IndirectY, 5, true),
+    op(0xF2, Mnemonic::Sbc, AddrModes::ZeroPageIndirect, 5, false),
+    op(0xF3, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xF4, Mnemonic::Nop, AddrModes::ZeroPageX, 4, false),
+    op(0xF5, Mnemonic::Sbc, AddrModes::ZeroPageX, 4, false),
+    op(0xF6, Mnemonic::Inc, AddrModes::ZeroPageX, 6, false),
+    op(0xF7, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xF8, Mnemonic::Sed, AddrModes::Implied, 2, false),
+    op(0xF9, Mnemonic::Sbc, AddrModes::AbsoluteY, 4, true),
+    op(0xFA, Mnemonic::Plx, AddrModes::Implied, 4, false),
+    op(0xFB, Mnemonic::Nop, AddrModes::Implied, 1, false),
+    op(0xFC, Mnemonic::Nop, AddrModes::Absolute, 4, false),
+    op(0xFD, Mnemonic::Sbc, AddrModes::AbsoluteX, 4, true),
+    op(0xFE, Mnemonic::Inc, AddrModes::AbsoluteX, 7, false),
+    op(0xFF, Mnemonic::Nop, AddrModes::Implied, 1, false),
+];
+
+// Names for the opcodes the 65C02 adds, they share bytes with the unofficial NMOS ops in Ops
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum CmosOps {
+    Bra = 0x80,
+    Phx = 0xDA,
+    Phy = 0x5A,
+    Plx = 0xFA,
+    Ply = 0x7A,
+    StzZp = 0x64,
+    StzZpX = 0x74,
+    StzAbs = 0x9C,
+    StzAbsX = 0x9E,
+    TrbZp = 0x14,
+    TrbAbs = 0x1C,
+    TsbZp = 0x04,
+    TsbAbs = 0x0C,
+    BitI = 0x89,
+    BitZpX = 0x34,
+    BitAbsX = 0x3C,
+    IncAccum = 0x1A,
+    DecAccum = 0x3A,
+    OraZpInd = 0x12,
+    AndZpInd = 0x32,
+    EorZpInd = 0x52,
+    AdcZpInd = 0x72,
+    StaZpInd = 0x92,
+    LdaZpInd = 0xB2,
+    CmpZpInd = 0xD2,
+    SbcZpInd = 0xF2,
+    JmpAbsIndX = 0x7C,
+    NopAbs5C = 0x5C,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use num_traits::FromPrimitive;
 
     #[test]
     fn test_from_int() {
         let op: Ops = FromPrimitive::from_u8(0x69).unwrap();
         assert!(op == Ops::AdcI);
     }
+
+    #[test]
+    fn test_redirects_pc() {
+        assert!(lookup(Ops::JmpAbs as u8).unwrap().redirects_pc());
+        assert!(lookup(Ops::Rts as u8).unwrap().redirects_pc());
+        assert!(!lookup(Ops::Bcc as u8).unwrap().redirects_pc());
+        assert!(!lookup(Ops::LdaI as u8).unwrap().redirects_pc());
+    }
+
+    #[test]
+    fn test_from_int_unofficial() {
+        let op: Ops = FromPrimitive::from_u8(0xA7).unwrap();
+        assert!(op == Ops::LaxZp);
+        let invalid: Option<Ops> = FromPrimitive::from_u8(0x8B);
+        assert!(invalid.is_none());
+    }
+
+    #[test]
+    fn test_access() {
+        assert_eq!(lookup(Ops::LdaAbsX as u8).unwrap().access(), Access::Read);
+        assert_eq!(lookup(Ops::NopZp04 as u8).unwrap().access(), Access::Read);
+        assert_eq!(lookup(Ops::Nop as u8).unwrap().access(), Access::Implied);
+        assert_eq!(lookup(Ops::AslAccum as u8).unwrap().access(), Access::Implied);
+        assert_eq!(lookup(Ops::AslZp as u8).unwrap().access(), Access::Modify);
+        assert_eq!(lookup(Ops::SaxZp as u8).unwrap().access(), Access::Write);
+        assert_eq!(lookup(Ops::Php as u8).unwrap().access(), Access::Push);
+    }
+
+    #[test]
+    fn test_is_jam() {
+        assert!(lookup(Ops::Kil02 as u8).unwrap().is_jam());
+        assert!(!lookup(Ops::Nop as u8).unwrap().is_jam());
+    }
+
+    #[test]
+    fn test_lookup() {
+        let inst = lookup(0xBD).unwrap();
+        assert_eq!(inst.mnemonic, Mnemonic::Lda);
+        assert_eq!(inst.mode, AddrModes::AbsoluteX);
+        assert_eq!(inst.bytes, 3);
+        assert_eq!(inst.cycles, 4);
+        assert!(inst.page_penalty);
+        assert!(lookup(0x8B).is_none());
+    }
+
+    #[test]
+    fn test_table_matches_ops() {
+        for byte in 0..=255u8 {
+            let op: Option<Ops> = FromPrimitive::from_u8(byte);
+            assert_eq!(op.is_some(), lookup(byte).is_some(), "opcode {:02X}", byte);
+            if let Some(inst) = lookup(byte) {
+                assert_eq!(inst.op, byte);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cmos_table() {
+        let inst = CMOS_OPCODES[CmosOps::StzAbsX as usize].unwrap();
+        assert_eq!(inst.mnemonic, Mnemonic::Stz);
+        assert_eq!(inst.mode, AddrModes::AbsoluteX);
+        assert_eq!(CMOS_OPCODES[CmosOps::JmpAbsIndX as usize].unwrap().bytes, 3);
+        assert_eq!(CMOS_OPCODES[Ops::JmpInd as usize].unwrap().cycles, 6);
+        for byte in 0..=255u8 {
+            let inst = CMOS_OPCODES[byte as usize].unwrap();
+            assert_eq!(inst.op, byte);
+            assert!(!inst.is_jam());
+            // Official encodings are shared with the NMOS table
+            if let Some(nmos) = lookup(byte) {
+                if (byte & 0x03) != 0x03 && !matches!(nmos.mnemonic, Mnemonic::Nop | Mnemonic::Kil
+                    | Mnemonic::Anc | Mnemonic::Alr | Mnemonic::Arr | Mnemonic::Axs) && byte != 0xEB {
+                    assert_eq!(inst.mnemonic, nmos.mnemonic, "opcode {:02X}", byte);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ldy_absx_mode() {
+        assert_eq!(lookup(Ops::LdyAbsX as u8).unwrap().mode, AddrModes::AbsoluteX);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,223 @@
+use crate::hardware::cpu::Cpu;
+use crate::hardware::cycle::MicroState;
+use crate::hardware::memory::MemoryOps;
+use crate::hardware::stack::{StackOps, STACK_BASE};
+use crate::hardware::timing::Timing;
+
+pub const NMI_VECTOR: u16 = 0xFFFA;
+pub const RESET_VECTOR: u16 = 0xFFFC;
+pub const IRQ_VECTOR: u16 = 0xFFFE;
+
+// Reset, NMI and IRQ all take as long as BRK
+pub const INTERRUPT_CYCLES: u8 = 7;
+
+pub trait Interrupts {
+    fn reset(&mut self);
+    // Lines driven by the devices on the bus
+    fn nmi(&mut self);
+    fn irq(&mut self, asserted: bool);
+    fn irq_asserted(&self) -> bool;
+    fn poll_interrupts(&mut self) -> bool;
+    fn enter_interrupt(&mut self, vector: u16, is_brk: bool);
+    fn mask_interrupts(&mut self);
+    fn read_vector(&mut self, vector: u16) -> u16;
+}
+
+impl Interrupts for Cpu {
+
+    // Same state as power on: stack pointer at $FD, interrupts disabled and pc from the reset vector.
+    // Runs the interrupt sequence with the bus held in read, so the three pushes are reads too
+    fn reset(&mut self) {
+        self.nmi_pending = false;
+        self.jammed = false;
+        self.micro = MicroState::new();
+        self.read(self.pc);
+        self.read(self.pc);
+        for offset in 0..3 {
+            self.read(STACK_BASE + self.sp.wrapping_sub(offset) as u16);
+        }
+        self.sp = 0xFD;
+        self.flags.inter_disable = true;
+        self.pc = self.read_vector(RESET_VECTOR);
+        self.add_cycles(INTERRUPT_CYCLES);
+    }
+
+    // NMI is edge triggered, an edge is latched until the cpu services it
+    fn nmi(&mut self) {
+        self.nmi_pending = true;
+    }
+
+    // IRQ is level triggered, it keeps firing for as long as the line is held and I is clear
+    fn irq(&mut self, asserted: bool) {
+        self.irq_line = asserted;
+    }
+
+    // Cartridge boards drive the same line, any one of them holding it low is enough
+    fn irq_asserted(&self) -> bool {
+        return self.irq_line || self.bus.irq();
+    }
+
+    // Checked between instructions, NMI wins if both are pending.
+    // Returns true if an interrupt was serviced instead of running an instruction
+    fn poll_interrupts(&mut self) -> bool {
+        let vector = if self.nmi_pending {
+            self.nmi_pending = false;
+            NMI_VECTOR
+        } else if self.irq_asserted() && !self.flags.inter_disable {
+            IRQ_VECTOR
+        } else {
+            return false;
+        };
+        // The opcode fetch and the cycle after it both read pc and throw the byte away
+        self.read(self.pc);
+        self.read(self.pc);
+        self.enter_interrupt(vector, false);
+        self.add_cycles(INTERRUPT_CYCLES);
+        return true;
+    }
+
+    // Hardware interrupts push P with B clear, BRK pushes it set so handlers can tell them apart
+    fn enter_interrupt(&mut self, vector: u16, is_brk: bool) {
+        self.save_pc();
+        self.save_status(is_brk);
+        self.mask_interrupts();
+        self.pc = self.read_vector(vector);
+    }
+
+    // Every handler starts with I set. The 65C02 also drops out of decimal mode so
+    // handlers start in a known state
+    fn mask_interrupts(&mut self) {
+        self.flags.inter_disable = true;
+        if self.variant.is_cmos() {
+            self.flags.decimal = false;
+        }
+    }
+
+    fn read_vector(&mut self, vector: u16) -> u16 {
+        return self.read_word(vector, vector + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::bus::{Bus, FlatRam};
+    use crate::hardware::bus::testing::{Access, RecordingBus};
+    use crate::hardware::debug::DebugUtils;
+    use crate::hardware::variant::CpuVariant;
+
+    #[test]
+    fn test_reset() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0xFFFC, 0x00);
+        cpu.poke(0xFFFD, 0xC0);
+        cpu.reset();
+        assert_eq!(cpu.pc, 0xC000);
+        assert_eq!(cpu.sp, 0xFD);
+        assert!(cpu.flags.inter_disable);
+        assert_eq!(cpu.cycles, 7);
+    }
+
+    #[test]
+    fn test_reset_bus_reads() {
+        let (bus, log) = RecordingBus::new();
+        let mut cpu = Cpu::with_bus(CpuVariant::Ricoh2A03, Box::new(bus));
+        cpu.pc = 0x8000;
+        cpu.sp = 0x00;
+        cpu.reset();
+        assert_eq!(log.take(), vec![
+            Access::Read(0x8000), Access::Read(0x8000),
+            Access::Read(0x0100), Access::Read(0x01FF), Access::Read(0x01FE),
+            Access::Read(0xFFFC), Access::Read(0xFFFD),
+        ]);
+        assert_eq!(cpu.sp, 0xFD);
+    }
+
+    #[test]
+    fn test_nmi() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0xFFFA, 0x34);
+        cpu.poke(0xFFFB, 0x12);
+        cpu.pc = 0x8000;
+        cpu.flags.carry = true;
+        cpu.flags.inter_disable = true;
+        cpu.nmi();
+        assert_eq!(cpu.step().unwrap(), 7);
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.peek_stack(), 0x25);
+        assert!(!cpu.nmi_pending);
+    }
+
+    #[test]
+    fn test_nmi_edge() {
+        let mut cpu = Cpu::new();
+        cpu.nmi();
+        cpu.nmi();
+        assert!(cpu.poll_interrupts());
+        assert!(!cpu.poll_interrupts());
+    }
+
+    #[test]
+    fn test_irq() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0xFFFE, 0x78);
+        cpu.poke(0xFFFF, 0x56);
+        cpu.pc = 0x8000;
+        cpu.irq(true);
+        assert_eq!(cpu.step().unwrap(), 7);
+        assert_eq!(cpu.pc, 0x5678);
+        assert_eq!(cpu.peek_stack(), 0x20);
+        assert!(cpu.flags.inter_disable);
+    }
+
+    #[test]
+    fn test_irq_masked() {
+        let mut cpu = Cpu::new();
+        cpu.flags.inter_disable = true;
+        cpu.irq(true);
+        assert!(!cpu.poll_interrupts());
+        cpu.flags.inter_disable = false;
+        assert!(cpu.poll_interrupts());
+    }
+
+    // Flat RAM with a device holding IRQ low
+    struct IrqBus(FlatRam);
+
+    impl Bus for IrqBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            return self.0.read(addr);
+        }
+
+        fn write(&mut self, addr: u16, val: u8) {
+            self.0.write(addr, val);
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            return self.0.peek(addr);
+        }
+
+        fn poke(&mut self, addr: u16, val: u8) {
+            self.0.poke(addr, val);
+        }
+
+        fn irq(&self) -> bool {
+            return true;
+        }
+    }
+
+    #[test]
+    fn test_irq_from_bus() {
+        let mut cpu = Cpu::with_bus(CpuVariant::Ricoh2A03, Box::new(IrqBus(FlatRam::new())));
+        cpu.flags.inter_disable = false;
+        assert!(cpu.irq_asserted());
+        assert!(cpu.poll_interrupts());
+    }
+
+    #[test]
+    fn test_irq_released() {
+        let mut cpu = Cpu::new();
+        cpu.irq(true);
+        cpu.irq(false);
+        assert!(!cpu.poll_interrupts());
+    }
+}
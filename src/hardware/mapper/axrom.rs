@@ -0,0 +1,90 @@
+use crate::hardware::cartridge::{Cartridge, Mirroring, PRG_ROM_START};
+use crate::hardware::mapper::{has_bus_conflicts, Mapper, PRG_BANK_SIZE_32K};
+
+const SCREEN_SELECT: u8 = 0x10;
+
+// Mapper 7. A latch at $8000-$FFFF picks a 32 KiB PRG bank with bits 0-2 and which
+// of the console's nametables fills all four screens with bit 4. Only AMROM has
+// bus conflicts, ANROM and AOROM don't
+pub struct Axrom {
+    cartridge: Cartridge,
+    latch: u8,
+    bus_conflicts: bool,
+}
+
+impl Axrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let bus_conflicts = has_bus_conflicts(&cartridge, false);
+        return Axrom { cartridge, latch: 0, bus_conflicts };
+    }
+}
+
+impl Mapper for Axrom {
+    fn cpu_read(&self, addr: u16) -> Option<u8> {
+        if addr < PRG_ROM_START {
+            return None;
+        }
+        let bank = (self.latch & 0x07) as usize;
+        return Some(self.cartridge.read_prg_offset(bank * PRG_BANK_SIZE_32K + (addr - PRG_ROM_START) as usize));
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if let Some(rom) = self.cpu_read(addr) {
+            self.latch = if self.bus_conflicts { val & rom } else { val };
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        return self.cartridge.read_chr(addr as usize);
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.cartridge.write_chr(addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.latch & SCREEN_SELECT != 0 {
+            return Mirroring::SingleScreenUpper;
+        }
+        return Mirroring::SingleScreenLower;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::bus::Bus;
+    use crate::hardware::mapper::tests::{banked_rom, nes_bus};
+
+    #[test]
+    fn test_bank_switch() {
+        let mut bus = nes_bus(banked_rom(7, 0, 16, 0));
+        assert_eq!((bus.read(0x8000), bus.read(0xC000)), (0, 1));
+        bus.write(0x8000, 3);
+        assert_eq!((bus.read(0x8000), bus.read(0xC000)), (6, 7));
+    }
+
+    #[test]
+    fn test_single_screen() {
+        let mut bus = nes_bus(banked_rom(7, 0, 16, 0));
+        bus.ppu_bus().unwrap().write(0x2C00, 0x42);
+        assert_eq!(bus.ppu_bus().unwrap().read(0x2000), 0x42);
+
+        bus.write(0x8001, 0x10);
+        let ppu_bus = bus.ppu_bus().unwrap();
+        assert_eq!(ppu_bus.read(0x2000), 0x00);
+        ppu_bus.write(0x2400, 0x24);
+        assert_eq!(ppu_bus.read(0x2800), 0x24);
+    }
+
+    #[test]
+    fn test_bus_conflicts_submapper() {
+        // $8000 of bank 0 holds 0, the write only sticks without conflicts
+        let mut bus = nes_bus(banked_rom(7, 0, 16, 0));
+        bus.write(0x8000, 2);
+        assert_eq!(bus.read(0x8000), 4);
+
+        let mut bus = nes_bus(banked_rom(7, 2, 16, 0));
+        bus.write(0x8000, 2);
+        assert_eq!(bus.read(0x8000), 0);
+    }
+}
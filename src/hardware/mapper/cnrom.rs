@@ -0,0 +1,77 @@
+use crate::hardware::cartridge::{Cartridge, Mirroring, CHR_BANK_SIZE, PRG_ROM_START};
+use crate::hardware::mapper::{has_bus_conflicts, Mapper};
+
+// Mapper 3. PRG is laid out like NROM, a latch at $8000-$FFFF picks the 8 KiB
+// CHR bank. Most boards have bus conflicts
+pub struct Cnrom {
+    cartridge: Cartridge,
+    bank: u8,
+    bus_conflicts: bool,
+}
+
+impl Cnrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let bus_conflicts = has_bus_conflicts(&cartridge, true);
+        return Cnrom { cartridge, bank: 0, bus_conflicts };
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        return self.bank as usize * CHR_BANK_SIZE + addr as usize;
+    }
+}
+
+impl Mapper for Cnrom {
+    fn cpu_read(&self, addr: u16) -> Option<u8> {
+        if addr < PRG_ROM_START {
+            return None;
+        }
+        return Some(self.cartridge.read_prg(addr));
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if let Some(rom) = self.cpu_read(addr) {
+            self.bank = if self.bus_conflicts { val & rom } else { val };
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        return self.cartridge.read_chr(self.chr_offset(addr));
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        let offset = self.chr_offset(addr);
+        self.cartridge.write_chr(offset, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.cartridge.header.mirroring;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::bus::Bus;
+    use crate::hardware::mapper::tests::{banked_rom, nes_bus};
+
+    #[test]
+    fn test_chr_bank_switch() {
+        let mut bus = nes_bus(banked_rom(3, 0, 2, 4));
+        assert_eq!(bus.ppu_bus().unwrap().read(0x0000), 0);
+        bus.write(0x8001, 3);
+        assert_eq!(bus.ppu_bus().unwrap().read(0x0000), 3);
+        assert_eq!(bus.read(0x8000), 0);
+        assert_eq!(bus.read(0xC000), 1);
+    }
+
+    #[test]
+    fn test_bus_conflicts() {
+        // $C000 holds 1
+        let mut bus = nes_bus(banked_rom(3, 0, 2, 4));
+        bus.write(0xC000, 3);
+        assert_eq!(bus.ppu_bus().unwrap().read(0x0000), 1);
+
+        let mut bus = nes_bus(banked_rom(3, 1, 2, 4));
+        bus.write(0xC000, 3);
+        assert_eq!(bus.ppu_bus().unwrap().read(0x0000), 3);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::hardware::cartridge::{Cartridge, Mirroring, CHR_BANK_SIZE, PRG_ROM_START};
+use crate::hardware::mapper::{Mapper, PRG_BANK_SIZE_32K};
+
+// Mapper 11. GxROM with the latch bits moved around: bits 0-1 pick the 32 KiB PRG
+// bank and bits 4-7 the 8 KiB CHR bank. The board has bus conflicts
+pub struct ColorDreams {
+    cartridge: Cartridge,
+    latch: u8,
+}
+
+impl ColorDreams {
+    pub fn new(cartridge: Cartridge) -> Self {
+        return ColorDreams { cartridge, latch: 0 };
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        return (self.latch >> 4) as usize * CHR_BANK_SIZE + addr as usize;
+    }
+}
+
+impl Mapper for ColorDreams {
+    fn cpu_read(&self, addr: u16) -> Option<u8> {
+        if addr < PRG_ROM_START {
+            return None;
+        }
+        let bank = (self.latch & 0x03) as usize;
+        return Some(self.cartridge.read_prg_offset(bank * PRG_BANK_SIZE_32K + (addr - PRG_ROM_START) as usize));
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if let Some(rom) = self.cpu_read(addr) {
+            self.latch = val & rom;
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        return self.cartridge.read_chr(self.chr_offset(addr));
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        let offset = self.chr_offset(addr);
+        self.cartridge.write_chr(offset, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.cartridge.header.mirroring;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::bus::Bus;
+    use crate::hardware::mapper::tests::{banked_rom, nes_bus};
+
+    #[test]
+    fn test_bank_switch() {
+        let mut bus = nes_bus(banked_rom(11, 0, 8, 16));
+        bus.write(0x8001, 0xA2);
+        assert_eq!((bus.read(0x8000), bus.read(0xC000)), (4, 5));
+        assert_eq!(bus.ppu_bus().unwrap().read(0x0000), 10);
+    }
+
+    #[test]
+    fn test_bus_conflicts() {
+        // $C000 of bank 0 holds 1
+        let mut bus = nes_bus(banked_rom(11, 0, 8, 16));
+        bus.write(0xC000, 0xA3);
+        assert_eq!(bus.read(0x8000), 2);
+        assert_eq!(bus.ppu_bus().unwrap().read(0x0000), 0);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::hardware::cartridge::{Cartridge, Mirroring, CHR_BANK_SIZE, PRG_ROM_START};
+use crate::hardware::mapper::{Mapper, PRG_BANK_SIZE_32K};
+
+// Mapper 66, GNROM and MHROM. A latch at $8000-$FFFF picks a 32 KiB PRG bank with
+// bits 4-5 and an 8 KiB CHR bank with bits 0-1. The board has bus conflicts
+pub struct Gxrom {
+    cartridge: Cartridge,
+    latch: u8,
+}
+
+impl Gxrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        return Gxrom { cartridge, latch: 0 };
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        return (self.latch & 0x03) as usize * CHR_BANK_SIZE + addr as usize;
+    }
+}
+
+impl Mapper for Gxrom {
+    fn cpu_read(&self, addr: u16) -> Option<u8> {
+        if addr < PRG_ROM_START {
+            return None;
+        }
+        let bank = ((self.latch >> 4) & 0x03) as usize;
+        return Some(self.cartridge.read_prg_offset(bank * PRG_BANK_SIZE_32K + (addr - PRG_ROM_START) as usize));
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if let Some(rom) = self.cpu_read(addr) {
+            self.latch = val & rom;
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        return self.cartridge.read_chr(self.chr_offset(addr));
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        let offset = self.chr_offset(addr);
+        self.cartridge.write_chr(offset, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.cartridge.header.mirroring;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::bus::Bus;
+    use crate::hardware::mapper::tests::{banked_rom, nes_bus};
+
+    #[test]
+    fn test_bank_switch() {
+        let mut bus = nes_bus(banked_rom(66, 0, 8, 4));
+        bus.write(0x8001, 0x21);
+        assert_eq!((bus.read(0x8000), bus.read(0xC000)), (4, 5));
+        assert_eq!(bus.ppu_bus().unwrap().read(0x1FFF), 1);
+    }
+
+    #[test]
+    fn test_bus_conflicts() {
+        // $8000 of bank 0 holds 0
+        let mut bus = nes_bus(banked_rom(66, 0, 8, 4));
+        bus.write(0x8000, 0x21);
+        assert_eq!(bus.read(0x8000), 0);
+        assert_eq!(bus.ppu_bus().unwrap().read(0x0000), 0);
+    }
+}
@@ -0,0 +1,418 @@
+use crate::hardware::cartridge::{Cartridge, Mirroring, PRG_BANK_SIZE, PRG_RAM_BANK_SIZE, PRG_RAM_START, PRG_ROM_START};
+use crate::hardware::mapper::{battery_ram, load_battery_ram, Mapper};
+
+const CHR_BANK_SIZE_4K: usize = 0x1000;
+// SUROM and SXROM have 512 KiB of PRG, twice what the PRG register can reach
+const PRG_OUTER_SIZE: usize = 0x40000;
+
+// Writes with bit 7 set clear the shift register and lock the last PRG bank at $C000
+const RESET_BIT: u8 = 0x80;
+const CONTROL_PRG_MODE: u8 = 0x0C;
+const CONTROL_CHR_4K: u8 = 0x10;
+// Set in the PRG register on MMC1B and later when PRG RAM is disabled
+const PRG_RAM_DISABLE: u8 = 0x10;
+
+// Mapper 1, SxROM boards. Registers are loaded a bit at a time through a 5 bit
+// shift register, the fifth write to $8000-$FFFF copies it into the register
+// picked by address bits 13-14: control, CHR bank 0, CHR bank 1 and PRG bank.
+// Boards with CHR RAM reuse the CHR bank lines, SUROM and SXROM take PRG A18 from
+// bit 4 and SOROM and SXROM bank PRG RAM with bits 2-3
+pub struct Mmc1 {
+    cartridge: Cartridge,
+    shift: u8,
+    // Writes shifted in so far
+    shift_count: u8,
+    control: u8,
+    chr_bank0: u8,
+    chr_bank1: u8,
+    prg_bank: u8,
+    // PPU A12 as of the last pattern table access. In 4 KiB CHR mode it picks the
+    // CHR register that drives the lines SUROM, SOROM and SXROM borrow
+    ppu_a12: bool,
+    cycle: u64,
+    // The cycle of the last write to $8000-$FFFF. The chip ignores a write on the
+    // very next cycle, so the second write of a read-modify-write is lost
+    last_write: Option<u64>,
+}
+
+impl Mmc1 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        return Mmc1 {
+            cartridge,
+            shift: 0,
+            shift_count: 0,
+            // Powers up with the last bank fixed at $C000 so the reset vector is there
+            control: CONTROL_PRG_MODE,
+            chr_bank0: 0,
+            chr_bank1: 0,
+            prg_bank: 0,
+            ppu_a12: false,
+            cycle: 0,
+            last_write: None,
+        };
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr & 0x6000 {
+            0x0000 => self.control = val,
+            0x2000 => self.chr_bank0 = val,
+            0x4000 => self.chr_bank1 = val,
+            _ => self.prg_bank = val,
+        }
+    }
+
+    // The CHR register on the CHR address lines right now, CHR bank 1 only drives
+    // them in 4 KiB mode while the PPU is in $1000-$1FFF
+    fn chr_lines(&self) -> u8 {
+        if self.control & CONTROL_CHR_4K != 0 && self.ppu_a12 {
+            return self.chr_bank1;
+        }
+        return self.chr_bank0;
+    }
+
+    // 256 KiB half of PRG ROM selected by CHR bit 4 on SUROM and SXROM
+    fn prg_outer(&self) -> usize {
+        if self.cartridge.prg_rom.len() < 2 * PRG_OUTER_SIZE {
+            return 0;
+        }
+        return ((self.chr_lines() >> 4) & 0x01) as usize * PRG_OUTER_SIZE;
+    }
+
+    // 16 KiB bank number at $8000 or $C000
+    fn prg_bank_at(&self, addr: u16) -> usize {
+        let bank = (self.prg_bank & 0x0F) as usize;
+        let upper = addr >= 0xC000;
+        return match (self.control & CONTROL_PRG_MODE) >> 2 {
+            // 32 KiB at a time, the low bit is ignored
+            0 | 1 => (bank & !0x01) | upper as usize,
+            // First bank fixed at $8000
+            2 => if upper { bank } else { 0 },
+            // Last bank fixed at $C000
+            _ => if upper { 0x0F } else { bank },
+        };
+    }
+
+    // 8 KiB PRG RAM bank from the CHR lines, SOROM has two banks and SXROM four
+    fn prg_ram_offset(&self, addr: u16) -> Option<usize> {
+        let len = self.cartridge.prg_ram.len();
+        if len == 0 || self.prg_bank & PRG_RAM_DISABLE != 0 {
+            return None;
+        }
+        let bank = match len / PRG_RAM_BANK_SIZE {
+            2 => (self.chr_lines() >> 3) & 0x01,
+            4 => (self.chr_lines() >> 2) & 0x03,
+            _ => 0,
+        } as usize;
+        return Some((bank * PRG_RAM_BANK_SIZE + (addr - PRG_RAM_START) as usize) % len);
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let offset = addr as usize & (CHR_BANK_SIZE_4K - 1);
+        let bank = if self.control & CONTROL_CHR_4K == 0 {
+            // 8 KiB at a time, the low bit is ignored
+            (self.chr_bank0 & !0x01) as usize | (addr as usize >> 12)
+        } else if addr < 0x1000 {
+            self.chr_bank0 as usize
+        } else {
+            self.chr_bank1 as usize
+        };
+        return bank * CHR_BANK_SIZE_4K + offset;
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn cpu_read(&self, addr: u16) -> Option<u8> {
+        return match addr {
+            PRG_ROM_START..=0xFFFF => {
+                let offset = self.prg_bank_at(addr) * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1));
+                Some(self.cartridge.read_prg_offset(self.prg_outer() + offset))
+            },
+            PRG_RAM_START..=0x7FFF => self.prg_ram_offset(addr).map(|offset| self.cartridge.prg_ram[offset]),
+            _ => None,
+        };
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if (PRG_RAM_START..PRG_ROM_START).contains(&addr) {
+            self.cpu_poke(addr, val);
+            return;
+        }
+        if addr < PRG_ROM_START {
+            return;
+        }
+        let consecutive = self.last_write == Some(self.cycle.wrapping_sub(1));
+        self.last_write = Some(self.cycle);
+        if consecutive {
+            return;
+        }
+        if val & RESET_BIT != 0 {
+            self.shift = 0;
+            self.shift_count = 0;
+            self.control |= CONTROL_PRG_MODE;
+            return;
+        }
+        // LSB first, the fifth bit lands in bit 4
+        self.shift |= (val & 0x01) << self.shift_count;
+        self.shift_count += 1;
+        if self.shift_count == 5 {
+            self.write_register(addr, self.shift);
+            self.shift = 0;
+            self.shift_count = 0;
+        }
+    }
+
+    // Only PRG RAM takes a poke, the shift register never sees it
+    fn cpu_poke(&mut self, addr: u16, val: u8) {
+        if (PRG_RAM_START..PRG_ROM_START).contains(&addr) {
+            if let Some(offset) = self.prg_ram_offset(addr) {
+                self.cartridge.prg_ram[offset] = val;
+            }
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        return self.cartridge.read_chr(self.chr_offset(addr));
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        let offset = self.chr_offset(addr);
+        self.cartridge.write_chr(offset, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return match self.control & 0x03 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        };
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.cycle += 1;
+    }
+
+    fn ppu_a12_rise(&mut self) {
+        self.ppu_a12 = true;
+    }
+
+    fn ppu_a12_fall(&mut self) {
+        self.ppu_a12 = false;
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        return battery_ram(&self.cartridge);
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        load_battery_ram(&mut self.cartridge, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::cpu::Cpu;
+    use crate::hardware::debug::DebugUtils;
+    use crate::hardware::interrupts::Interrupts;
+    use crate::hardware::mapper::tests::rom;
+    use crate::hardware::nes_bus::NesBus;
+    use crate::hardware::variant::CpuVariant;
+
+    // Every PRG byte holds its 16 KiB bank number and every CHR byte its 4 KiB
+    // bank number. `prg_ram` is in 8 KiB banks
+    fn mmc1(prg_banks: u8, chr_banks: u8, prg_ram: u8) -> Mmc1 {
+        let mut bytes = rom(1, prg_banks, chr_banks);
+        bytes[8] = prg_ram;
+        let chr_start = 16 + prg_banks as usize * PRG_BANK_SIZE;
+        for (i, byte) in bytes[16..].iter_mut().enumerate() {
+            *byte = if i + 16 < chr_start { i / PRG_BANK_SIZE } else { (i + 16 - chr_start) / CHR_BANK_SIZE_4K } as u8;
+        }
+        return Mmc1::new(Cartridge::from_bytes(&bytes).unwrap());
+    }
+
+    // Five writes a cycle apart from each other so none are ignored
+    fn load(mapper: &mut Mmc1, addr: u16, val: u8) {
+        for bit in 0..5 {
+            mapper.cpu_cycle();
+            mapper.cpu_cycle();
+            mapper.cpu_write(addr, val >> bit);
+        }
+    }
+
+    #[test]
+    fn test_power_on() {
+        let mapper = mmc1(8, 2, 0);
+        assert_eq!(mapper.cpu_read(0x8000), Some(0));
+        assert_eq!(mapper.cpu_read(0xC000), Some(7));
+    }
+
+    #[test]
+    fn test_prg_modes() {
+        let mut mapper = mmc1(8, 2, 0);
+        load(&mut mapper, 0xE000, 5);
+        assert_eq!(mapper.cpu_read(0x8000), Some(5));
+        assert_eq!(mapper.cpu_read(0xC000), Some(7));
+
+        load(&mut mapper, 0x8000, 0x08);
+        assert_eq!(mapper.cpu_read(0x8000), Some(0));
+        assert_eq!(mapper.cpu_read(0xFFFF), Some(5));
+
+        load(&mut mapper, 0x8000, 0x00);
+        assert_eq!(mapper.cpu_read(0x8000), Some(4));
+        assert_eq!(mapper.cpu_read(0xC000), Some(5));
+    }
+
+    #[test]
+    fn test_chr_modes() {
+        let mut mapper = mmc1(2, 4, 0);
+        load(&mut mapper, 0xA000, 3);
+        load(&mut mapper, 0xC000, 1);
+        assert_eq!(mapper.ppu_read(0x0000), 2);
+        assert_eq!(mapper.ppu_read(0x1000), 3);
+
+        load(&mut mapper, 0x8000, CONTROL_CHR_4K | 0x0C);
+        assert_eq!(mapper.ppu_read(0x0000), 3);
+        assert_eq!(mapper.ppu_read(0x1000), 1);
+    }
+
+    #[test]
+    fn test_mirroring() {
+        let mut mapper = mmc1(2, 2, 0);
+        for (control, mirroring) in [
+            (0, Mirroring::SingleScreenLower),
+            (1, Mirroring::SingleScreenUpper),
+            (2, Mirroring::Vertical),
+            (3, Mirroring::Horizontal),
+        ] {
+            load(&mut mapper, 0x9FFF, control);
+            assert_eq!(mapper.mirroring(), mirroring);
+        }
+    }
+
+    #[test]
+    fn test_reset_write() {
+        let mut mapper = mmc1(8, 2, 0);
+        load(&mut mapper, 0x8000, 0x00);
+        mapper.cpu_cycle();
+        mapper.cpu_write(0xE000, 1);
+        mapper.cpu_cycle();
+        mapper.cpu_cycle();
+        mapper.cpu_write(0x8000, RESET_BIT);
+        // The partial load is gone and the last bank is fixed at $C000 again
+        load(&mut mapper, 0xE000, 2);
+        assert_eq!(mapper.cpu_read(0x8000), Some(2));
+        assert_eq!(mapper.cpu_read(0xC000), Some(7));
+    }
+
+    #[test]
+    fn test_consecutive_writes_ignored() {
+        let mut mapper = mmc1(8, 2, 0);
+        // The dummy write of a read-modify-write resets, the real one is ignored
+        mapper.cpu_cycle();
+        mapper.cpu_write(0xE000, RESET_BIT);
+        mapper.cpu_cycle();
+        mapper.cpu_write(0xE000, 0x01);
+        load(&mut mapper, 0xE000, 3);
+        assert_eq!(mapper.cpu_read(0x8000), Some(3));
+    }
+
+    #[test]
+    fn test_prg_ram() {
+        let mut mapper = mmc1(2, 2, 1);
+        mapper.cpu_write(0x6000, 0x42);
+        assert_eq!(mapper.cpu_read(0x6000), Some(0x42));
+        load(&mut mapper, 0xE000, PRG_RAM_DISABLE);
+        assert_eq!(mapper.cpu_read(0x6000), None);
+        mapper.cpu_write(0x6000, 0x24);
+        load(&mut mapper, 0xE000, 0);
+        assert_eq!(mapper.cpu_read(0x6000), Some(0x42));
+    }
+
+    #[test]
+    fn test_surom_outer_bank() {
+        let mut mapper = mmc1(32, 0, 1);
+        assert_eq!(mapper.cpu_read(0xC000), Some(15));
+        load(&mut mapper, 0xA000, 0x10);
+        assert_eq!(mapper.cpu_read(0x8000), Some(16));
+        assert_eq!(mapper.cpu_read(0xC000), Some(31));
+    }
+
+    #[test]
+    fn test_sorom_ram_banks() {
+        let mut mapper = mmc1(16, 0, 2);
+        mapper.cpu_write(0x6000, 0x11);
+        load(&mut mapper, 0xA000, 0x08);
+        assert_eq!(mapper.cpu_read(0x6000), Some(0x00));
+        mapper.cpu_write(0x6000, 0x22);
+        load(&mut mapper, 0xA000, 0x00);
+        assert_eq!(mapper.cpu_read(0x6000), Some(0x11));
+    }
+
+    #[test]
+    fn test_sxrom_ram_banks() {
+        let mut mapper = mmc1(32, 0, 4);
+        for bank in 0..4 {
+            load(&mut mapper, 0xA000, bank << 2);
+            mapper.cpu_write(0x6000, bank);
+        }
+        load(&mut mapper, 0xA000, 2 << 2 | 0x10);
+        assert_eq!(mapper.cpu_read(0x6000), Some(2));
+        assert_eq!(mapper.cpu_read(0x8000), Some(16));
+    }
+
+    #[test]
+    fn test_chr_4k_outer_bank() {
+        // SUROM and SOROM in 4 KiB CHR mode, the upper CHR register picks the
+        // outer PRG bank and the PRG RAM bank while the PPU fetches from $1000
+        let mut mapper = mmc1(32, 0, 2);
+        load(&mut mapper, 0x8000, CONTROL_CHR_4K | CONTROL_PRG_MODE);
+        load(&mut mapper, 0xA000, 0x00);
+        load(&mut mapper, 0xC000, 0x18);
+        mapper.cpu_write(0x6000, 0x11);
+        mapper.ppu_a12_rise();
+        assert_eq!(mapper.cpu_read(0x8000), Some(16));
+        assert_eq!(mapper.cpu_read(0x6000), Some(0x00));
+        mapper.cpu_write(0x6000, 0x22);
+        mapper.ppu_a12_fall();
+        assert_eq!(mapper.cpu_read(0x8000), Some(0));
+        assert_eq!(mapper.cpu_read(0x6000), Some(0x11));
+    }
+
+    #[test]
+    fn test_chr_ram() {
+        // CHR bank bits past the 8 KiB of RAM don't matter
+        let mut mapper = mmc1(16, 0, 2);
+        load(&mut mapper, 0x8000, CONTROL_CHR_4K | 0x0C);
+        load(&mut mapper, 0xC000, 0x09);
+        mapper.ppu_write(0x1000, 0x42);
+        load(&mut mapper, 0xA000, 0x01);
+        assert_eq!(mapper.ppu_read(0x0000), 0x42);
+    }
+
+    #[test]
+    fn test_rmw_through_cpu() {
+        // A bit is half loaded, then INC on ROM holding $FF writes $FF (a reset)
+        // and $00 on back to back cycles. Bank 1 is only selected if the reset
+        // cleared the stray bit and the $00 was dropped
+        let program = [
+            0xA9, 0x01, 0x8D, 0x00, 0xE0, 0xEE, 0x00, 0x80, 0x8D, 0x00, 0xE0, 0xA9, 0x00,
+            0x8D, 0x00, 0xE0, 0x8D, 0x00, 0xE0, 0x8D, 0x00, 0xE0, 0x8D, 0x00, 0xE0,
+            0xAD, 0x00, 0x80, 0x8D, 0x00, 0x02,
+        ];
+        let mut bytes = rom(1, 4, 1);
+        let bank = |n: usize| 16 + n * PRG_BANK_SIZE;
+        bytes[bank(0)] = 0xFF;
+        bytes[bank(1)] = 0x42;
+        bytes[bank(3)..bank(3) + program.len()].copy_from_slice(&program);
+        bytes[bank(3) + 0x3FFC..bank(3) + 0x3FFE].copy_from_slice(&[0x00, 0xC0]);
+        let mut bus = NesBus::new();
+        bus.insert_cartridge(Cartridge::from_bytes(&bytes).unwrap()).unwrap();
+        let mut cpu = Cpu::with_bus(CpuVariant::Ricoh2A03, Box::new(bus));
+        cpu.reset();
+        for _ in 0..11 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.peek(0x0200), 0x42);
+    }
+}
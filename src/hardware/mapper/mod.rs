@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::hardware::cartridge::{Cartridge, Mirroring};
+use crate::hardware::error::{CartridgeError, CartridgeResult};
+
+mod nrom;
+mod mmc1;
+mod uxrom;
+mod cnrom;
+mod axrom;
+mod gxrom;
+mod color_dreams;
+
+pub use nrom::Nrom;
+pub use mmc1::Mmc1;
+pub use uxrom::Uxrom;
+pub use cnrom::Cnrom;
+pub use axrom::Axrom;
+pub use gxrom::Gxrom;
+pub use color_dreams::ColorDreams;
+
+pub const PRG_BANK_SIZE_32K: usize = 0x8000;
+
+// The logic on a cartridge board. The cpu bus hands it $4020-$FFFF and the PPU
+// bus hands it the pattern tables and asks it how nametables are mirrored
+pub trait Mapper {
+    // None where the board doesn't drive the data bus, the read sees open bus
+    fn cpu_read(&self, addr: u16) -> Option<u8>;
+    fn cpu_write(&mut self, addr: u16, val: u8);
+    // A store into RAM on the board for tools, never a register write
+    fn cpu_poke(&mut self, _addr: u16, _val: u8) {}
+
+    // Pattern tables, $0000-$1FFF on the PPU bus
+    fn ppu_read(&self, addr: u16) -> u8;
+    fn ppu_write(&mut self, addr: u16, val: u8);
+
+    // Nametables at $2000-$3EFF are console RAM, the board only wires up the mirroring
+    fn mirroring(&self) -> Mirroring;
+
+    // Called once per cpu cycle before the cycle's access, for boards that count
+    // cycles or care how far apart writes are
+    fn cpu_cycle(&mut self) {}
+
+    // PPU address line 12 went from low to high, scanline counters clock on this
+    fn ppu_a12_rise(&mut self) {}
+    fn ppu_a12_fall(&mut self) {}
+
+    // The board's IRQ output, wired to the cpu's IRQ line
+    fn irq(&self) -> bool {
+        return false;
+    }
+
+    // Battery backed RAM to keep between runs, None if the board has none
+    fn save_ram(&self) -> Option<&[u8]> {
+        return None;
+    }
+
+    fn load_save_ram(&mut self, _data: &[u8]) {}
+}
+
+// The cpu and PPU buses both talk to the same board
+pub type SharedMapper = Rc<RefCell<Box<dyn Mapper>>>;
+
+// Picks the board logic for the mapper number in the header
+pub fn from_cartridge(cartridge: Cartridge) -> CartridgeResult<Box<dyn Mapper>> {
+    return match cartridge.header.mapper {
+        0 => Ok(Box::new(Nrom::new(cartridge))),
+        1 => Ok(Box::new(Mmc1::new(cartridge))),
+        2 => Ok(Box::new(Uxrom::new(cartridge))),
+        3 => Ok(Box::new(Cnrom::new(cartridge))),
+        7 => Ok(Box::new(Axrom::new(cartridge))),
+        11 => Ok(Box::new(ColorDreams::new(cartridge))),
+        66 => Ok(Box::new(Gxrom::new(cartridge))),
+        mapper => Err(CartridgeError::UnsupportedMapper(mapper)),
+    };
+}
+
+// Discrete latch boards where the ROM keeps driving the data bus while the cpu
+// writes to it, so the latch gets the AND of both. NES 2.0 submapper 1 says the
+// board has no conflicts and 2 says it has them, otherwise it's the usual board
+pub fn has_bus_conflicts(cartridge: &Cartridge, default: bool) -> bool {
+    return match cartridge.header.submapper {
+        1 => false,
+        2 => true,
+        _ => default,
+    };
+}
+
+// Shared by boards with PRG RAM at $6000-$7FFF, battery backed if the header says so
+pub fn battery_ram(cartridge: &Cartridge) -> Option<&[u8]> {
+    if !cartridge.header.battery || cartridge.prg_ram.is_empty() {
+        return None;
+    }
+    return Some(&cartridge.prg_ram);
+}
+
+pub fn load_battery_ram(cartridge: &mut Cartridge, data: &[u8]) {
+    if cartridge.header.battery {
+        let len = cartridge.prg_ram.len().min(data.len());
+        cartridge.prg_ram[..len].copy_from_slice(&data[..len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::cartridge::{CHR_BANK_SIZE, PRG_BANK_SIZE};
+    use crate::hardware::nes_bus::NesBus;
+
+    pub fn rom(mapper: u8, prg_banks: u8, chr_banks: u8) -> Vec<u8> {
+        let mut bytes = vec![b'N', b'E', b'S', 0x1A, prg_banks, chr_banks, mapper << 4, mapper & 0xF0];
+        bytes.resize(16, 0);
+        bytes.resize(16 + prg_banks as usize * 0x4000 + chr_banks as usize * 0x2000, 0);
+        return bytes;
+    }
+
+    // NES 2.0 ROM where the first byte of each 16 KiB PRG bank is its bank number and
+    // the rest are $FF, so writes past the first byte don't conflict. Every CHR byte
+    // is its 8 KiB bank number, no CHR ROM means 8 KiB of CHR RAM
+    pub fn banked_rom(mapper: u16, submapper: u8, prg_banks: u8, chr_banks: u8) -> Vec<u8> {
+        let mut bytes = rom(mapper as u8, prg_banks, chr_banks);
+        bytes[7] |= 0x08;
+        bytes[8] = submapper << 4 | (mapper >> 8) as u8;
+        bytes[11] = if chr_banks == 0 { 0x07 } else { 0 };
+        for bank in 0..prg_banks as usize {
+            let start = 16 + bank * PRG_BANK_SIZE;
+            bytes[start] = bank as u8;
+            bytes[start + 1..start + PRG_BANK_SIZE].fill(0xFF);
+        }
+        let chr_start = 16 + prg_banks as usize * PRG_BANK_SIZE;
+        for bank in 0..chr_banks as usize {
+            let start = chr_start + bank * CHR_BANK_SIZE;
+            bytes[start..start + CHR_BANK_SIZE].fill(bank as u8);
+        }
+        return bytes;
+    }
+
+    pub fn nes_bus(bytes: Vec<u8>) -> NesBus {
+        let mut bus = NesBus::new();
+        bus.insert_cartridge(Cartridge::from_bytes(&bytes).unwrap()).unwrap();
+        return bus;
+    }
+
+    #[test]
+    fn test_bus_conflict_submappers() {
+        let cart = Cartridge::from_bytes(&banked_rom(2, 0, 1, 0)).unwrap();
+        assert!(has_bus_conflicts(&cart, true));
+        assert!(!has_bus_conflicts(&cart, false));
+        let cart = Cartridge::from_bytes(&banked_rom(2, 1, 1, 0)).unwrap();
+        assert!(!has_bus_conflicts(&cart, true));
+        let cart = Cartridge::from_bytes(&banked_rom(7, 2, 2, 0)).unwrap();
+        assert!(has_bus_conflicts(&cart, false));
+    }
+
+    #[test]
+    fn test_from_cartridge() {
+        let cart = Cartridge::from_bytes(&rom(0, 1, 1)).unwrap();
+        assert!(from_cartridge(cart).is_ok());
+        let cart = Cartridge::from_bytes(&rom(0xFF, 1, 1)).unwrap();
+        assert_eq!(from_cartridge(cart).err(), Some(CartridgeError::UnsupportedMapper(0xFF)));
+    }
+
+    #[test]
+    fn test_battery_ram() {
+        let mut bytes = rom(0, 1, 1);
+        let mut cart = Cartridge::from_bytes(&bytes).unwrap();
+        load_battery_ram(&mut cart, &[1, 2, 3]);
+        assert_eq!(battery_ram(&cart), None);
+        assert_eq!(cart.prg_ram[0], 0);
+
+        bytes[6] |= 0x02;
+        let mut cart = Cartridge::from_bytes(&bytes).unwrap();
+        load_battery_ram(&mut cart, &[1, 2, 3]);
+        assert_eq!(&battery_ram(&cart).unwrap()[..4], &[1, 2, 3, 0]);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::hardware::cartridge::{Cartridge, Mirroring, PRG_RAM_START, PRG_ROM_START};
+use crate::hardware::mapper::{battery_ram, load_battery_ram, Mapper};
+
+// Mapper 0, no banking. NROM-128 has one 16 KiB PRG bank that shows up at both
+// $8000 and $C000, NROM-256 fills $8000-$FFFF. Mirroring is soldered on the board
+pub struct Nrom {
+    cartridge: Cartridge,
+}
+
+impl Nrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        return Nrom { cartridge };
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_read(&self, addr: u16) -> Option<u8> {
+        return match addr {
+            PRG_ROM_START..=0xFFFF => Some(self.cartridge.read_prg(addr)),
+            // Only Family BASIC carts have RAM here
+            PRG_RAM_START..=0x7FFF => self.cartridge.read_prg_ram(addr),
+            _ => None,
+        };
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if (PRG_RAM_START..PRG_ROM_START).contains(&addr) {
+            self.cartridge.write_prg_ram(addr, val);
+        }
+    }
+
+    // Nothing on the board reacts to a write
+    fn cpu_poke(&mut self, addr: u16, val: u8) {
+        self.cpu_write(addr, val);
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        return self.cartridge.read_chr(addr as usize);
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.cartridge.write_chr(addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.cartridge.header.mirroring;
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        return battery_ram(&self.cartridge);
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        load_battery_ram(&mut self.cartridge, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::mapper::tests::rom;
+
+    // PRG filled with the bank number in the low bit and the low address byte
+    // above it, CHR with its low address byte
+    fn nrom(prg_banks: u8, chr_banks: u8, flags6: u8) -> Nrom {
+        let mut bytes = rom(0, prg_banks, chr_banks);
+        bytes[6] = flags6;
+        for (i, byte) in bytes[16..].iter_mut().enumerate() {
+            *byte = if i < prg_banks as usize * 0x4000 { (i / 0x4000) as u8 | (i as u8) << 1 } else { i as u8 };
+        }
+        return Nrom::new(Cartridge::from_bytes(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_nrom_128() {
+        let mapper = nrom(1, 1, 0);
+        assert_eq!(mapper.cpu_read(0x8001), Some(0x02));
+        assert_eq!(mapper.cpu_read(0xC001), Some(0x02));
+        assert_eq!(mapper.cpu_read(0x5000), None);
+    }
+
+    #[test]
+    fn test_nrom_256() {
+        let mapper = nrom(2, 1, 0);
+        assert_eq!(mapper.cpu_read(0x8001), Some(0x02));
+        assert_eq!(mapper.cpu_read(0xC001), Some(0x03));
+    }
+
+    #[test]
+    fn test_rom_not_writable() {
+        let mut mapper = nrom(1, 1, 0);
+        mapper.cpu_write(0x8001, 0xFF);
+        mapper.ppu_write(0x0010, 0xFF);
+        assert_eq!(mapper.cpu_read(0x8001), Some(0x02));
+        assert_eq!(mapper.ppu_read(0x0010), 0x10);
+    }
+
+    #[test]
+    fn test_chr_ram() {
+        let mut mapper = nrom(1, 0, 0);
+        mapper.ppu_write(0x1FFF, 0x42);
+        assert_eq!(mapper.ppu_read(0x1FFF), 0x42);
+    }
+
+    #[test]
+    fn test_prg_ram() {
+        let mut mapper = nrom(1, 1, 0x02);
+        mapper.cpu_write(0x6000, 0x42);
+        assert_eq!(mapper.cpu_read(0x6000), Some(0x42));
+        assert_eq!(mapper.save_ram().unwrap()[0], 0x42);
+    }
+
+    #[test]
+    fn test_mirroring() {
+        assert_eq!(nrom(1, 1, 0).mirroring(), Mirroring::Horizontal);
+        assert_eq!(nrom(1, 1, 1).mirroring(), Mirroring::Vertical);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::hardware::cartridge::{Cartridge, Mirroring, PRG_BANK_SIZE, PRG_ROM_START};
+use crate::hardware::mapper::{has_bus_conflicts, Mapper};
+
+// Mapper 2. A latch at $8000-$FFFF picks the 16 KiB bank at $8000, the last bank
+// is fixed at $C000. UNROM uses 3 bits of it and UOROM 4, banks past the end of
+// the ROM wrap. Most boards have bus conflicts
+pub struct Uxrom {
+    cartridge: Cartridge,
+    bank: u8,
+    bus_conflicts: bool,
+}
+
+impl Uxrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let bus_conflicts = has_bus_conflicts(&cartridge, true);
+        return Uxrom { cartridge, bank: 0, bus_conflicts };
+    }
+}
+
+impl Mapper for Uxrom {
+    fn cpu_read(&self, addr: u16) -> Option<u8> {
+        if addr < PRG_ROM_START {
+            return None;
+        }
+        let bank = if addr >= 0xC000 {
+            self.cartridge.prg_rom.len() / PRG_BANK_SIZE - 1
+        } else {
+            self.bank as usize
+        };
+        return Some(self.cartridge.read_prg_offset(bank * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1))));
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if let Some(rom) = self.cpu_read(addr) {
+            self.bank = if self.bus_conflicts { val & rom } else { val };
+        }
+    }
+
+    fn ppu_read(&self, addr: u16) -> u8 {
+        return self.cartridge.read_chr(addr as usize);
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.cartridge.write_chr(addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.cartridge.header.mirroring;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::bus::Bus;
+    use crate::hardware::mapper::tests::{banked_rom, nes_bus};
+
+    #[test]
+    fn test_bank_switch() {
+        let mut bus = nes_bus(banked_rom(2, 0, 8, 0));
+        assert_eq!((bus.read(0x8000), bus.read(0xC000)), (0, 7));
+        bus.write(0x8001, 5);
+        assert_eq!((bus.read(0x8000), bus.read(0xC000)), (5, 7));
+        // UOROM's fourth bit wraps on a 128 KiB UNROM
+        bus.write(0xC001, 0x0B);
+        assert_eq!(bus.read(0x8000), 3);
+    }
+
+    #[test]
+    fn test_bus_conflicts() {
+        // $8000 of bank 4 holds 4, so writing 3 there gives 4 & 3
+        let mut bus = nes_bus(banked_rom(2, 0, 8, 0));
+        bus.write(0x8001, 4);
+        bus.write(0x8000, 3);
+        assert_eq!(bus.read(0x8000), 0);
+    }
+
+    #[test]
+    fn test_no_bus_conflicts_submapper() {
+        let mut bus = nes_bus(banked_rom(2, 1, 8, 0));
+        bus.write(0x8001, 4);
+        bus.write(0x8000, 3);
+        assert_eq!(bus.read(0x8000), 3);
+    }
+
+    #[test]
+    fn test_chr_ram() {
+        let mut bus = nes_bus(banked_rom(2, 0, 8, 0));
+        let ppu_bus = bus.ppu_bus().unwrap();
+        ppu_bus.write(0x1234, 0x42);
+        assert_eq!(ppu_bus.read(0x1234), 0x42);
+    }
+}
@@ -1,121 +1,153 @@
 use crate::hardware::cpu::Cpu;
-use crate::hardware::debug::DebugUtils;
-use crate::utils::{combine_bytes, split_bytes};
+use crate::hardware::timing::crosses_page;
+use crate::utils::combine_bytes;
 
-pub const MEM_SIZE: usize = 0xFFFF + 1;
 // pub const ZERO_PAGE_BOUND: usize = 0xFF;
 // pub const INTERNAL_BOUND: u16 = 0x07FF;
 
 pub trait MemoryOps {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, val: u8);
+    fn read_word(&mut self, lower_addr: u16, upper_addr: u16) -> u16;
     fn fetch_next_byte(&mut self) -> u8;
     fn fetch_two_bytes(&mut self) -> (u8, u8);
-    fn fetch_zp(&mut self) -> usize;
-    fn fetch_zpx(&mut self) -> usize;
-    fn fetch_abs(&mut self) -> usize;
-    fn fetch_absx(&mut self) -> usize;
-    fn fetch_absy(&mut self) -> usize;
-    fn fetch_indirectx(&mut self) -> usize;
-    fn fetch_indirecty(&mut self) -> usize;
-    fn save_pc(&mut self, dec: bool);
-    fn save_status(&mut self);
-    fn pull_pc(&mut self);
-    fn fetch_indirect(&mut self) -> usize;
-    fn push_stack(&mut self, item: u8);
-    fn pop_stack(&mut self) -> u8;
-    fn peek_stack(&self) -> u8;
+    fn fetch_zp(&mut self) -> u16;
+    fn fetch_zpx(&mut self) -> u16;
+    fn fetch_zpy(&mut self) -> u16;
+    fn fetch_abs(&mut self) -> u16;
+    fn fetch_absx(&mut self, always_fix: bool) -> u16;
+    fn fetch_absy(&mut self, always_fix: bool) -> u16;
+    fn fetch_indirectx(&mut self) -> u16;
+    fn fetch_indirecty(&mut self, always_fix: bool) -> u16;
+    fn index_address(&mut self, base: u16, index: u8, always_fix: bool) -> u16;
+    fn fetch_indirect(&mut self) -> u16;
+    fn read_zp_pointer(&mut self, zp_addr: u8) -> u16;
+    fn fetch_zp_indirect(&mut self) -> u16;
+    fn fetch_abs_indirectx(&mut self) -> u16;
 }
 
 impl MemoryOps for Cpu {
 
-    fn peek_stack(&self) -> u8 {
-        return self.memory[(self.sp + 1) as usize];
+    // Every access the cpu makes goes through these two
+    fn read(&mut self, addr: u16) -> u8 {
+        return self.bus.read(addr);
     }
 
-    fn push_stack(&mut self, item: u8) {
-        self.memory[self.sp as usize] = item;
-        self.sp -= 1;
+    fn write(&mut self, addr: u16, val: u8) {
+        self.bus.write(addr, val);
     }
-    fn pop_stack(&mut self) -> u8 {
-        let val = self.peek_stack();
-        self.sp += 1;
-        return val;
+
+    // Little endian pointer, the two halves can come from anywhere to model the page wrap bugs
+    fn read_word(&mut self, lower_addr: u16, upper_addr: u16) -> u16 {
+        let lower = self.read(lower_addr);
+        let upper = self.read(upper_addr);
+        return combine_bytes(upper.into(), lower.into());
     }
 
     fn fetch_next_byte(&mut self) -> u8 {
-        self.pc += 1;
-        return self.memory[self.pc as usize];
+        self.pc = self.pc.wrapping_add(1);
+        return self.read(self.pc);
     }
 
+    // Operands are little endian, returns (lower, upper)
     fn fetch_two_bytes(&mut self) -> (u8, u8) {
-        let (p1, p2) = (self.pc + 1, self.pc + 2);
-        self.pc += 2;
-        return (self.memory[p1 as usize], self.memory[p2 as usize])
+        let lower = self.fetch_next_byte();
+        let upper = self.fetch_next_byte();
+        return (lower, upper);
     }
 
-    fn fetch_zp(&mut self) -> usize {
+    fn fetch_zp(&mut self) -> u16 {
         return self.fetch_next_byte().into();
     }
 
-    fn fetch_zpx(&mut self) -> usize {
-        return (self.fetch_next_byte().wrapping_add(self.regx)).into();
+    // The cpu reads the unindexed zero page address while it adds the index
+    fn fetch_zpx(&mut self) -> u16 {
+        let zp_addr = self.fetch_next_byte();
+        self.read(zp_addr.into());
+        return (zp_addr.wrapping_add(self.regx)).into();
     }
 
-    fn fetch_abs(&mut self) -> usize {
-        let (upper, lower) = self.fetch_two_bytes();
-        println!("{} : {}", upper, lower);
-        return combine_bytes(upper.into(), lower.into()).into();
+    fn fetch_zpy(&mut self) -> u16 {
+        let zp_addr = self.fetch_next_byte();
+        self.read(zp_addr.into());
+        return (zp_addr.wrapping_add(self.regy)).into();
     }
 
-    fn fetch_absx(&mut self) -> usize {
-        let (upper, lower) = self.fetch_two_bytes();
-        let addr: usize = combine_bytes(upper.into(), lower.into()).into();
-        return addr + self.regx as usize;
+    fn fetch_abs(&mut self) -> u16 {
+        let (lower, upper) = self.fetch_two_bytes();
+        return combine_bytes(upper.into(), lower.into());
     }
 
-    fn fetch_absy(&mut self) -> usize {
-        let (upper, lower) = self.fetch_two_bytes();
-        let addr: usize = combine_bytes(upper.into(), lower.into()).into();
-        return addr + self.regy as usize;
+    fn fetch_absx(&mut self, always_fix: bool) -> u16 {
+        let addr = self.fetch_abs();
+        return self.index_address(addr, self.regx, always_fix);
     }
 
-    fn fetch_indirect(&mut self) -> usize {
-        let (upper, lower) = self.fetch_two_bytes();
-        let addr: usize = combine_bytes(upper.into(), lower.into()).into();
-        let (lower_base, upper_base) = (self.memory[addr], self.memory[addr + 1]);
-        return combine_bytes(upper_base.into(), lower_base.into()).into();
+    fn fetch_absy(&mut self, always_fix: bool) -> u16 {
+        let addr = self.fetch_abs();
+        return self.index_address(addr, self.regy, always_fix);
     }
 
-    fn fetch_indirectx(&mut self) -> usize {
-        let zp_addr: usize = (self.fetch_next_byte().wrapping_add(self.regx)).into();
-        let base: usize = self.memory[zp_addr].into();
-        let (upper, lower) = (self.memory[base+1], self.memory[base]);
-        return combine_bytes(upper.into(), lower.into()).into();
+    // The index is added to the low byte first and the cpu reads from that address
+    // while it fixes up the high byte. Reads skip it when there's no carry to fix,
+    // writes and read-modify-writes do it regardless unless the opcode has a page
+    // penalty (the 65C02 shifts). The 65C02 reads the last operand byte again
+    // instead of the half-formed address
+    fn index_address(&mut self, base: u16, index: u8, always_fix: bool) -> u16 {
+        let indexed = base.wrapping_add(index.into());
+        self.page_crossed = crosses_page(base, indexed);
+        if self.page_crossed || always_fix {
+            let dummy = if self.variant.is_cmos() { self.pc } else { (base & 0xFF00) | (indexed & 0x00FF) };
+            self.read(dummy);
+        }
+        return indexed;
     }
 
-    fn fetch_indirecty(&mut self) -> usize {
-        let zp_addr: usize = (self.fetch_next_byte().wrapping_add(self.regy)).into();
-        let base: usize = self.memory[zp_addr].into();
-        let (upper, lower) = (self.memory[base+1], self.memory[base]);
-        return combine_bytes(upper.into(), lower.into()).into();
+    // NMOS bug: the pointer's high byte is fetched without carrying into the next page,
+    // so JMP ($10FF) reads $10FF and $1000. The 65C02 fixed it with an extra cycle
+    // that reads the last operand byte again
+    fn fetch_indirect(&mut self) -> u16 {
+        let addr = self.fetch_abs();
+        let upper_addr = if self.variant.is_cmos() {
+            self.read(self.pc);
+            addr.wrapping_add(1)
+        } else {
+            (addr & 0xFF00) | (addr.wrapping_add(1) & 0x00FF)
+        };
+        return self.read_word(addr, upper_addr);
     }
 
-    fn save_pc(&mut self, dec: bool) {
-        let mut val = self.pc;
-        if dec {
-            val -= 1;
-        }
-        let (upper, lower) = split_bytes(val);
-        self.push_stack(lower);
-        self.push_stack(upper);
+    // Pointers stored in zero page wrap around to $00 instead of reading $0100
+    fn read_zp_pointer(&mut self, zp_addr: u8) -> u16 {
+        return self.read_word(zp_addr.into(), zp_addr.wrapping_add(1).into());
+    }
+
+    // (zp,X): X is added to the zero page address before the pointer is read
+    fn fetch_indirectx(&mut self) -> u16 {
+        let zp_addr = self.fetch_next_byte();
+        self.read(zp_addr.into());
+        return self.read_zp_pointer(zp_addr.wrapping_add(self.regx));
     }
 
-    fn save_status(&mut self) {
-        self.push_stack(self.flags.to_u8());
+    // 65C02 (zp): the (zp),Y pointer without the index
+    fn fetch_zp_indirect(&mut self) -> u16 {
+        let zp_addr = self.fetch_next_byte();
+        return self.read_zp_pointer(zp_addr);
     }
 
-    fn pull_pc(&mut self) {
-        let (upper, lower) = (self.pop_stack(), self.pop_stack());
-        self.pc = combine_bytes(upper.into(), lower.into());
+    // 65C02 JMP (abs,X): X is added to the pointer address, which may cross pages freely.
+    // Adding it costs a cycle that reads the last operand byte again
+    fn fetch_abs_indirectx(&mut self) -> u16 {
+        let addr = self.fetch_abs().wrapping_add(self.regx.into());
+        self.read(self.pc);
+        return self.read_word(addr, addr.wrapping_add(1));
+    }
+
+    // (zp),Y: Y is added to the pointer read from zero page
+    fn fetch_indirecty(&mut self, always_fix: bool) -> u16 {
+        let zp_addr = self.fetch_next_byte();
+        let base = self.read_zp_pointer(zp_addr);
+        return self.index_address(base, self.regy, always_fix);
     }
 
 }
@@ -124,76 +156,88 @@ impl MemoryOps for Cpu {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hardware::debug::DebugUtils;
 
     #[test]
-    fn test_pull_pc() {
+    fn test_fetch_indirect() {
         let mut cpu = Cpu::new();
-        cpu.pc = 0x4567;
-        cpu.save_pc(false);
-        cpu.pc = 0x3479;
-        cpu.pull_pc();
-        assert_eq!(cpu.pc, 0x4567);
+        cpu.poke(0x0120, 0xFC);
+        cpu.poke(0x0121, 0xBA);
+        cpu.poke(1, 0x20);
+        cpu.poke(2, 0x01);
+        assert_eq!(cpu.fetch_indirect(), 0xBAFC);
     }
 
     #[test]
-    fn test_fetch_indirect() {
+    fn test_fetch_indirect_page_wrap() {
         let mut cpu = Cpu::new();
-        cpu.memory[0x0120] = 0xFC;
-        cpu.memory[0x0121] = 0xBA;
-        cpu.memory[1] = 0x01;
-        cpu.memory[2] = 0x20;
+        cpu.poke(0x10FF, 0xFC);
+        cpu.poke(0x1000, 0xBA);
+        cpu.poke(0x1100, 0x12);
+        cpu.poke(1, 0xFF);
+        cpu.poke(2, 0x10);
         assert_eq!(cpu.fetch_indirect(), 0xBAFC);
     }
 
     #[test]
-    fn test_save_pc() {
+    fn test_fetch_indirecty() {
         let mut cpu = Cpu::new();
-        cpu.pc = 0x3456;
-        cpu.save_pc(false);
-        assert_eq!(cpu.peek_stack(), 0x34);
-        assert_eq!(cpu.memory[(cpu.sp + 2) as usize], 0x56);
+        cpu.poke(0x14, 0x45);
+        cpu.poke(0x15, 0x34);
+        cpu.poke(1, 0x14);
+        cpu.regy = 10;
+        assert_eq!(cpu.fetch_indirecty(false), 0x3445 + 10);
+        assert!(!cpu.page_crossed);
     }
 
     #[test]
-    fn test_fetch_indirecty() {
+    fn test_fetch_indirecty_page_cross() {
         let mut cpu = Cpu::new();
-        cpu.memory[0x14] = 0x24;
-        cpu.memory[0x24] = 0x45;
-        cpu.memory[0x25] = 0x34;
-        cpu.memory[1] = 0x14 - 10;
-        cpu.regy = 10;
-        assert_eq!(cpu.fetch_indirecty(), 0x3445);
+        cpu.poke(0x14, 0xF0);
+        cpu.poke(0x15, 0x34);
+        cpu.poke(1, 0x14);
+        cpu.regy = 0x20;
+        assert_eq!(cpu.fetch_indirecty(false), 0x3510);
+        assert!(cpu.page_crossed);
     }
 
     #[test]
     fn test_fetch_indirecty_wrap() {
         let mut cpu = Cpu::new();
-        cpu.memory[0x14] = 0x24;
-        cpu.memory[0x24] = 0x45;
-        cpu.memory[0x25] = 0x34;
-        cpu.memory[1] = 0x15;
-        cpu.regy = 0xFF;
-        assert_eq!(cpu.fetch_indirecty(), 0x3445);
+        cpu.poke(0xFF, 0x45);
+        cpu.poke(0x00, 0x34);
+        cpu.poke(0x100, 0x12);
+        cpu.poke(1, 0xFF);
+        assert_eq!(cpu.fetch_indirecty(false), 0x3445);
     }
 
     #[test]
     fn test_fetch_indirectx_wrap() {
         let mut cpu = Cpu::new();
-        cpu.memory[0x14] = 0x24;
-        cpu.memory[0x24] = 0x45;
-        cpu.memory[0x25] = 0x34;
-        cpu.memory[1] = 0x15;
+        cpu.poke(0x14, 0x45);
+        cpu.poke(0x15, 0x34);
+        cpu.poke(1, 0x15);
         cpu.regx = 0xFF;
         assert_eq!(cpu.fetch_indirectx(), 0x3445);
     }
 
+    #[test]
+    fn test_fetch_indirectx_pointer_wrap() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0xFF, 0x45);
+        cpu.poke(0x00, 0x34);
+        cpu.poke(0x100, 0x12);
+        cpu.poke(1, 0xFE);
+        cpu.regx = 1;
+        assert_eq!(cpu.fetch_indirectx(), 0x3445);
+    }
+
     #[test]
     fn test_fetch_indirectx() {
         let mut cpu = Cpu::new();
-        cpu.memory[0x14] = 0x24;
-        cpu.memory[0x24] = 0x45;
-        cpu.memory[0x25] = 0x34;
-        cpu.memory[1] = 0x14 - 10;
+        cpu.poke(0x14, 0x45);
+        cpu.poke(0x15, 0x34);
+        cpu.poke(1, 0x14 - 10);
         cpu.regx = 10;
         assert_eq!(cpu.fetch_indirectx(), 0x3445);
     }
@@ -201,63 +245,88 @@ mod tests {
     #[test]
     fn test_fetch_absy() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 0x11;
-        cpu.memory[2] = 0x11;
+        cpu.poke(1, 0x11);
+        cpu.poke(2, 0x11);
         cpu.regy = 10;
-        assert_eq!(cpu.fetch_absy(), 0x1111 + 10);
+        assert_eq!(cpu.fetch_absy(false), 0x1111 + 10);
     }
 
     #[test]
     fn test_fetch_absx() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 0x11;
-        cpu.memory[2] = 0x11;
+        cpu.poke(1, 0x11);
+        cpu.poke(2, 0x11);
         cpu.regx = 10;
-        assert_eq!(cpu.fetch_absx(), 0x1111 + 10);
+        assert_eq!(cpu.fetch_absx(false), 0x1111 + 10);
     }
 
     #[test]
     fn test_fetch_two_bytes() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 10;
-        cpu.memory[2] = 20;
+        cpu.poke(1, 10);
+        cpu.poke(2, 20);
         assert_eq!(cpu.fetch_two_bytes(), (10, 20));
     }
 
     #[test]
     fn test_fetch_abs() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 0x11;
-        cpu.memory[2] = 0x11;
+        cpu.poke(1, 0x11);
+        cpu.poke(2, 0x11);
         assert_eq!(cpu.fetch_abs(), 0x1111);
     }
 
+    #[test]
+    fn test_fetch_abs_little_endian() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x34);
+        cpu.poke(2, 0x12);
+        assert_eq!(cpu.fetch_abs(), 0x1234);
+    }
+
+    #[test]
+    fn test_fetch_absx_wrap() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0xFF);
+        cpu.poke(2, 0xFF);
+        cpu.regx = 2;
+        assert_eq!(cpu.fetch_absx(false), 0x0001);
+    }
+
     #[test]
     fn test_fetch_byte() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 23;
+        cpu.poke(1, 23);
         assert_eq!(cpu.fetch_next_byte(), 23u8);
     }
 
     #[test]
     fn test_fetch_zp() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 10;
+        cpu.poke(1, 10);
         assert_eq!(cpu.fetch_zp(), 10);
     }
 
     #[test]
     fn test_fetch_zpx() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 8;
+        cpu.poke(1, 8);
         cpu.regx = 2;
         assert_eq!(cpu.fetch_zpx(), 10);
     }
 
+    #[test]
+    fn test_fetch_zpy_wrap() {
+        let mut cpu = Cpu::new();
+        cpu.poke(1, 0x80);
+        cpu.regy = 0xFF;
+        assert_eq!(cpu.fetch_zpy(), 0x7F);
+    }
+
     #[test]
     fn test_fetch_zpx_wrap() {
         let mut cpu = Cpu::new();
-        cpu.memory[1] = 0x80;
+        cpu.poke(1, 0x80);
         cpu.regx = 0xFF;
         assert_eq!(cpu.fetch_zpx(), 0x7F);
     }
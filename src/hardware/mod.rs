@@ -1,6 +1,22 @@
-mod cpu;
+pub mod cpu;
 mod instruction;
 mod registers;
 mod memory;
 mod timing;
-mod debug;
\ No newline at end of file
+pub mod debug;
+pub mod interrupts;
+mod error;
+mod stack;
+mod alu;
+pub mod variant;
+mod bus;
+pub mod nes_bus;
+mod cycle;
+mod dma;
+mod ppu_bus;
+mod mapper;
+pub mod cartridge;
+
+// Opcode names and run options for code driving the cpu
+pub use instruction::{CmosOps, JamBehavior, Ops};
+pub use cycle::ExecMode;
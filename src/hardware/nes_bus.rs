@@ -0,0 +1,557 @@
+use crate::hardware::bus::Bus;
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::hardware::cartridge::{Cartridge, TvSystem};
+use crate::hardware::error::CartridgeResult;
+use crate::hardware::mapper::{self, SharedMapper};
+use crate::hardware::ppu_bus::PpuBus;
+use crate::hardware::dma::{Dma, DmaCycle, OAM_DATA, OAM_DMA};
+
+pub const RAM_SIZE: usize = 0x0800;
+pub const PPU_REG_COUNT: usize = 8;
+pub const IO_REG_COUNT: usize = 0x18;
+// $4020-$FFFF
+pub const CART_START: u16 = 0x4020;
+pub const CART_SPACE_SIZE: usize = 0x10000 - CART_START as usize;
+pub const OAM_SIZE: usize = 0x100;
+
+const APU_STATUS: u16 = 0x15;
+const JOY1: u16 = 0x16;
+const JOY2: u16 = 0x17;
+const OAM_ADDR_REG: u16 = 3;
+const OAM_DATA_REG: u16 = 4;
+// $4015 doesn't drive bit 5, the controller ports only drive bits 0-4
+const APU_STATUS_OPEN_BITS: u8 = 0x20;
+const JOY_OPEN_BITS: u8 = 0xE0;
+
+// Where a cpu address lands on the NES, with the offset inside that region
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Region {
+    // $0000-$1FFF, 2 KiB mirrored four times
+    Ram(u16),
+    // $2000-$3FFF, eight registers mirrored every 8 bytes
+    Ppu(u16),
+    // $4000-$4017
+    Io(u16),
+    // $4018-$401F, only used by the CPU test mode
+    Test(u16),
+    // $4020-$FFFF, offset from $4020
+    Cartridge(u16),
+}
+
+pub fn decode(addr: u16) -> Region {
+    return match addr {
+        0x0000..=0x1FFF => Region::Ram(addr & 0x07FF),
+        0x2000..=0x3FFF => Region::Ppu(addr & 0x0007),
+        0x4000..=0x4017 => Region::Io(addr - 0x4000),
+        0x4018..=0x401F => Region::Test(addr - 0x4018),
+        _ => Region::Cartridge(addr - CART_START),
+    };
+}
+
+// The NES cpu address space. The PPU and APU are plain registers for now, their
+// devices hang off the matching regions as they are added. $4020-$FFFF is plain
+// memory until a cartridge is inserted, then its mapper decodes it. OAM lives
+// here until there's a PPU so sprite DMA has somewhere to go
+pub struct NesBus {
+    ram: Vec<u8>,
+    ppu_regs: [u8; PPU_REG_COUNT],
+    oam: [u8; OAM_SIZE],
+    oam_addr: u8,
+    io_regs: [u8; IO_REG_COUNT],
+    cart: Vec<u8>,
+    // What the controller ports drive on D0-D4 when $4016/$4017 are read
+    joy_lines: [u8; 2],
+    // Last value on the data bus. Nothing drives unmapped addresses so the
+    // capacitance of the lines keeps returning it
+    open_bus: u8,
+    dma: Dma,
+    mapper: Option<SharedMapper>,
+    // Shares the mapper, here until there's a PPU to own it
+    ppu_bus: Option<PpuBus>,
+    // Set from the cartridge, NTSC until one is inserted
+    tv_system: TvSystem,
+}
+
+impl NesBus {
+    pub fn new() -> Self {
+        return NesBus {
+            ram: vec![0; RAM_SIZE],
+            ppu_regs: [0; PPU_REG_COUNT],
+            oam: [0; OAM_SIZE],
+            oam_addr: 0,
+            io_regs: [0; IO_REG_COUNT],
+            cart: vec![0; CART_SPACE_SIZE],
+            joy_lines: [0; 2],
+            open_bus: 0,
+            dma: Dma::new(),
+            mapper: None,
+            ppu_bus: None,
+            tv_system: TvSystem::Ntsc,
+        }
+    }
+
+    // The cartridge's mapper answers at $4020-$FFFF and on the PPU bus from now on
+    // and the machine runs at the speed the game was made for
+    pub fn insert_cartridge(&mut self, cartridge: Cartridge) -> CartridgeResult<()> {
+        let tv_system = cartridge.header.tv_system.machine();
+        let mapper: SharedMapper = Rc::new(RefCell::new(mapper::from_cartridge(cartridge)?));
+        self.tv_system = tv_system;
+        self.ppu_bus = Some(PpuBus::new(mapper.clone()));
+        self.mapper = Some(mapper);
+        return Ok(());
+    }
+
+    pub fn ppu_bus(&mut self) -> Option<&mut PpuBus> {
+        return self.ppu_bus.as_mut();
+    }
+
+    pub fn save_ram(&self) -> Option<Vec<u8>> {
+        return self.mapper.as_ref()?.borrow().save_ram().map(|ram| ram.to_vec());
+    }
+
+    pub fn load_save_ram(&mut self, data: &[u8]) {
+        if let Some(mapper) = &self.mapper {
+            mapper.borrow_mut().load_save_ram(data);
+        }
+    }
+
+    // Every read and write is one cpu cycle
+    fn tick_mapper(&mut self) {
+        if let Some(mapper) = &self.mapper {
+            mapper.borrow_mut().cpu_cycle();
+        }
+    }
+
+    pub fn tv_system(&self) -> TvSystem {
+        return self.tv_system;
+    }
+
+    pub fn oam(&self) -> &[u8] {
+        return &self.oam;
+    }
+
+    // Called by the APU when the DMC needs its next sample byte, see Dma::request_dmc
+    pub fn request_dmc(&mut self, addr: u16, at: u64) {
+        self.dma.request_dmc(addr, at);
+    }
+
+    pub fn take_dmc_sample(&mut self) -> Option<u8> {
+        return self.dma.take_dmc_sample();
+    }
+
+    pub fn set_joy_lines(&mut self, port: usize, lines: u8) {
+        self.joy_lines[port] = lines;
+    }
+
+    pub fn open_bus(&self) -> u8 {
+        return self.open_bus;
+    }
+
+    fn write_ppu(&mut self, reg: u16, val: u8) {
+        self.ppu_regs[reg as usize] = val;
+        match reg {
+            OAM_ADDR_REG => self.oam_addr = val,
+            OAM_DATA_REG => {
+                self.oam[self.oam_addr as usize] = val;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            },
+            _ => {},
+        }
+    }
+
+    // Only $4015-$4017 are readable, the rest of $4000-$4017 is write only
+    fn peek_io(&self, reg: u16) -> u8 {
+        return match reg {
+            // The status bits come from the APU, they read as 0 until there is one
+            APU_STATUS => self.open_bus & APU_STATUS_OPEN_BITS,
+            JOY1 | JOY2 => (self.joy_lines[(reg - JOY1) as usize] & !JOY_OPEN_BITS) | (self.open_bus & JOY_OPEN_BITS),
+            _ => self.open_bus,
+        };
+    }
+}
+
+impl Default for NesBus {
+    fn default() -> Self {
+        return NesBus::new();
+    }
+}
+
+impl Bus for NesBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.tick_mapper();
+        let val = self.peek(addr);
+        // $4015 is answered inside the 2A03, the external data bus keeps its old value
+        if decode(addr) != Region::Io(APU_STATUS) {
+            self.open_bus = val;
+        }
+        return val;
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        self.tick_mapper();
+        self.open_bus = val;
+        match decode(addr) {
+            Region::Ram(offset) => self.ram[offset as usize] = val,
+            Region::Ppu(reg) => self.write_ppu(reg, val),
+            Region::Io(reg) => {
+                self.io_regs[reg as usize] = val;
+                if addr == OAM_DMA {
+                    self.dma.start_oam(val);
+                }
+            },
+            // Test mode is disabled on retail units, the writes go nowhere
+            Region::Test(_) => {},
+            Region::Cartridge(offset) => match &self.mapper {
+                Some(mapper) => mapper.borrow_mut().cpu_write(addr, val),
+                None => self.cart[offset as usize] = val,
+            },
+        };
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        return match decode(addr) {
+            Region::Ram(offset) => self.ram[offset as usize],
+            Region::Ppu(OAM_DATA_REG) => self.oam[self.oam_addr as usize],
+            Region::Ppu(reg) => self.ppu_regs[reg as usize],
+            Region::Io(reg) => self.peek_io(reg),
+            // Test mode is disabled on retail units, nothing answers here
+            Region::Test(_) => self.open_bus,
+            Region::Cartridge(offset) => match &self.mapper {
+                Some(mapper) => mapper.borrow().cpu_read(addr).unwrap_or(self.open_bus),
+                None => self.cart[offset as usize],
+            },
+        };
+    }
+
+    // No mapper cycle, open bus or DMA. The write only registers and board registers
+    // ignore it, CHR RAM is poked through the PPU bus
+    fn poke(&mut self, addr: u16, val: u8) {
+        match decode(addr) {
+            Region::Ram(offset) => self.ram[offset as usize] = val,
+            Region::Cartridge(offset) => match &self.mapper {
+                Some(mapper) => mapper.borrow_mut().cpu_poke(addr, val),
+                None => self.cart[offset as usize] = val,
+            },
+            Region::Ppu(_) | Region::Io(_) | Region::Test(_) => {},
+        };
+    }
+
+    // Transfers go through read and write like the cpu's own accesses, so OAM DMA
+    // lands in OAM through $2004. The halted cpu keeps repeating its read while DMA
+    // isn't using the bus, which is how a DMC fetch double-reads $4016 or $2007
+    fn dma_cycle(&mut self, cycle: u64, addr: u16) -> bool {
+        let access = match self.dma.next(cycle) {
+            Some(access) => access,
+            None => return false,
+        };
+        match access {
+            DmaCycle::OamRead(addr) => {
+                let val = self.read(addr);
+                self.dma.oam_loaded(val);
+            },
+            DmaCycle::OamWrite(val) => self.write(OAM_DATA, val),
+            DmaCycle::DmcRead(addr) => {
+                let val = self.read(addr);
+                self.dma.dmc_loaded(val);
+            },
+            DmaCycle::Halt | DmaCycle::Dummy | DmaCycle::Align => { self.read(addr); },
+        }
+        return true;
+    }
+
+    fn irq(&self) -> bool {
+        return match &self.mapper {
+            Some(mapper) => mapper.borrow().irq(),
+            None => false,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::cpu::Cpu;
+    use crate::hardware::debug::DebugUtils;
+    use crate::hardware::error::CartridgeError;
+    use crate::hardware::interrupts::Interrupts;
+    use crate::hardware::variant::CpuVariant;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(0x0000), Region::Ram(0x0000));
+        assert_eq!(decode(0x1FFF), Region::Ram(0x07FF));
+        assert_eq!(decode(0x2000), Region::Ppu(0));
+        assert_eq!(decode(0x3FFF), Region::Ppu(7));
+        assert_eq!(decode(0x4000), Region::Io(0x00));
+        assert_eq!(decode(0x4017), Region::Io(0x17));
+        assert_eq!(decode(0x4018), Region::Test(0));
+        assert_eq!(decode(0x401F), Region::Test(7));
+        assert_eq!(decode(0x4020), Region::Cartridge(0));
+        assert_eq!(decode(0xFFFF), Region::Cartridge(0xBFDF));
+    }
+
+    #[test]
+    fn test_ram_mirrors() {
+        let mut bus = NesBus::new();
+        bus.write(0x0123, 0x42);
+        assert_eq!(bus.read(0x0923), 0x42);
+        assert_eq!(bus.read(0x1123), 0x42);
+        assert_eq!(bus.read(0x1923), 0x42);
+        bus.write(0x1FFF, 0x24);
+        assert_eq!(bus.read(0x07FF), 0x24);
+    }
+
+    #[test]
+    fn test_ppu_register_mirrors() {
+        let mut bus = NesBus::new();
+        bus.write(0x3FFE, 0x11);
+        assert_eq!(bus.peek(0x2006), 0x11);
+        assert_eq!(bus.peek(0x2E0E), 0x11);
+    }
+
+    #[test]
+    fn test_cartridge_not_mirrored() {
+        let mut bus = NesBus::new();
+        bus.write(0x8000, 0x03);
+        assert_eq!(bus.peek(0x8000), 0x03);
+        assert_eq!(bus.peek(0xC000), 0x00);
+        assert_eq!(bus.peek(0x0000), 0x00);
+    }
+
+    #[test]
+    fn test_open_bus_tracks_reads_and_writes() {
+        let mut bus = NesBus::new();
+        bus.write(0x0010, 0x5A);
+        assert_eq!(bus.open_bus(), 0x5A);
+        bus.write(0x0011, 0xC3);
+        bus.read(0x0010);
+        assert_eq!(bus.open_bus(), 0x5A);
+    }
+
+    #[test]
+    fn test_open_bus_unmapped() {
+        let mut bus = NesBus::new();
+        bus.write(0x0010, 0x5A);
+        bus.read(0x0010);
+        assert_eq!(bus.read(0x4018), 0x5A);
+        assert_eq!(bus.read(0x401F), 0x5A);
+        // Write only APU registers
+        assert_eq!(bus.read(0x4000), 0x5A);
+        assert_eq!(bus.read(0x4014), 0x5A);
+    }
+
+    #[test]
+    fn test_open_bus_joy_ports() {
+        let mut bus = NesBus::new();
+        bus.set_joy_lines(0, 0x01);
+        bus.set_joy_lines(1, 0x1F);
+        bus.write(0x0000, 0x40);
+        bus.read(0x0000);
+        assert_eq!(bus.read(0x4016), 0x41);
+        bus.write(0x0000, 0xA0);
+        bus.read(0x0000);
+        assert_eq!(bus.read(0x4017), 0xBF);
+    }
+
+    #[test]
+    fn test_open_bus_apu_status() {
+        let mut bus = NesBus::new();
+        bus.write(0x4015, 0x0F);
+        assert_eq!(bus.read(0x4015) & !0x20, 0x00);
+        bus.write(0x0000, 0xFF);
+        bus.read(0x0000);
+        assert_eq!(bus.read(0x4015), 0x20);
+        assert_eq!(bus.open_bus(), 0xFF);
+    }
+
+    #[test]
+    fn test_oam_data() {
+        let mut bus = NesBus::new();
+        bus.write(0x2003, 0xFF);
+        bus.write(0x2004, 0x11);
+        bus.write(0x2004, 0x22);
+        assert_eq!(bus.oam()[0xFF], 0x11);
+        assert_eq!(bus.oam()[0x00], 0x22);
+        bus.write(0x2003, 0xFF);
+        assert_eq!(bus.read(0x2004), 0x11);
+    }
+
+    #[test]
+    fn test_oam_dma() {
+        let mut bus = NesBus::new();
+        for i in 0..=0xFF {
+            bus.write(0x0300 + i, i as u8);
+        }
+        bus.write(0x2003, 0x10);
+        bus.write(0x4014, 0x03);
+        let mut cycles = 0;
+        while bus.dma_cycle(cycles, 0x0000) {
+            cycles += 1;
+        }
+        assert_eq!(cycles, 513);
+        assert_eq!(bus.oam()[0x10], 0x00);
+        assert_eq!(bus.oam()[0x0F], 0xFF);
+        assert!(!bus.dma_cycle(cycles, 0x0000));
+    }
+
+    #[test]
+    fn test_dmc_dma() {
+        let mut bus = NesBus::new();
+        bus.write(0xC000, 0x5A);
+        bus.request_dmc(0xC000, 4);
+        assert!(!bus.dma_cycle(3, 0x0000));
+        assert!(bus.dma_cycle(4, 0x0000));
+        assert!(bus.dma_cycle(5, 0x0000));
+        assert_eq!(bus.take_dmc_sample(), None);
+        assert!(bus.dma_cycle(6, 0x0000));
+        assert!(bus.dma_cycle(7, 0x0000));
+        assert!(!bus.dma_cycle(8, 0x0000));
+        assert_eq!(bus.take_dmc_sample(), Some(0x5A));
+    }
+
+    #[test]
+    fn test_dma_halt_repeats_cpu_read() {
+        let mut bus = NesBus::new();
+        bus.write(0x0010, 0x77);
+        bus.write(0xC000, 0x5A);
+        bus.request_dmc(0xC000, 4);
+        bus.write(0x0000, 0x00);
+        assert!(bus.dma_cycle(4, 0x0010));
+        assert_eq!(bus.open_bus(), 0x77);
+        assert!(bus.dma_cycle(5, 0x0010));
+        assert!(bus.dma_cycle(6, 0x0010));
+        assert_eq!(bus.open_bus(), 0x77);
+        assert!(bus.dma_cycle(7, 0x0010));
+        assert_eq!(bus.open_bus(), 0x5A);
+    }
+
+    // Two 16 KiB PRG banks holding their low address byte, `flags` fills bytes 6-15
+    fn rom(flags: [u8; 10]) -> Vec<u8> {
+        let mut rom = vec![b'N', b'E', b'S', 0x1A, 2, 0];
+        rom.extend(flags);
+        if flags[0] & 0x04 != 0 {
+            rom.extend(vec![0x77; 512]);
+        }
+        rom.extend((0..0x8000).map(|i| i as u8));
+        return rom;
+    }
+
+    #[test]
+    fn test_insert_cartridge() {
+        let mut bus = NesBus::new();
+        bus.insert_cartridge(Cartridge::from_bytes(&rom([0x04, 0, 0, 0, 0, 0, 0, 0, 0, 0])).unwrap()).unwrap();
+        assert_eq!(bus.read(0x8001), 0x01);
+        assert_eq!(bus.read(0xC0FF), 0xFF);
+        assert_eq!(bus.read(0x7000), 0x77);
+        assert_eq!(bus.read(0x7200), 0x00);
+        bus.write(0x8001, 0x42);
+        assert_eq!(bus.read(0x8001), 0x01);
+        bus.write(0x6000, 0x42);
+        assert_eq!(bus.read(0x6000), 0x42);
+        assert_eq!(bus.tv_system(), TvSystem::Ntsc);
+    }
+
+    #[test]
+    fn test_cartridge_open_bus() {
+        let mut bus = NesBus::new();
+        bus.insert_cartridge(Cartridge::from_bytes(&rom([0; 10])).unwrap()).unwrap();
+        bus.read(0x8042);
+        assert_eq!(bus.read(0x5000), 0x42);
+    }
+
+    #[test]
+    fn test_unsupported_mapper() {
+        let mut bus = NesBus::new();
+        let cartridge = Cartridge::from_bytes(&rom([0xF0, 0xF0, 0, 0, 0, 0, 0, 0, 0, 0])).unwrap();
+        assert_eq!(bus.insert_cartridge(cartridge), Err(CartridgeError::UnsupportedMapper(0xFF)));
+        bus.write(0x8000, 0x42);
+        assert_eq!(bus.read(0x8000), 0x42);
+    }
+
+    #[test]
+    fn test_ppu_bus_reaches_cartridge() {
+        let mut bus = NesBus::new();
+        assert!(bus.ppu_bus().is_none());
+        bus.insert_cartridge(Cartridge::from_bytes(&rom([0; 10])).unwrap()).unwrap();
+        let ppu_bus = bus.ppu_bus().unwrap();
+        // No CHR ROM so the board has CHR RAM
+        ppu_bus.write(0x0123, 0x42);
+        assert_eq!(ppu_bus.read(0x0123), 0x42);
+    }
+
+    #[test]
+    fn test_save_ram() {
+        let mut bus = NesBus::new();
+        assert_eq!(bus.save_ram(), None);
+        bus.insert_cartridge(Cartridge::from_bytes(&rom([0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0])).unwrap()).unwrap();
+        bus.load_save_ram(&[0x42]);
+        assert_eq!(bus.read(0x6000), 0x42);
+        bus.write(0x7FFF, 0x24);
+        let ram = bus.save_ram().unwrap();
+        assert_eq!((ram.len(), ram[0x1FFF]), (0x2000, 0x24));
+    }
+
+    #[test]
+    fn test_run_from_cartridge() {
+        // LDA #$42, STA $0200, JMP $C005 from the reset vector at $FFFC
+        let mut bytes = rom([0; 10]);
+        let prg = 16 + 0x4000;
+        bytes[prg..prg + 8].copy_from_slice(&[0xA9, 0x42, 0x8D, 0x00, 0x02, 0x4C, 0x05, 0xC0]);
+        bytes[prg + 0x3FFC..prg + 0x3FFE].copy_from_slice(&[0x00, 0xC0]);
+        let mut bus = NesBus::new();
+        bus.insert_cartridge(Cartridge::from_bytes(&bytes).unwrap()).unwrap();
+        let mut cpu = Cpu::with_bus(CpuVariant::Ricoh2A03, Box::new(bus));
+        cpu.reset();
+        for _ in 0..3 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.peek(0x0200), 0x42);
+        assert_eq!(cpu.pc, 0xC005);
+    }
+
+    #[test]
+    fn test_cartridge_without_prg_ram() {
+        // NES 2.0 with no PRG RAM, for PAL machines
+        let mut bus = NesBus::new();
+        bus.insert_cartridge(Cartridge::from_bytes(&rom([0, 0x08, 0, 0, 0, 0, 0x01, 0, 0, 0])).unwrap()).unwrap();
+        bus.write(0x6000, 0x42);
+        bus.read(0x0000);
+        assert_eq!(bus.read(0x6000), 0x00);
+        assert_eq!(bus.tv_system(), TvSystem::Pal);
+    }
+
+    #[test]
+    fn test_poke_has_no_side_effects() {
+        // MMC1 with 16 KiB PRG banks that start with their bank number
+        let mut bytes = rom([0x12, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes[16] = 0xA0;
+        bytes[16 + 0x4000] = 0xA1;
+        let mut bus = NesBus::new();
+        bus.insert_cartridge(Cartridge::from_bytes(&bytes).unwrap()).unwrap();
+        bus.write(0x0000, 0x5A);
+        bus.poke(0x4014, 0x03);
+        assert!(!bus.dma_cycle(0, 0x0000));
+        // Four pokes would leave one bit to go before the PRG bank switches
+        for _ in 0..4 {
+            bus.poke(0x8000, 0x01);
+        }
+        bus.write(0xE000, 0x01);
+        assert_eq!(bus.read(0x8000), 0xA0);
+        bus.write(0x0000, 0x5A);
+        bus.poke(0x0010, 0x42);
+        bus.poke(0x6000, 0x24);
+        assert_eq!(bus.open_bus(), 0x5A);
+        assert_eq!(bus.peek(0x0810), 0x42);
+        assert_eq!(bus.peek(0x6000), 0x24);
+    }
+
+    #[test]
+    fn test_peek_leaves_open_bus() {
+        let mut bus = NesBus::new();
+        bus.write(0x0000, 0x12);
+        bus.write(0x0001, 0x34);
+        bus.peek(0x0000);
+        assert_eq!(bus.open_bus(), 0x34);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::hardware::cartridge::NAMETABLE_SIZE;
+use crate::hardware::mapper::SharedMapper;
+
+// Two nametables in the console plus two more for four screen boards
+const VRAM_SIZE: usize = 4 * NAMETABLE_SIZE;
+const PALETTE_SIZE: usize = 0x20;
+const NAMETABLE_START: u16 = 0x2000;
+const A12: u16 = 0x1000;
+
+// The PPU's 14 bit address space. Pattern tables belong to the cartridge,
+// nametables are console RAM mirrored the way the board is wired and palette RAM
+// is inside the PPU
+pub struct PpuBus {
+    mapper: SharedMapper,
+    vram: [u8; VRAM_SIZE],
+    palette: [u8; PALETTE_SIZE],
+    // A12 on the last access, the mapper hears about rising edges
+    a12: bool,
+}
+
+// The backdrop entries of the sprite palettes are the background ones
+fn palette_index(addr: u16) -> usize {
+    let index = (addr as usize) & (PALETTE_SIZE - 1);
+    if index & 0x13 == 0x10 {
+        return index & 0x0F;
+    }
+    return index;
+}
+
+impl PpuBus {
+    pub fn new(mapper: SharedMapper) -> Self {
+        return PpuBus {
+            mapper,
+            vram: [0; VRAM_SIZE],
+            palette: [0; PALETTE_SIZE],
+            a12: false,
+        };
+    }
+
+    pub fn read(&mut self, addr: u16) -> u8 {
+        let addr = addr & 0x3FFF;
+        self.watch_a12(addr);
+        return self.peek(addr);
+    }
+
+    pub fn write(&mut self, addr: u16, val: u8) {
+        let addr = addr & 0x3FFF;
+        self.watch_a12(addr);
+        self.poke(addr, val);
+    }
+
+    // A write without the A12 edge, for tools
+    pub fn poke(&mut self, addr: u16, val: u8) {
+        let addr = addr & 0x3FFF;
+        match addr {
+            0..=0x1FFF => self.mapper.borrow_mut().ppu_write(addr, val),
+            NAMETABLE_START..=0x3EFF => {
+                let offset = self.mapper.borrow().mirroring().nametable_offset(addr);
+                self.vram[offset] = val;
+            },
+            _ => self.palette[palette_index(addr)] = val,
+        }
+    }
+
+    pub fn peek(&self, addr: u16) -> u8 {
+        let addr = addr & 0x3FFF;
+        return match addr {
+            0..=0x1FFF => self.mapper.borrow().ppu_read(addr),
+            NAMETABLE_START..=0x3EFF => self.vram[self.mapper.borrow().mirroring().nametable_offset(addr)],
+            _ => self.palette[palette_index(addr)],
+        };
+    }
+
+    fn watch_a12(&mut self, addr: u16) {
+        let a12 = addr & A12 != 0;
+        if a12 && !self.a12 {
+            self.mapper.borrow_mut().ppu_a12_rise();
+        } else if !a12 && self.a12 {
+            self.mapper.borrow_mut().ppu_a12_fall();
+        }
+        self.a12 = a12;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::hardware::cartridge::Mirroring;
+    use crate::hardware::mapper::Mapper;
+
+    // CHR RAM and a count of A12 rises
+    struct TestMapper {
+        chr: Vec<u8>,
+        mirroring: Mirroring,
+        rises: Rc<RefCell<u32>>,
+    }
+
+    impl Mapper for TestMapper {
+        fn cpu_read(&self, _addr: u16) -> Option<u8> {
+            return None;
+        }
+
+        fn cpu_write(&mut self, _addr: u16, _val: u8) {}
+
+        fn ppu_read(&self, addr: u16) -> u8 {
+            return self.chr[addr as usize];
+        }
+
+        fn ppu_write(&mut self, addr: u16, val: u8) {
+            self.chr[addr as usize] = val;
+        }
+
+        fn mirroring(&self) -> Mirroring {
+            return self.mirroring;
+        }
+
+        fn ppu_a12_rise(&mut self) {
+            *self.rises.borrow_mut() += 1;
+        }
+    }
+
+    fn ppu_bus(mirroring: Mirroring) -> (PpuBus, Rc<RefCell<u32>>) {
+        let rises = Rc::new(RefCell::new(0));
+        let mapper = TestMapper { chr: vec![0; 0x2000], mirroring, rises: rises.clone() };
+        return (PpuBus::new(Rc::new(RefCell::new(Box::new(mapper)))), rises);
+    }
+
+    #[test]
+    fn test_pattern_tables() {
+        let (mut bus, _) = ppu_bus(Mirroring::Vertical);
+        bus.write(0x1234, 0x42);
+        assert_eq!(bus.read(0x1234), 0x42);
+        assert_eq!(bus.read(0x5234), 0x42);
+    }
+
+    #[test]
+    fn test_nametable_mirroring() {
+        let (mut bus, _) = ppu_bus(Mirroring::Vertical);
+        bus.write(0x2001, 0x42);
+        assert_eq!(bus.read(0x2801), 0x42);
+        assert_eq!(bus.read(0x2401), 0x00);
+        assert_eq!(bus.read(0x3001), 0x42);
+
+        let (mut bus, _) = ppu_bus(Mirroring::Horizontal);
+        bus.write(0x2001, 0x42);
+        assert_eq!(bus.read(0x2401), 0x42);
+        assert_eq!(bus.read(0x2801), 0x00);
+    }
+
+    #[test]
+    fn test_palette_mirrors() {
+        let (mut bus, _) = ppu_bus(Mirroring::Vertical);
+        bus.write(0x3F10, 0x0F);
+        assert_eq!(bus.read(0x3F00), 0x0F);
+        bus.write(0x3F05, 0x16);
+        assert_eq!(bus.read(0x3FE5), 0x16);
+        assert_eq!(bus.read(0x3F15), 0x00);
+    }
+
+    #[test]
+    fn test_a12_rise() {
+        let (mut bus, rises) = ppu_bus(Mirroring::Vertical);
+        bus.read(0x0000);
+        bus.read(0x1000);
+        bus.read(0x1008);
+        assert_eq!(*rises.borrow(), 1);
+        bus.read(0x0000);
+        bus.write(0x1000, 0);
+        assert_eq!(*rises.borrow(), 2);
+        bus.peek(0x0000);
+        bus.peek(0x1000);
+        bus.poke(0x0000, 0x42);
+        bus.poke(0x1000, 0x42);
+        assert_eq!(*rises.borrow(), 2);
+        assert_eq!(bus.peek(0x1000), 0x42);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::utils::check_bit;
 
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Registers {
     A, X, Y
 }
@@ -30,7 +31,7 @@ impl Flags {
         }
     }
 
-    pub fn to_u8(&self) -> u8 {
+    pub fn to_u8(self) -> u8 {
         let mut res = 0;
 
         let flags = [
@@ -53,20 +54,12 @@ impl Flags {
         return res as u8;
     }
 
-    pub fn set_breaks(&mut self) {
-        self.break1 = true;
-        self.break2 = true;
-    }
-
-    pub fn reset_breaks(&mut self) {
-        self.break1 = false;
-        self.break2 = false;
-    }
-
-    pub fn set_for_cmp(&mut self, reg: u8, val: u8) {
-        self.carry = reg >= val;
-        self.zero = reg == val;
-        self.negative = reg < val;
+    // Restore from a byte pulled off the stack, bits 4 and 5 aren't real flags so they're skipped
+    pub fn set_from_stack(&mut self, val: u8) {
+        let (break1, break2) = (self.break1, self.break2);
+        *self = Flags::from(val);
+        self.break1 = break1;
+        self.break2 = break2;
     }
 }
 
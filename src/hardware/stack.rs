@@ -0,0 +1,268 @@
+use crate::hardware::cpu::Cpu;
+#[cfg(test)]
+use crate::hardware::debug::DebugUtils;
+use crate::hardware::interrupts::{Interrupts, IRQ_VECTOR};
+use crate::hardware::memory::MemoryOps;
+use crate::utils::{combine_bytes, split_bytes};
+
+// The stack grows down from STACK_BASE + sp
+pub const STACK_BASE: u16 = 0x0100;
+
+// Everything that goes through page one: pushes and pulls plus the
+// JSR/RTS and BRK/RTI pairs, which have to agree byte for byte
+pub trait StackOps {
+    fn push_stack(&mut self, item: u8);
+    fn pop_stack(&mut self) -> u8;
+    #[cfg(test)]
+    fn peek_stack(&self) -> u8;
+    fn save_pc(&mut self);
+    fn pull_pc(&mut self);
+    fn save_status(&mut self, b_flag: bool);
+    fn pull_status(&mut self);
+    fn jsr(&mut self);
+    fn rts(&mut self);
+    fn brk(&mut self);
+    fn rti(&mut self);
+}
+
+impl StackOps for Cpu {
+
+    // sp wraps around inside page one like the real stack pointer
+    #[cfg(test)]
+    fn peek_stack(&self) -> u8 {
+        return self.peek(STACK_BASE + self.sp.wrapping_add(1) as u16);
+    }
+
+    fn push_stack(&mut self, item: u8) {
+        self.write(STACK_BASE + self.sp as u16, item);
+        self.sp = self.sp.wrapping_sub(1);
+    }
+
+    fn pop_stack(&mut self) -> u8 {
+        self.sp = self.sp.wrapping_add(1);
+        return self.read(STACK_BASE + self.sp as u16);
+    }
+
+    // High byte first, so the low byte ends up at the lower address
+    fn save_pc(&mut self) {
+        let (upper, lower) = split_bytes(self.pc);
+        self.push_stack(upper);
+        self.push_stack(lower);
+    }
+
+    fn pull_pc(&mut self) {
+        let lower = self.pop_stack();
+        let upper = self.pop_stack();
+        self.pc = combine_bytes(upper.into(), lower.into());
+    }
+
+    // Bit 5 is always set on the stack, B is only set when pushed by BRK or PHP
+    fn save_status(&mut self, b_flag: bool) {
+        let mut pushed = self.flags;
+        pushed.break1 = true;
+        pushed.break2 = b_flag;
+        self.push_stack(pushed.to_u8());
+    }
+
+    // Bits 4 and 5 only exist on the stack, PLP and RTI leave the cpu's copies alone
+    fn pull_status(&mut self) {
+        let popped = self.pop_stack();
+        self.flags.set_from_stack(popped);
+    }
+
+    // The high byte of the target is only fetched after the pushes, with pc on it,
+    // so the pushed return address is JSR + 2, one short of the next instruction
+    fn jsr(&mut self) {
+        let lower = self.fetch_next_byte();
+        self.read(STACK_BASE + self.sp as u16);
+        self.pc = self.pc.wrapping_add(1);
+        self.save_pc();
+        let upper = self.read(self.pc);
+        self.pc = combine_bytes(upper.into(), lower.into());
+    }
+
+    // The pulled address is read once more before pc steps past it
+    fn rts(&mut self) {
+        self.read(self.pc.wrapping_add(1));
+        self.read(STACK_BASE + self.sp as u16);
+        self.pull_pc();
+        self.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    // BRK is followed by a padding byte, the return address is BRK + 2
+    fn brk(&mut self) {
+        self.read(self.pc.wrapping_add(1));
+        self.pc = self.pc.wrapping_add(2);
+        self.enter_interrupt(IRQ_VECTOR, true);
+    }
+
+    // Unlike RTS the pulled address is the next instruction, nothing is added
+    fn rti(&mut self) {
+        self.read(self.pc.wrapping_add(1));
+        self.read(STACK_BASE + self.sp as u16);
+        self.pull_status();
+        self.pull_pc();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::instruction::Ops;
+    use crate::hardware::registers::Flags;
+
+    fn stack_bytes(cpu: &Cpu, count: u16) -> Vec<u8> {
+        return (1..=count).map(|ind| cpu.peek(STACK_BASE + cpu.sp as u16 + ind)).collect();
+    }
+
+    #[test]
+    fn test_stack_page() {
+        let mut cpu = Cpu::new();
+        cpu.push_stack(0x42);
+        assert_eq!(cpu.peek(0x01FF), 0x42);
+        assert_eq!(cpu.peek(0xFF), 0);
+        assert_eq!(cpu.sp, 0xFE);
+        assert_eq!(cpu.pop_stack(), 0x42);
+        assert_eq!(cpu.sp, 0xFF);
+    }
+
+    #[test]
+    fn test_stack_wrap() {
+        let mut cpu = Cpu::new();
+        cpu.sp = 0x00;
+        cpu.push_stack(0x42);
+        assert_eq!(cpu.peek(0x0100), 0x42);
+        assert_eq!(cpu.sp, 0xFF);
+        assert_eq!(cpu.pop_stack(), 0x42);
+        assert_eq!(cpu.sp, 0x00);
+    }
+
+    #[test]
+    fn test_save_pc() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x3456;
+        cpu.save_pc();
+        assert_eq!(cpu.peek(0x01FF), 0x34);
+        assert_eq!(cpu.peek(0x01FE), 0x56);
+        assert_eq!(cpu.sp, 0xFD);
+    }
+
+    #[test]
+    fn test_pull_pc() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x4567;
+        cpu.save_pc();
+        cpu.pc = 0x3479;
+        cpu.pull_pc();
+        assert_eq!(cpu.pc, 0x4567);
+        assert_eq!(cpu.sp, 0xFF);
+    }
+
+    #[test]
+    fn test_jsr_stack() {
+        let mut cpu = Cpu::new();
+        cpu.sp = 0xFD;
+        cpu.pc = 0x0600;
+        cpu.poke(0x0600, Ops::Jsr as u8);
+        cpu.poke(0x0601, 0x34);
+        cpu.poke(0x0602, 0x12);
+        assert_eq!(cpu.step().unwrap(), 6);
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.sp, 0xFB);
+        assert_eq!(cpu.peek(0x01FD), 0x06);
+        assert_eq!(cpu.peek(0x01FC), 0x02);
+    }
+
+    #[test]
+    fn test_jsr_rts() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x0600;
+        cpu.poke(0x0600, Ops::Jsr as u8);
+        cpu.poke(0x0601, 0x00);
+        cpu.poke(0x0602, 0x07);
+        cpu.poke(0x0603, Ops::IncX as u8);
+        cpu.poke(0x0700, Ops::Rts as u8);
+        cpu.step().unwrap();
+        assert_eq!(cpu.step().unwrap(), 6);
+        assert_eq!(cpu.pc, 0x0603);
+        assert_eq!(cpu.sp, 0xFF);
+        cpu.step().unwrap();
+        assert_eq!(cpu.regx, 1);
+    }
+
+    #[test]
+    fn test_rts_does_not_pull_status() {
+        let mut cpu = Cpu::new();
+        cpu.push_stack(0x12);
+        cpu.push_stack(0x33);
+        cpu.flags.carry = true;
+        cpu.exec_instruction(Ops::Rts).unwrap();
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.sp, 0xFF);
+        assert!(cpu.flags.carry);
+    }
+
+    #[test]
+    fn test_brk_stack() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0xFFFE, 0x00);
+        cpu.poke(0xFFFF, 0x90);
+        cpu.pc = 0x0600;
+        cpu.flags.carry = true;
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x9000);
+        assert_eq!(cpu.sp, 0xFC);
+        // P, then pc low, then pc high, reading up from sp
+        assert_eq!(stack_bytes(&cpu, 3), vec![0x31, 0x02, 0x06]);
+        assert!(cpu.flags.inter_disable);
+    }
+
+    #[test]
+    fn test_brk_rti() {
+        let mut cpu = Cpu::new();
+        cpu.poke(0xFFFE, 0x00);
+        cpu.poke(0xFFFF, 0x90);
+        cpu.poke(0x9000, Ops::Rti as u8);
+        cpu.pc = 0x0600;
+        cpu.flags.carry = true;
+        cpu.step().unwrap();
+        cpu.flags.carry = false;
+        assert_eq!(cpu.step().unwrap(), 6);
+        assert_eq!(cpu.pc, 0x0602);
+        assert_eq!(cpu.sp, 0xFF);
+        assert!(cpu.flags.carry);
+        assert!(!cpu.flags.inter_disable);
+    }
+
+    #[test]
+    fn test_rti_ignores_break_bits() {
+        let mut cpu = Cpu::new();
+        cpu.push_stack(0x12);
+        cpu.push_stack(0x34);
+        cpu.push_stack(0xFF);
+        cpu.exec_instruction(Ops::Rti).unwrap();
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.flags.to_u8(), 0xCF);
+    }
+
+    #[test]
+    fn test_plp_ignores_break_bits() {
+        let mut cpu = Cpu::new();
+        cpu.push_stack(0x30);
+        cpu.exec_instruction(Ops::Plp).unwrap();
+        assert_eq!(cpu.flags.to_u8(), 0x00);
+    }
+
+    #[test]
+    fn test_php_plp_round_trip() {
+        let mut cpu = Cpu::new();
+        cpu.flags.negative = true;
+        cpu.flags.decimal = true;
+        cpu.exec_instruction(Ops::Php).unwrap();
+        assert_eq!(cpu.peek_stack(), 0xB8);
+        cpu.flags = Flags::new();
+        cpu.exec_instruction(Ops::Plp).unwrap();
+        assert_eq!(cpu.flags.to_u8(), 0x88);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::hardware::cpu::Cpu;
+use crate::hardware::instruction::{Instruction, Mnemonic};
+
+// A taken branch costs one more cycle, and one more again if the target is on a different page
+pub fn branch_penalty(taken: bool, page_crossed: bool) -> u8 {
+    if !taken {
+        return 0;
+    }
+    return if page_crossed { 2 } else { 1 };
+}
+
+pub fn crosses_page(from: u16, to: u16) -> bool {
+    return (from & 0xFF00) != (to & 0xFF00);
+}
+
+pub trait Timing {
+    fn instruction_cycles(&self, inst: &Instruction) -> u8;
+    fn add_cycles(&mut self, cycles: u8);
+    fn decimal_penalty(&self, mnemonic: Mnemonic) -> bool;
+    fn dma_stall(&mut self, addr: u16) -> bool;
+    fn run_dma(&mut self);
+}
+
+impl Timing for Cpu {
+    // Cycles for the instruction just executed, using the page cross and branch
+    // state recorded while it ran
+    fn instruction_cycles(&self, inst: &Instruction) -> u8 {
+        let mut cycles = inst.cycles;
+        if self.branch_taken {
+            cycles += branch_penalty(true, self.page_crossed);
+        } else if self.page_crossed && inst.page_penalty {
+            cycles += 1;
+        }
+        if self.decimal_penalty(inst.mnemonic) {
+            cycles += 1;
+        }
+        return cycles;
+    }
+
+    // The 65C02 spends a cycle fixing up flags after a decimal add or subtract
+    fn decimal_penalty(&self, mnemonic: Mnemonic) -> bool {
+        return self.variant.is_cmos() && self.decimal_mode() && matches!(mnemonic, Mnemonic::Adc | Mnemonic::Sbc);
+    }
+
+    // One cycle halted for DMA on a read of addr, if the bus has any pending. DMA can
+    // only halt the cpu on a read, the read is made again once it lets go
+    fn dma_stall(&mut self, addr: u16) -> bool {
+        if !self.bus.dma_cycle(self.cycles, addr) {
+            return false;
+        }
+        self.cycles += 1;
+        return true;
+    }
+
+    // Between instructions the cpu is halted on its opcode fetch
+    fn run_dma(&mut self) {
+        while self.dma_stall(self.pc) {}
+    }
+
+    fn add_cycles(&mut self, cycles: u8) {
+        self.cycles_taken = cycles;
+        self.cycles += cycles as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::instruction::{Ops, lookup};
+
+    fn cycles(op: Ops) -> u8 {
+        return lookup(op as u8).unwrap().cycles;
+    }
+
+    fn page_penalty(op: Ops) -> bool {
+        return lookup(op as u8).unwrap().page_penalty;
+    }
+
+    #[test]
+    fn test_base_cycles() {
+        assert_eq!(cycles(Ops::LdaI), 2);
+        assert_eq!(cycles(Ops::StaAbsX), 5);
+        assert_eq!(cycles(Ops::IncAbsX), 7);
+        assert_eq!(cycles(Ops::Brk), 7);
+        assert_eq!(cycles(Ops::LdaIndY), 5);
+        assert_eq!(cycles(Ops::DcpIndY), 8);
+        assert_eq!(cycles(Ops::NopZpX14), 4);
+    }
+
+    #[test]
+    fn test_has_page_penalty() {
+        assert!(page_penalty(Ops::LdaAbsX));
+        assert!(page_penalty(Ops::AdcIndY));
+        assert!(!page_penalty(Ops::StaAbsX));
+        assert!(!page_penalty(Ops::AslAbsX));
+    }
+
+    #[test]
+    fn test_instruction_cycles() {
+        let mut cpu = Cpu::new();
+        let inst = lookup(Ops::LdaAbsX as u8).unwrap();
+        assert_eq!(cpu.instruction_cycles(inst), 4);
+        cpu.page_crossed = true;
+        assert_eq!(cpu.instruction_cycles(inst), 5);
+    }
+
+    #[test]
+    fn test_branch_penalty() {
+        assert_eq!(branch_penalty(false, true), 0);
+        assert_eq!(branch_penalty(true, false), 1);
+        assert_eq!(branch_penalty(true, true), 2);
+    }
+
+    #[test]
+    fn test_crosses_page() {
+        assert!(crosses_page(0x10FF, 0x1100));
+        assert!(!crosses_page(0x1000, 0x10FF));
+    }
+}
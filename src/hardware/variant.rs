@@ -0,0 +1,49 @@
+use crate::hardware::instruction::{Instruction, CMOS_OPCODES, OPCODES};
+
+// Which member of the 6502 family the core behaves as, fixed when the cpu is built
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CpuVariant {
+    // NES cpu, an NMOS 6502 with the decimal adder cut out. D can be set but does nothing
+    Ricoh2A03,
+    Nmos6502,
+    Cmos65C02,
+}
+
+impl CpuVariant {
+    pub fn has_decimal_mode(&self) -> bool {
+        return *self != CpuVariant::Ricoh2A03;
+    }
+
+    pub fn is_cmos(&self) -> bool {
+        return *self == CpuVariant::Cmos65C02;
+    }
+
+    // Decode an opcode byte with this variant's instruction set
+    pub fn lookup(&self, op: u8) -> Option<&'static Instruction> {
+        let table = match self {
+            CpuVariant::Cmos65C02 => &CMOS_OPCODES,
+            _ => &OPCODES,
+        };
+        return table[op as usize].as_ref();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_decimal_mode() {
+        assert!(!CpuVariant::Ricoh2A03.has_decimal_mode());
+        assert!(CpuVariant::Nmos6502.has_decimal_mode());
+        assert!(CpuVariant::Cmos65C02.has_decimal_mode());
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert!(CpuVariant::Ricoh2A03.lookup(0x02).unwrap().is_jam());
+        assert!(!CpuVariant::Cmos65C02.lookup(0x02).unwrap().is_jam());
+        assert!(CpuVariant::Nmos6502.lookup(0x8B).is_none());
+        assert!(CpuVariant::Cmos65C02.lookup(0x8B).is_some());
+    }
+}
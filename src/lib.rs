@@ -0,0 +1,8 @@
+// Functions end with an explicit return throughout
+#![allow(clippy::needless_return)]
+
+extern crate num_traits;
+extern crate num_derive;
+
+pub mod hardware;
+mod utils;
@@ -1,9 +1,37 @@
-extern crate num_traits;
-extern crate num_derive;
-
-mod hardware;
-mod utils;
+use std::env;
+use std::path::Path;
+use std::process;
+use nes_rs::hardware::cartridge::Cartridge;
+use nes_rs::hardware::cpu::Cpu;
+use nes_rs::hardware::debug::DebugUtils;
+use nes_rs::hardware::interrupts::Interrupts;
+use nes_rs::hardware::nes_bus::NesBus;
 
 fn main() {
-    println!("Hello, world!");
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: nes-rs <rom.nes>");
+            process::exit(2);
+        },
+    };
+    let cartridge = match Cartridge::load(Path::new(&path)) {
+        Ok(cartridge) => cartridge,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            process::exit(1);
+        },
+    };
+    println!("{}", cartridge.header);
+
+    let variant = cartridge.header.cpu_variant();
+    let mut bus = NesBus::new();
+    if let Err(err) = bus.insert_cartridge(cartridge) {
+        eprintln!("{}: {}", path, err);
+        process::exit(1);
+    }
+    println!("{:?} machine, cpu at {} Hz", bus.tv_system(), bus.tv_system().cpu_clock_hz());
+    let mut cpu = Cpu::with_bus(variant, Box::new(bus));
+    cpu.reset();
+    println!("{}", cpu.dump_registers());
 }